@@ -0,0 +1,58 @@
+#!/usr/bin/env python3
+"""Sample Build Studio plugin speaking JSON-RPC 2.0 over stdio, one message per line."""
+import json
+import os
+import sys
+import tarfile
+
+
+def send(message):
+    sys.stdout.write(json.dumps(dict(jsonrpc="2.0", **message)) + "\n")
+    sys.stdout.flush()
+
+
+def notify(method, **params):
+    send(dict(method=method, params=params))
+
+
+def build(request_id, params):
+    files = []
+    for root, dirs, names in os.walk(params["projectDir"]):
+        dirs[:] = [d for d in dirs if not d.startswith(".") and d != "builds"]
+        files.extend(os.path.join(root, name) for name in names)
+    notify("log", id=request_id, message=f"{len(files)} files in {params['projectDir']}")
+    return {"artifacts": []}
+
+
+def package(request_id, params):
+    output_dir = params["outputDir"]
+    archive = os.path.join(output_dir, "package.tar.gz")
+    names = [n for n in sorted(os.listdir(output_dir)) if n != "package.tar.gz"]
+    if not names:
+        notify("diagnostic", id=request_id, severity="warning", message=f"{output_dir} is empty")
+    with tarfile.open(archive, "w:gz") as tar:
+        for index, name in enumerate(names, 1):
+            tar.add(os.path.join(output_dir, name), arcname=name)
+            notify("progress", id=request_id, percent=100 * index / len(names), message=name)
+    return {"artifacts": [os.path.relpath(archive, params["projectDir"])]}
+
+
+HANDLERS = {
+    "initialize": lambda request_id, params: {"name": "Sample JSON-RPC Packager"},
+    "provides": lambda request_id, params: {"toolchains": [], "packagers": ["tarball"], "hooks": []},
+    "build": build,
+    "package": package,
+    # Requests are handled one at a time, so there is never one in flight to cancel
+    "cancel": lambda request_id, params: None,
+}
+
+for line in sys.stdin:
+    request = json.loads(line)
+    handler = HANDLERS.get(request["method"])
+    if handler is None:
+        send(dict(id=request["id"], error={"code": -32601, "message": f"Unknown method {request['method']}"}))
+        continue
+    try:
+        send(dict(id=request["id"], result=handler(request["id"], request.get("params", {}))))
+    except Exception as e:
+        send(dict(id=request["id"], error={"code": 1, "message": str(e)}))
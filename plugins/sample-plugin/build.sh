@@ -9,8 +9,15 @@ go version > /dev/null 2>&1 || {
 }
 
 # Get parameters from Build Studio
-PLATFORM=$1
-OUTPUT_DIR=$2
+COMMAND=$1
+PLATFORM=$2
+SOURCE_DIR=$3
+OUTPUT_DIR=$4
+
+if [ "$COMMAND" != "build-go" ]; then
+  echo "Unknown command: $COMMAND"
+  exit 1
+fi
 
 # Set GOOS based on platform
 GOOS=""
@@ -24,7 +31,7 @@ case $PLATFORM in
   "macos")
     GOOS="darwin"
     ;;
-  "webassembly")
+  "webassembly"|"wasm")
     GOOS="js"
     GOARCH="wasm"
     ;;
@@ -35,12 +42,14 @@ case $PLATFORM in
 esac
 
 # Build the Go project
+cd "$SOURCE_DIR" || exit 1
+mkdir -p "$OUTPUT_DIR"
 if [ "$GOOS" = "js" ] && [ "$GOARCH" = "wasm" ]; then
   echo "Building WebAssembly module..."
-  GOOS=$GOOS GOARCH=$GOARCH go build -o $OUTPUT_DIR/main.wasm .
+  GOOS=$GOOS GOARCH=$GOARCH go build -o "$OUTPUT_DIR/main.wasm" .
 else
   echo "Building for $GOOS..."
-  GOOS=$GOOS go build -o $OUTPUT_DIR/main .
+  GOOS=$GOOS go build -o "$OUTPUT_DIR/main" .
 fi
 
 echo "Build completed successfully for $PLATFORM"
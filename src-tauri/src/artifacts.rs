@@ -0,0 +1,232 @@
+//! Build artifacts for Build Studio
+//! Files matched by a build's `artifacts:` globs are copied into `builds/{platform}/{timestamp}`
+//! together with a manifest describing them
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use crate::fsutils::safe_file_name;
+
+/// Directory inside the project holding collected artifacts
+pub const ARTIFACTS_DIR: &str = "builds";
+/// Manifest written next to the collected files
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// A single collected file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Artifact {
+    /// Path relative to the artifact directory (and to the build directory it came from)
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// Contents of `manifest.json`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ArtifactManifest {
+    pub build_id: String,
+    pub build_name: Option<String>,
+    pub platform: String,
+    pub created_at: String,
+    pub artifacts: Vec<Artifact>,
+}
+
+/// Reject patterns that could match outside the build directory
+fn check_pattern(pattern: &str) -> Result<(), String> {
+    let path = Path::new(pattern);
+    if path.is_absolute() || path.components().any(|c| matches!(c, Component::ParentDir | Component::Prefix(_))) {
+        return Err(format!("Artifact pattern must stay inside the project: {}", pattern));
+    }
+    Ok(())
+}
+
+/// Whether `relative` lies in an earlier collection (`builds/<platform>/<timestamp>`),
+/// which the sandbox or a broad pattern such as `**/*.exe` would otherwise pick up again
+fn is_collected(work_dir: &Path, relative: &Path) -> bool {
+    let mut components = relative.components();
+    match (components.next(), components.next(), components.next()) {
+        (Some(root), Some(platform), Some(timestamp)) if root.as_os_str() == ARTIFACTS_DIR => work_dir
+            .join(root)
+            .join(platform)
+            .join(timestamp)
+            .join(MANIFEST_FILE)
+            .is_file(),
+        _ => false,
+    }
+}
+
+/// Whether the last component of a pattern is a wildcard, as in `target/release/*`
+fn ends_in_wildcard(pattern: &str) -> bool {
+    pattern
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .is_some_and(|last| last.contains(['*', '?', '[']))
+}
+
+/// Add `path` to `files`, or every file below it if it is a directory
+fn add_matched(work_dir: &Path, path: &Path, files: &mut BTreeSet<PathBuf>) -> io::Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            add_matched(work_dir, &entry?.path(), files)?;
+        }
+    } else if path.is_file() {
+        if let Ok(relative) = path.strip_prefix(work_dir) {
+            let skipped = relative.starts_with(".sandbox") || is_collected(work_dir, relative);
+            if !skipped {
+                files.insert(relative.to_path_buf());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Files under `work_dir` matching any of `patterns`, relative to `work_dir`
+fn match_patterns(work_dir: &Path, patterns: &[String]) -> Result<BTreeSet<PathBuf>, String> {
+    let root = glob::Pattern::escape(&work_dir.to_string_lossy());
+    let mut files = BTreeSet::new();
+    for pattern in patterns {
+        check_pattern(pattern)?;
+        let paths = glob::glob(&format!("{}/{}", root, pattern))
+            .map_err(|e| format!("Invalid artifact pattern '{}': {}", pattern, e))?;
+        // A directory named outright is collected whole; a wildcard such as
+        // `target/release/*` only collects the files it matches
+        let files_only = ends_in_wildcard(pattern);
+        for path in paths.flatten() {
+            if files_only && path.is_dir() {
+                continue;
+            }
+            add_matched(work_dir, &path, &mut files)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(files)
+}
+
+/// Copy `src` to `dest`, returning its size and SHA-256 digest
+fn copy_and_hash(src: &Path, dest: &Path) -> io::Result<(u64, String)> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let size = fs::copy(src, dest)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(dest)?, &mut hasher)?;
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+/// Collect the files under `work_dir` matching `patterns` into
+/// `<project>/builds/{platform}/{timestamp}` and write their manifest there.
+/// Returns `None` when no file matches.
+pub fn collect_artifacts(
+    work_dir: &Path,
+    project_dir: &Path,
+    build_id: &str,
+    build_name: Option<&str>,
+    platform: Option<&str>,
+    patterns: &[String],
+) -> Result<Option<(PathBuf, ArtifactManifest)>, String> {
+    let files = match_patterns(work_dir, patterns)?;
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let platform = platform.unwrap_or("native");
+    let now = chrono::Local::now();
+    let parent = project_dir.join(ARTIFACTS_DIR).join(safe_file_name(platform));
+    let mut dir = parent.join(now.format("%Y-%m-%d_%H-%M-%S").to_string());
+    if dir.exists() {
+        // Two builds for the same platform finished within the same second
+        dir = parent.join(format!("{}_{}", now.format("%Y-%m-%d_%H-%M-%S"), &build_id[..8.min(build_id.len())]));
+    }
+
+    let mut artifacts = Vec::new();
+    for relative in files {
+        let (size, sha256) = copy_and_hash(&work_dir.join(&relative), &dir.join(&relative))
+            .map_err(|e| format!("Failed to collect artifact {}: {}", relative.display(), e))?;
+        artifacts.push(Artifact {
+            path: relative.to_string_lossy().replace('\\', "/"),
+            size,
+            sha256,
+        });
+    }
+
+    let manifest = ArtifactManifest {
+        build_id: build_id.to_string(),
+        build_name: build_name.map(str::to_string),
+        platform: platform.to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        artifacts,
+    };
+    let content = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    fs::write(dir.join(MANIFEST_FILE), content).map_err(|e| format!("Failed to write artifact manifest: {}", e))?;
+    Ok(Some((dir, manifest)))
+}
+
+/// Read the manifest of an artifact directory
+pub fn read_manifest(dir: &Path) -> Result<ArtifactManifest, String> {
+    let content = fs::read_to_string(dir.join(MANIFEST_FILE))
+        .map_err(|e| format!("Failed to read artifact manifest in {}: {}", dir.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid artifact manifest in {}: {}", dir.display(), e))
+}
+
+/// The most recently collected artifact directory of a project, optionally for one platform
+pub fn latest_artifacts(project_dir: &Path, platform: Option<&str>) -> Option<PathBuf> {
+    let root = project_dir.join(ARTIFACTS_DIR);
+    let platform_dirs: Vec<PathBuf> = match platform {
+        Some(platform) => vec![root.join(safe_file_name(platform))],
+        None => fs::read_dir(&root).ok()?.flatten().map(|e| e.path()).collect(),
+    };
+    platform_dirs
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten().map(|e| e.path()))
+        .filter_map(|dir| read_manifest(&dir).ok().map(|manifest| (manifest.created_at, dir)))
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, dir)| dir)
+}
+
+/// Most recent artifact directory of a project, used as the packaging source
+#[tauri::command]
+pub fn get_latest_artifacts(project_dir: String, platform: Option<String>) -> Option<String> {
+    latest_artifacts(Path::new(&project_dir), platform.as_deref()).map(|dir| dir.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("build-studio-artifacts-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("out")).unwrap();
+        fs::write(dir.join("out/app.bin"), "app").unwrap();
+        dir
+    }
+
+    #[test]
+    fn nothing_matched_collects_nothing() {
+        let dir = project();
+        let collected = collect_artifacts(&dir, &dir, "id", None, Some("linux"), &["out/*.exe".to_string()]);
+        assert!(collected.unwrap().is_none());
+        assert!(!dir.join(ARTIFACTS_DIR).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn latest_artifacts_per_platform() {
+        let dir = project();
+        let patterns = ["out/*".to_string()];
+        let (linux, manifest) = collect_artifacts(&dir, &dir, "linux-id", None, Some("linux"), &patterns).unwrap().unwrap();
+        assert_eq!(manifest.artifacts.len(), 1);
+        assert_eq!(manifest.artifacts[0].path, "out/app.bin");
+        let (windows, _) = collect_artifacts(&dir, &dir, "windows-id", None, Some("windows/x64"), &patterns).unwrap().unwrap();
+        assert!(windows.starts_with(dir.join("builds/windows_x64")));
+
+        assert_eq!(latest_artifacts(&dir, Some("linux")), Some(linux));
+        assert_eq!(latest_artifacts(&dir, Some("windows/x64")), Some(windows));
+        assert_eq!(latest_artifacts(&dir, Some("macos")), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,16 +1,173 @@
+use std::collections::BTreeMap;
 use std::env;
 use std::path::Path;
+use build_studio_lib::build::{terminal_sink, OutputStream};
+use build_studio_lib::graph::{summary_table, BuildGraph, FailureMode};
 
 fn print_usage() {
     println!("Build Studio CLI\n");
     println!("Usage:");
     println!("  buildstudio-cli <command> [options]\n");
     println!("Commands:");
-    println!("  build <project_dir>      Run build for project");
-    println!("  package <project_dir>    Package project artifacts");
-    println!("  plugins <plugins_dir>    List available plugins");
-    println!("  nodes                    List remote build nodes");
-    println!("  help                     Show this help message");
+    println!("  build <project_dir> [build...] [--jobs N] [--keep-going | --fail-fast]");
+    println!("                              Run builds in dependency order, N at a time");
+    println!("  build <project_dir> --node <node_id>");
+    println!("                              Run the builds on a local Docker node");
+    println!("  build <project_dir> --locked");
+    println!("                              Fail if the toolchain differs from buildstudio.lock");
+    println!("  build <project_dir> --var NAME=value");
+    println!("                              Set a config variable, over the environment and .env");
+    println!("  build <project_dir> --profile NAME");
+    println!("                              Apply one of the config's profiles");
+    println!("  config <project_dir> [--profile NAME] [--json]");
+    println!("                              Print the config with includes, profile and extends resolved");
+    println!("  lock <project_dir>          Write buildstudio.lock for this host's toolchains");
+    println!("  package <project_dir>       Package the latest artifacts of each platform");
+    println!("  plugins <plugins_dir>       List available plugins");
+    println!("  plugin run <plugin> <command> [--platform P] [--project DIR] [--plugins-dir DIR] [-- args...]");
+    println!("                              Run a plugin command on a project");
+    println!("  plugin provides <plugin> [--project DIR] [--plugins-dir DIR]");
+    println!("                              Ask a JSON-RPC plugin which toolchains, packagers and hooks it adds");
+    println!("  plugin install <archive|checkout>");
+    println!("                              Install a plugin from a .zip, .tar.gz or git checkout");
+    println!("  plugin uninstall|enable|disable|rollback <plugin>");
+    println!("                              Manage an installed plugin; rollback returns to the replaced version");
+    println!("  history [project_dir]       List recorded builds");
+    println!("  history log <build_id>      Show the output of a recorded build");
+    println!("  history artifacts <id>      List the artifacts a recorded build collected");
+    println!("  history delete <build_id>   Delete a recorded build");
+    println!("  validate <project_dir|file> Check a config for errors");
+    println!("  schema [output_file]        Print the JSON Schema of the config file");
+    println!("  doctor [project_dir]        Show installed toolchains and which builds can run here");
+    println!("  nodes                       List remote build nodes");
+    println!("  help                        Show this help message");
+}
+
+/// Read the config of a project directory, in whichever supported format it has
+fn read_project_config(project_dir: &str) -> Result<build_studio_lib::config::BuildStudioConfig, String> {
+    read_project_config_with(project_dir, None, &BTreeMap::new())
+}
+
+/// Read the config of a project directory with a `--profile` and `--var` values for its variables
+fn read_project_config_with(
+    project_dir: &str,
+    profile: Option<&str>,
+    vars: &BTreeMap<String, String>,
+) -> Result<build_studio_lib::config::BuildStudioConfig, String> {
+    let path = build_studio_lib::config::find_config(Path::new(project_dir))
+        .ok_or_else(|| format!("No buildstudio.config.yaml (or .yml, .json, .toml) in {}", project_dir))?;
+    build_studio_lib::config::load_config_with(&path, profile, vars).map_err(|e| e.to_string())
+}
+
+/// `--profile` and `--var` options shared by `build` and `config`
+struct ConfigOptions {
+    profile: Option<String>,
+    vars: BTreeMap<String, String>,
+    /// Arguments that are not config options
+    rest: Vec<String>,
+}
+
+fn config_options(args: &[String]) -> Result<ConfigOptions, String> {
+    let mut profile = None;
+    let mut vars = BTreeMap::new();
+    let mut rest = Vec::new();
+    let mut options = args.iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--profile" => profile = Some(options.next().ok_or("--profile needs a profile name")?.clone()),
+            "--var" => {
+                let (name, value) = build_studio_lib::vars::parse_override(options.next().ok_or("--var needs NAME=value")?)
+                    .map_err(|e| format!("--var: {}", e))?;
+                vars.insert(name, value);
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+    Ok(ConfigOptions { profile, vars, rest })
+}
+
+/// Resolve the toolchain of every build in the project and write them to its lockfile
+fn lock_project(config: &build_studio_lib::config::BuildStudioConfig, project_dir: &str) -> Result<(), String> {
+    use build_studio_lib::lockfile;
+    let mut builds = Vec::new();
+    for build in &config.builds {
+        let job = build_studio_lib::build::BuildJob::from_config(config, build, project_dir)?;
+        builds.push(lockfile::lock_job(&job));
+    }
+    lockfile::write_lockfile(Path::new(project_dir), &lockfile::Lockfile::new(builds))
+}
+
+/// `plugin run <plugin> <command> [options] [-- args...]`: run a plugin command on a project,
+/// in the project's sandbox and under its security policy
+fn run_plugin(args: &[String]) -> Result<(), String> {
+    use build_studio_lib::plugin::{self, PluginRun};
+    let mut positional = Vec::new();
+    let mut extra = Vec::new();
+    let mut platform = env::consts::OS.to_string();
+    let mut project_dir = ".".to_string();
+    let mut plugins_dir = None;
+    let mut options = args.iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--platform" => platform = options.next().ok_or("--platform needs a platform")?.clone(),
+            "--project" => project_dir = options.next().ok_or("--project needs a directory")?.clone(),
+            "--plugins-dir" => plugins_dir = Some(options.next().ok_or("--plugins-dir needs a directory")?.clone()),
+            "--" => extra.extend(options.by_ref().cloned()),
+            _ if positional.len() < 2 => positional.push(arg.clone()),
+            _ => extra.push(arg.clone()),
+        }
+    }
+    let [name, command] = positional.as_slice() else {
+        return Err("Usage: buildstudio-cli plugin run <plugin> <command> [--platform P] [--project DIR] [--plugins-dir DIR] [-- args...]".to_string());
+    };
+    let dirs = match plugins_dir {
+        Some(dir) => plugin::plugins_in(Path::new(&dir)),
+        None => plugin::plugin_dirs(Path::new(&project_dir)),
+    };
+    let run = PluginRun::new(&plugin::find_plugin(&dirs, name)?, command, &platform, extra)?;
+
+    let config = match build_studio_lib::config::find_config(Path::new(&project_dir)) {
+        Some(_) => Some(read_project_config(&project_dir)?),
+        None => None,
+    };
+    let app_config_dir = build_studio_lib::fsutils::app_config_dir();
+    let security = build_studio_lib::build::resolve_cli_security(app_config_dir.as_deref(), config.as_ref(), None)?;
+    let sink = terminal_sink(None);
+    sink(OutputStream::Stdout, format!("Running plugin command: {}/{} (platform: {})", run.plugin, run.command, run.platform));
+    let job = build_studio_lib::build::BuildJob::for_plugin(run, &project_dir);
+    build_studio_lib::build::run_build_no_window(&job, &security.config, sink)
+}
+
+fn plugin_provides(args: &[String]) -> Result<(), String> {
+    use build_studio_lib::plugin;
+    let mut name = None;
+    let mut project_dir = ".".to_string();
+    let mut plugins_dir = None;
+    let mut options = args.iter();
+    while let Some(arg) = options.next() {
+        match arg.as_str() {
+            "--project" => project_dir = options.next().ok_or("--project needs a directory")?.clone(),
+            "--plugins-dir" => plugins_dir = Some(options.next().ok_or("--plugins-dir needs a directory")?.clone()),
+            _ => name = Some(arg.clone()),
+        }
+    }
+    let name = name.ok_or("Usage: buildstudio-cli plugin provides <plugin> [--project DIR] [--plugins-dir DIR]")?;
+    let dirs = match plugins_dir {
+        Some(dir) => plugin::plugins_in(Path::new(&dir)),
+        None => plugin::plugin_dirs(Path::new(&project_dir)),
+    };
+    let plugin = plugin::find_plugin(&dirs, &name)?;
+    let config = match build_studio_lib::config::find_config(Path::new(&project_dir)) {
+        Some(_) => Some(read_project_config(&project_dir)?),
+        None => None,
+    };
+    let app_config_dir = build_studio_lib::fsutils::app_config_dir();
+    let security = build_studio_lib::build::resolve_cli_security(app_config_dir.as_deref(), config.as_ref(), None)?;
+    let provides = build_studio_lib::rpc::provides(&plugin, &security.config)?;
+    for (kind, names) in [("Toolchains", &provides.toolchains), ("Packagers", &provides.packagers), ("Hooks", &provides.hooks)] {
+        println!("{}: {}", kind, if names.is_empty() { "none".to_string() } else { names.join(", ") });
+    }
+    Ok(())
 }
 
 fn main() {
@@ -22,23 +179,91 @@ fn main() {
     match args[1].as_str() {
         "build" => {
             if args.len() < 3 {
-                println!("Usage: buildstudio-cli build <project_dir>");
+                println!("Usage: buildstudio-cli build <project_dir> [build...] [--jobs N] [--keep-going | --fail-fast] [--node <node_id>] [--locked]");
                 return;
             }
-            let config_path = format!("{}/buildstudio.config.yaml", args[2]);
-            let config = match build_studio_lib::config::read_config(config_path.clone()) {
+            let mut jobs = num_cpus::get();
+            let mut mode = FailureMode::FailFast;
+            let mut targets = Vec::new();
+            let mut node = None;
+            let mut locked = false;
+            let config_options = match config_options(&args[3..]) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            };
+            let mut options = config_options.rest.iter();
+            while let Some(arg) = options.next() {
+                match arg.as_str() {
+                    "--jobs" | "-j" => match options.next().and_then(|n| n.parse::<usize>().ok()) {
+                        Some(n) if n > 0 => jobs = n,
+                        _ => {
+                            eprintln!("--jobs needs a positive number");
+                            return;
+                        }
+                    },
+                    "--node" => match options.next() {
+                        Some(id) => node = Some(id.clone()),
+                        None => {
+                            eprintln!("--node needs a node id");
+                            return;
+                        }
+                    },
+                    "--keep-going" | "-k" => mode = FailureMode::KeepGoing,
+                    "--fail-fast" => mode = FailureMode::FailFast,
+                    "--locked" => locked = true,
+                    name => targets.push(name.to_string()),
+                }
+            }
+            let config = match read_project_config_with(&args[2], config_options.profile.as_deref(), &config_options.vars) {
                 Ok(cfg) => cfg,
                 Err(e) => {
                     eprintln!("Failed to read config: {}", e);
                     return;
                 }
             };
-            for build in config.builds {
-                println!("Running build: {} (platform: {})", build.name, build.platform);
-                match build_studio_lib::build::run_build_no_window(build.command.clone(), args[2].clone(), Some(build.platform.clone())) {
-                    Ok(_) => println!("Build '{}' finished successfully.", build.name),
-                    Err(e) => eprintln!("Build '{}' failed: {}", build.name, e),
+            let graph = match BuildGraph::new(&config.builds) {
+                Ok(graph) => graph,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let selected = match graph.select(&targets) {
+                Ok(selected) => selected,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let app_config_dir = build_studio_lib::fsutils::app_config_dir();
+            let summaries = graph.run(&selected, jobs, mode, |build| {
+                // Label each line with its build once output from several builds can interleave
+                let sink = terminal_sink((jobs > 1).then(|| build.name.clone()));
+                sink(OutputStream::Stdout, format!("Running build: {} (platform: {})", build.name, build.platform));
+                let result = build_studio_lib::build::resolve_cli_security(app_config_dir.as_deref(), Some(&config), Some(&build.name))
+                    .and_then(|security| {
+                        sink(OutputStream::Stdout, "Security policy:".to_string());
+                        for line in security.summary() {
+                            sink(OutputStream::Stdout, format!("  {}", line));
+                        }
+                        let mut job = build_studio_lib::build::BuildJob::from_config(&config, build, &args[2])?;
+                        job.node = node.clone();
+                        job.locked = locked;
+                        build_studio_lib::build::run_build_no_window(&job, &security.config, sink.clone())
+                    });
+                match &result {
+                    Ok(_) => sink(OutputStream::Stdout, format!("Build '{}' finished successfully.", build.name)),
+                    Err(e) => sink(OutputStream::Stderr, format!("Build '{}' failed: {}", build.name, e)),
                 }
+                result
+            });
+            build_studio_lib::rpc::stop_all();
+            println!("\n{}", summary_table(&summaries));
+            if summaries.iter().any(|s| s.status != "success") {
+                std::process::exit(1);
             }
         },
         "package" => {
@@ -46,8 +271,7 @@ fn main() {
                 println!("Usage: buildstudio-cli package <project_dir>");
                 return;
             }
-            let config_path = format!("{}/buildstudio.config.yaml", args[2]);
-            let config = match build_studio_lib::config::read_config(config_path.clone()) {
+            let config = match read_project_config(&args[2]) {
                 Ok(cfg) => cfg,
                 Err(e) => {
                     eprintln!("Failed to read config: {}", e);
@@ -60,18 +284,38 @@ fn main() {
                 let _plugins_dir = project_path.parent()
                     .map(|parent| parent.join("plugins"))
                     .unwrap_or_else(|| Path::new("./plugins").to_path_buf());
-                
-                let opts = build_studio_lib::packaging::PackageConfig {
-                    name: pkg.name.unwrap_or("app".to_string()),
-                    version: pkg.version.unwrap_or("0.1.0".to_string()),
-                    package_type: pkg.r#type.unwrap_or("deb".to_string()),
-                    dependencies: pkg.dependencies.unwrap_or(vec![]),
-                    source_dir: args[2].clone(),
-                    output_dir: "./packages".to_string(),
-                };
-                match build_studio_lib::packaging::create_package(opts) {
-                    Ok(msg) => println!("Packaging successful: {}", msg),
-                    Err(e) => eprintln!("Packaging failed: {}", e),
+
+                // Package the artifacts of the most recent build of each configured platform
+                let mut platforms: Vec<&str> = Vec::new();
+                for build in &config.builds {
+                    if !platforms.contains(&build.platform.as_str()) {
+                        platforms.push(&build.platform);
+                    }
+                }
+                let mut packaged = 0;
+                for platform in platforms {
+                    let Some(source_dir) = build_studio_lib::artifacts::latest_artifacts(project_path, Some(platform)) else {
+                        println!("No build artifacts for platform '{}', skipping.", platform);
+                        continue;
+                    };
+                    println!("Packaging {} artifacts from {}", platform, source_dir.display());
+
+                    let opts = build_studio_lib::packaging::PackageConfig {
+                        name: pkg.name.clone().unwrap_or("app".to_string()),
+                        version: pkg.version.clone().unwrap_or("0.1.0".to_string()),
+                        package_type: pkg.r#type.clone().unwrap_or("deb".to_string()),
+                        dependencies: pkg.dependencies.clone().unwrap_or(vec![]),
+                        source_dir: source_dir.to_string_lossy().to_string(),
+                        output_dir: format!("./packages/{}", build_studio_lib::fsutils::safe_file_name(platform)),
+                    };
+                    match build_studio_lib::packaging::create_package(opts) {
+                        Ok(msg) => println!("Packaging successful: {}", msg),
+                        Err(e) => eprintln!("Packaging failed: {}", e),
+                    }
+                    packaged += 1;
+                }
+                if packaged == 0 {
+                    eprintln!("No build artifacts found in {}/builds. Declare `artifacts:` for a build and run it first.", args[2]);
                 }
             } else {
                 println!("No package section in config.");
@@ -83,13 +327,259 @@ fn main() {
             } else {
                 "./plugins".to_string()
             };
-            let plugins = build_studio_lib::plugin::list_plugins(plugins_dir);
+            let plugins = build_studio_lib::plugin::load_plugins(Path::new(&plugins_dir));
             if plugins.is_empty() {
                 println!("No plugins found.");
             } else {
                 for plugin in plugins {
-                    println!("{} ({}): {}", plugin.name, plugin.version, plugin.description);
+                    let state = match (plugin.installed, plugin.enabled) {
+                        (true, true) => " [installed]",
+                        (true, false) => " [disabled]",
+                        _ => "",
+                    };
+                    println!("{} ({}){}: {}", plugin.name, plugin.version, state, plugin.description);
+                    for command in &plugin.commands {
+                        println!("  {}: {}", command.name, command.description);
+                    }
+                    for message in plugin.dependency_checks.iter().filter_map(|c| c.message.as_deref()) {
+                        println!("  ! {}", message);
+                    }
+                }
+            }
+        },
+        "plugin" => match args.get(2).map(String::as_str) {
+            Some(action @ ("run" | "provides")) => {
+                let result = if action == "run" { run_plugin(&args[3..]) } else { plugin_provides(&args[3..]) };
+                build_studio_lib::rpc::stop_all();
+                if let Err(e) = result {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            Some(action @ ("install" | "uninstall" | "enable" | "disable" | "rollback")) => {
+                let Some(target) = args.get(3) else {
+                    println!("Usage: buildstudio-cli plugin {} <{}>", action, if action == "install" { "archive|checkout" } else { "plugin" });
+                    return;
+                };
+                let result = build_studio_lib::installer::PluginRegistry::open().and_then(|registry| match action {
+                    "install" => registry.install(Path::new(target)),
+                    "uninstall" => registry.uninstall(target),
+                    "enable" => registry.set_enabled(target, true),
+                    "disable" => registry.set_enabled(target, false),
+                    _ => registry.rollback(target),
+                });
+                match result {
+                    Ok(plugin) => {
+                        let verb = match action {
+                            "install" => "Installed",
+                            "uninstall" => "Uninstalled",
+                            "enable" => "Enabled",
+                            "disable" => "Disabled",
+                            _ => "Rolled back",
+                        };
+                        println!("{} {} {}", verb, plugin.name, plugin.version);
+                        if let (Some(previous), "install" | "rollback") = (&plugin.previous, action) {
+                            println!("Version {} is kept; `buildstudio-cli plugin rollback {}` returns to it", previous, plugin.id);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            _ => println!("Usage: buildstudio-cli plugin <run|provides|install|uninstall|enable|disable|rollback> ..."),
+        },
+        "history" => {
+            let app_config_dir = match build_studio_lib::fsutils::app_config_dir() {
+                Some(dir) => dir,
+                None => {
+                    eprintln!("Could not determine the app config directory");
+                    return;
+                }
+            };
+            let history = build_studio_lib::history::BuildHistory::new(&app_config_dir);
+            match (args.get(2).map(String::as_str), args.get(3)) {
+                (Some("log"), Some(id)) => match history.log(id) {
+                    Ok(lines) => {
+                        for entry in lines {
+                            if entry.stream == "stderr" {
+                                eprintln!("{}", entry.line);
+                            } else {
+                                println!("{}", entry.line);
+                            }
+                        }
+                    }
+                    Err(e) => eprintln!("{}", e),
+                },
+                (Some("artifacts"), Some(id)) => match history.artifacts(id) {
+                    Ok(manifest) => {
+                        for artifact in manifest.artifacts {
+                            println!("{}  {:>10}  {}", artifact.sha256, artifact.size, artifact.path);
+                        }
+                    }
+                    Err(e) => eprintln!("{}", e),
+                },
+                (Some("delete"), Some(id)) => match history.delete(id) {
+                    Ok(_) => println!("Deleted build {}", id),
+                    Err(e) => eprintln!("{}", e),
+                },
+                (Some("log"), None) | (Some("artifacts"), None) | (Some("delete"), None) => {
+                    println!("Usage: buildstudio-cli history {} <build_id>", args[2]);
                 }
+                (project, _) => {
+                    let builds = history.list(project);
+                    if builds.is_empty() {
+                        println!("No builds recorded.");
+                    }
+                    for build in builds {
+                        let duration = build
+                            .duration_ms
+                            .map(|ms| format!("{:.1}s", ms as f64 / 1000.0))
+                            .unwrap_or_else(|| "-".to_string());
+                        println!(
+                            "{}  {:<9}  {}  {:>8}  {} / {} ({})",
+                            build.id,
+                            build.status,
+                            build.started_at,
+                            duration,
+                            build.project,
+                            build.build_name.unwrap_or_else(|| build.command.clone()),
+                            build.platform.unwrap_or_else(|| "native".to_string()),
+                        );
+                    }
+                }
+            }
+        },
+        "validate" => {
+            if args.len() < 3 {
+                println!("Usage: buildstudio-cli validate <project_dir|config_file>");
+                return;
+            }
+            let target = Path::new(&args[2]);
+            let path = if target.is_dir() {
+                match build_studio_lib::config::find_config(target) {
+                    Some(path) => path,
+                    None => {
+                        eprintln!("No buildstudio.config.yaml (or .yml, .json, .toml) in {}", args[2]);
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                target.to_path_buf()
+            };
+            let errors = build_studio_lib::config::validate_config(path.to_string_lossy().to_string(), None);
+            if errors.is_empty() {
+                println!("{} is valid", path.display());
+            } else {
+                for error in &errors {
+                    eprintln!("{}", error);
+                }
+                std::process::exit(1);
+            }
+        },
+        "config" => {
+            if args.len() < 3 {
+                println!("Usage: buildstudio-cli config <project_dir> [--profile NAME] [--var NAME=value] [--json]");
+                return;
+            }
+            let options = match config_options(&args[3..]) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let config = match read_project_config_with(&args[2], options.profile.as_deref(), &options.vars) {
+                Ok(cfg) => cfg,
+                Err(e) => {
+                    eprintln!("Failed to read config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let printed = if options.rest.iter().any(|a| a == "--json") {
+                serde_json::to_string_pretty(&config).map(|json| json + "\n").map_err(|e| e.to_string())
+            } else {
+                serde_yaml::to_string(&config).map_err(|e| e.to_string())
+            };
+            match printed {
+                Ok(text) => print!("{}", text),
+                Err(e) => {
+                    eprintln!("Failed to print config: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        "schema" => {
+            let schema = build_studio_lib::config::config_schema();
+            let content = serde_json::to_string_pretty(&schema).unwrap_or_default() + "\n";
+            match args.get(2) {
+                Some(file) => match std::fs::write(file, content) {
+                    Ok(()) => println!("Wrote {}", file),
+                    Err(e) => {
+                        eprintln!("Failed to write {}: {}", file, e);
+                        std::process::exit(1);
+                    }
+                },
+                None => print!("{}", content),
+            }
+        },
+        "lock" => {
+            if args.len() < 3 {
+                println!("Usage: buildstudio-cli lock <project_dir>");
+                return;
+            }
+            let config = match read_project_config(&args[2]) {
+                Ok(cfg) => cfg,
+                Err(e) => {
+                    eprintln!("Failed to read config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            match lock_project(&config, &args[2]) {
+                Ok(()) => println!("Wrote {}/{}", args[2], build_studio_lib::lockfile::LOCK_FILE),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        "doctor" => {
+            let tools = build_studio_lib::probe::probe();
+            for kind in ["compiler", "sdk", "packaging"] {
+                println!("{}:", kind);
+                for tool in tools.iter().filter(|t| t.kind == kind) {
+                    let found = match (&tool.path, &tool.version) {
+                        (Some(_), Some(version)) => version.clone(),
+                        (Some(path), None) => path.clone(),
+                        (None, _) => "not found".to_string(),
+                    };
+                    println!("  {:<24} {}", tool.name, found);
+                    if !tool.details.is_empty() {
+                        println!("  {:<24} {}", "", tool.details.join(", "));
+                    }
+                }
+            }
+            let Some(project_dir) = args.get(2) else { return };
+            let config = match read_project_config(project_dir) {
+                Ok(cfg) => cfg,
+                Err(e) => {
+                    eprintln!("Failed to read config: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            println!("\nbuilds:");
+            let mut runnable = true;
+            for build in &config.builds {
+                let readiness = build_studio_lib::probe::check_build(&config, build, &tools);
+                println!("  {:<24} {}", readiness.name, if readiness.runnable { "ok" } else { "cannot run" });
+                for problem in &readiness.problems {
+                    println!("  {:<24} - {}", "", problem);
+                }
+                runnable &= readiness.runnable;
+            }
+            if !runnable {
+                std::process::exit(1);
             }
         },
         "nodes" => {
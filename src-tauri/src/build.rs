@@ -1,170 +1,1072 @@
-use std::process::{Command, Stdio};
-use std::io::{BufRead, BufReader};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::io::{BufRead, BufReader, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Once};
 use std::thread;
-use tauri::{Window, Emitter};
-use crate::toolchain::Toolchain;
-use crate::security::{self, SecurityConfig};
+use std::time::{Duration, Instant};
+use tauri::{Window, Emitter, Manager, State};
+use crate::artifacts;
+use crate::container::{ContainerRun, NodeRun};
+use crate::fsutils::LinkStrategy;
+use crate::config::{self, BuildConfig, BuildStudioConfig};
+use crate::graph::{BuildGraph, BuildSummary, FailureMode};
+use crate::history::{BuildHistory, BuildRecord};
+use crate::language;
+use crate::lockfile;
+use crate::plugin::PluginRun;
+use crate::project::ProjectManager;
+use crate::toolchain::{self, ToolchainProfile};
+use crate::security::{self, EffectiveSecurity, SecurityConfig, SecurityPolicy};
+use crate::policy::PolicyDecision;
+use crate::rpc;
+use crate::wasm;
 
-fn get_toolchain_for_platform(platform: &str) -> Toolchain {
-    match platform {
-        "windows" => Toolchain::MinGW,
-        "wasm" | "webassembly" => Toolchain::WasmPack,
-        "android" => Toolchain::AndroidNDK,
-        _ => Toolchain::Native,
+/// How often a running build is polled for exit, timeout or cancellation
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Cancellation flags for builds started from the GUI, keyed by build id
+#[derive(Default)]
+pub struct BuildManager {
+    pub running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl BuildManager {
+    /// Register a build so it can be cancelled while running; returns its cancellation flag
+    pub fn register(&self, build_id: &str) -> Arc<AtomicBool> {
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.running.lock().unwrap().insert(build_id.to_string(), cancel.clone());
+        cancel
+    }
+
+    /// Forget a build once it has finished
+    pub fn finish(&self, build_id: &str) {
+        self.running.lock().unwrap().remove(build_id);
+    }
+
+    /// Ask a running build to stop
+    pub fn cancel(&self, build_id: &str) -> Result<(), String> {
+        match self.running.lock().unwrap().get(build_id) {
+            Some(cancel) => {
+                cancel.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+            None => Err(format!("No running build with id {}", build_id)),
+        }
     }
 }
 
-#[tauri::command]
-pub async fn run_build(window: Window, command: String, cwd: String, platform: Option<String>) -> Result<(), String> {
-    // Load default security config
-    let security_config = SecurityConfig::default();
-    
-    // Validate command against security policy
-    if !security::validate_command(&command, &security_config) {
-        return Err("Command blocked by security policy".to_string());
-    }
-    
-    // Create sandboxed environment
-    let sandbox_dir = security::create_sandbox(&cwd, &security_config)
-        .map_err(|e| format!("Failed to create sandbox: {}", e))?;
-    
-    let toolchain = platform
-        .as_ref()
-        .map(|p| get_toolchain_for_platform(p))
-        .unwrap_or(Toolchain::Native);
-    let prefix = toolchain.command_prefix();
-    let full_command = if prefix.is_empty() {
-        command.clone()
+/// How a build process ended
+#[derive(Debug)]
+pub enum BuildOutcome {
+    Success,
+    Failure(ExitStatus),
+    Timeout,
+    Cancelled,
+}
+
+impl BuildOutcome {
+    /// Exit code of the build process, if it exited on its own
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            BuildOutcome::Success => Some(0),
+            BuildOutcome::Failure(status) => status.code(),
+            BuildOutcome::Timeout | BuildOutcome::Cancelled => None,
+        }
+    }
+
+    /// Status string emitted with the `build-finished` event
+    pub fn status(&self) -> &'static str {
+        match self {
+            BuildOutcome::Success => "success",
+            BuildOutcome::Failure(_) => "failure",
+            BuildOutcome::Timeout => "timeout",
+            BuildOutcome::Cancelled => "cancelled",
+        }
+    }
+
+    fn into_result(self, max_build_time: Option<u32>) -> Result<(), String> {
+        match self {
+            BuildOutcome::Success => Ok(()),
+            BuildOutcome::Failure(status) => Err(format!("Command exited with status: {}", status)),
+            BuildOutcome::Timeout => Err(format!(
+                "Build timed out after {} seconds",
+                max_build_time.unwrap_or_default()
+            )),
+            BuildOutcome::Cancelled => Err("Build cancelled".to_string()),
+        }
+    }
+}
+
+/// Build the command running `argv` behind `launcher` (e.g. a network namespace wrapper),
+/// placing it in its own process group on Unix so that the whole tree can be killed on
+/// timeout or cancellation.
+pub(crate) fn launch_command(argv: Vec<OsString>, launcher: &[String]) -> Command {
+    let mut argv = launcher.iter().map(OsString::from).chain(argv);
+    let mut c = Command::new(argv.next().unwrap_or_default());
+    c.args(argv);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        c.process_group(0);
+    }
+    c
+}
+
+/// Build the shell command. The launcher always starts a Unix shell, also on Windows hosts
+/// (e.g. a Linux container).
+fn shell_command(full_command: &str, launcher: &[String]) -> Command {
+    let argv: Vec<OsString> = if launcher.is_empty() && cfg!(target_os = "windows") {
+        vec!["cmd".into(), "/C".into(), full_command.into()]
     } else {
-        format!("{}{}", prefix.join(""), command)
+        vec!["sh".into(), "-c".into(), full_command.into()]
     };
+    launch_command(argv, launcher)
+}
 
-    let mut cmd = if cfg!(target_os = "windows") {
-        let mut c = Command::new("cmd");
-        c.arg("/C").arg(&full_command);
-        c
+/// Kill the build process together with everything it spawned
+pub(crate) fn kill_process_tree(child: &mut Child) {
+    let pid = child.id().to_string();
+    if cfg!(target_os = "windows") {
+        let _ = Command::new("taskkill").args(["/T", "/F", "/PID", &pid]).output();
     } else {
-        let mut c = Command::new("sh");
-        c.arg("-c").arg(&full_command);
-        c
-    };
+        // The child leads its own process group, so a negative pid targets the group
+        let _ = Command::new("kill").args(["-KILL", "--", &format!("-{}", pid)]).output();
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
 
-    // Set the working directory to sandbox
-    cmd.current_dir(&sandbox_dir);
+/// Wait for a build to exit, killing it once `max_build_time` elapses or `cancel` is set
+fn wait_for_build(child: &mut Child, max_build_time: Option<u32>, cancel: &AtomicBool) -> Result<BuildOutcome, String> {
+    let deadline = max_build_time.map(|secs| Instant::now() + Duration::from_secs(secs.into()));
+    loop {
+        if let Some(status) = child.try_wait().map_err(|e| format!("Failed to wait for command: {}", e))? {
+            return Ok(if status.success() {
+                BuildOutcome::Success
+            } else {
+                BuildOutcome::Failure(status)
+            });
+        }
+        if cancel.load(Ordering::SeqCst) {
+            kill_process_tree(child);
+            return Ok(BuildOutcome::Cancelled);
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            kill_process_tree(child);
+            return Ok(BuildOutcome::Timeout);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
 
-    // Set up stdout and stderr to be piped
+/// Spawn `cmd`, stream its output to `sink` and wait for it to exit, time out or be cancelled
+fn run_streamed(mut cmd: Command, max_build_time: Option<u32>, cancel: &AtomicBool, sink: &OutputSink) -> Result<BuildOutcome, String> {
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
-
-    // Spawn the command
     let mut child = cmd.spawn().map_err(|e| format!("Failed to execute command: {}", e))?;
 
-    // Get stdout and stderr
     let stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
     let stderr = child.stderr.take().ok_or("Failed to capture stderr")?;
+    let readers = [
+        stream_lines(stdout, OutputStream::Stdout, sink.clone()),
+        stream_lines(stderr, OutputStream::Stderr, sink.clone()),
+    ];
+    let outcome = wait_for_build(&mut child, max_build_time, cancel);
+    for reader in readers {
+        let _ = reader.join();
+    }
+    outcome
+}
+
+/// Which stream a line of build output came from
+#[derive(Clone, Copy)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
 
-    let window_clone = window.clone();
+/// Receives build output line by line (the GUI window or the terminal)
+pub type OutputSink = Arc<dyn Fn(OutputStream, String) + Send + Sync>;
 
-    // Spawn a thread to read stdout
+/// Sink emitting `build-output` and `build-error` events, prefixing lines with `[label]` if given
+fn window_sink(window: &Window, label: Option<String>) -> OutputSink {
+    let window = window.clone();
+    Arc::new(move |stream, line| {
+        let event = match stream {
+            OutputStream::Stdout => "build-output",
+            OutputStream::Stderr => "build-error",
+        };
+        let line = match &label {
+            Some(label) => format!("[{}] {}", label, line),
+            None => line,
+        };
+        let _ = window.emit(event, line);
+    })
+}
+
+/// Sink printing to stdout and stderr, prefixing lines with `[label]` if given
+pub fn terminal_sink(label: Option<String>) -> OutputSink {
+    Arc::new(move |stream, line| {
+        let line = match &label {
+            Some(label) => format!("[{}] {}", label, line),
+            None => line,
+        };
+        match stream {
+            OutputStream::Stdout => println!("{}", line),
+            OutputStream::Stderr => eprintln!("{}", line),
+        }
+    })
+}
+
+/// Forward every line of `reader` to `sink`
+pub(crate) fn stream_lines<R: std::io::Read + Send + 'static>(reader: R, stream: OutputStream, sink: OutputSink) -> thread::JoinHandle<()> {
     thread::spawn(move || {
-        let reader = BufReader::new(stdout);
-        for line in reader.lines() {
+        for line in BufReader::new(reader).lines() {
             match line {
-                Ok(content) => {
-                    let _ = window_clone.emit("build-output", content);
-                }
+                Ok(content) => sink(stream, content),
                 Err(e) => {
-                    let _ = window_clone.emit("build-error", format!("Error reading stdout: {}", e));
+                    let source = match stream {
+                        OutputStream::Stdout => "stdout",
+                        OutputStream::Stderr => "stderr",
+                    };
+                    sink(OutputStream::Stderr, format!("Error reading {}: {}", source, e));
                 }
             }
         }
-    });
+    })
+}
+
+/// A single build to run and record
+#[derive(Clone, Debug, Default)]
+pub struct BuildJob {
+    pub command: String,
+    /// Command run with network access before `command`, to download dependencies
+    pub fetch: Option<String>,
+    /// Project directory
+    pub cwd: String,
+    pub platform: Option<String>,
+    pub build_name: Option<String>,
+    /// Globs of files to collect into `builds/{platform}/{timestamp}` after a successful run
+    pub artifacts: Vec<String>,
+    /// Docker/Podman image to run the command in
+    pub container: Option<String>,
+    /// Id of the local Docker node to run the command on
+    pub node: Option<String>,
+    /// Language adapter name, whose tools are checked before a local build
+    pub language: Option<String>,
+    /// Cross-compilation environment the command runs with
+    pub toolchain: ToolchainProfile,
+    /// Fail unless the toolchain matches the project's `buildstudio.lock`
+    pub locked: bool,
+    /// Axis values of a build generated from a matrix
+    pub matrix: BTreeMap<String, String>,
+    /// Plugin command run instead of `command`, which then only describes it
+    pub plugin: Option<PluginRun>,
+}
 
-    let window_clone = window.clone();
+impl BuildJob {
+    /// The job for one of a project's configured builds
+    pub fn from_config(config: &BuildStudioConfig, build: &BuildConfig, cwd: &str) -> Result<Self, String> {
+        Self {
+            command: build.command.clone(),
+            // An empty `fetch:` turns off the language's fetch step
+            fetch: build.fetch.clone().filter(|fetch| !fetch.trim().is_empty()),
+            cwd: cwd.to_string(),
+            platform: Some(build.platform.clone()),
+            build_name: Some(build.name.clone()),
+            artifacts: build.artifacts.clone(),
+            container: build.container.clone(),
+            node: None,
+            language: build.language.clone(),
+            toolchain: toolchain::resolve_profile(
+                Some(&build.platform),
+                build.target.as_deref(),
+                build.toolchain.as_deref(),
+                &config.toolchains,
+            )?,
+            locked: false,
+            matrix: build.matrix_cell.as_ref().map(|cell| cell.values.clone()).unwrap_or_default(),
+            plugin: None,
+        }
+        .with_plugin(build.plugin.as_deref())
+    }
 
-    // Spawn a thread to read stderr
-    thread::spawn(move || {
-        let reader = BufReader::new(stderr);
-        for line in reader.lines() {
-            match line {
-                Ok(content) => {
-                    let _ = window_clone.emit("build-error", content);
-                }
-                Err(e) => {
-                    let _ = window_clone.emit("build-error", format!("Error reading stderr: {}", e));
-                }
+    /// The job running a plugin command on the project in `cwd`
+    pub fn for_plugin(run: PluginRun, cwd: &str) -> Self {
+        Self {
+            command: run.command_line(),
+            cwd: cwd.to_string(),
+            platform: Some(run.platform.clone()),
+            build_name: Some(format!("{}/{}", run.plugin, run.command)),
+            plugin: Some(run),
+            ..Default::default()
+        }
+    }
+
+    /// Everything the job runs, as one command line for the security policy to check
+    pub fn policy_command(&self) -> String {
+        match &self.fetch {
+            Some(fetch) => format!("{} && {}", fetch, self.command),
+            None => self.command.clone(),
+        }
+    }
+
+    /// Run the plugin command a build's `plugin:` names, if it has one
+    fn with_plugin(mut self, reference: Option<&str>) -> Result<Self, String> {
+        if let Some(reference) = reference {
+            let platform = self.platform.clone().unwrap_or_else(|| std::env::consts::OS.to_string());
+            let run = PluginRun::from_reference(reference, Path::new(&self.cwd), &platform)?;
+            self.command = run.command_line();
+            self.plugin = Some(run);
+        }
+        Ok(self)
+    }
+}
+
+/// Environment of a local build: the toolchain's variables, its sysroot added to the inherited
+/// `CFLAGS` and `CXXFLAGS`, and its `PATH` additions in front of the inherited `PATH`
+fn toolchain_env(profile: &ToolchainProfile) -> Result<Vec<(String, String)>, String> {
+    let mut vars = profile.env();
+    // Sysroot flags go after the flags the build inherits, unless the profile sets its own
+    for (key, flags) in vars.iter_mut() {
+        if toolchain::SYSROOT_FLAGS.contains(&key.as_str()) && !profile.env.contains_key(key.as_str()) {
+            if let Some(inherited) = std::env::var(key.as_str()).ok().filter(|f| !f.trim().is_empty()) {
+                *flags = format!("{} {}", inherited, flags);
             }
         }
-    });
+    }
+    if !profile.path.is_empty() {
+        let inherited = std::env::var_os("PATH").unwrap_or_default();
+        let dirs = profile.path.iter().map(PathBuf::from).chain(std::env::split_paths(&inherited));
+        let path = std::env::join_paths(dirs).map_err(|e| format!("Invalid toolchain path: {}", e))?;
+        vars.push(("PATH".to_string(), path.to_string_lossy().to_string()));
+    }
+    Ok(vars)
+}
+
+/// Run an already validated build in the sandbox described by `security_config`,
+/// streaming its output to `sink` until it exits, times out or `cancel` is set.
+/// Returns how it ended, the directory it ran in and the artifacts a WebAssembly plugin reported.
+fn execute_build(
+    job: &BuildJob,
+    security_config: &SecurityConfig,
+    cancel: &AtomicBool,
+    sink: OutputSink,
+) -> Result<(BuildOutcome, PathBuf, Vec<String>), String> {
+    if job.node.is_some() && job.container.is_some() {
+        return Err("A build runs either on a node or in a container image, not both".to_string());
+    }
+    // zig's wrapper scripts live on this host, and cross starts containers of its own
+    if (job.node.is_some() || job.container.is_some()) && matches!(job.toolchain.name.as_str(), "zig" | "cross") {
+        return Err(format!("The {} toolchain only runs builds on this host", job.toolchain.name));
+    }
+    // Plugin scripts live on this host too
+    if let (Some(run), true) = (&job.plugin, job.node.is_some() || job.container.is_some()) {
+        return Err(format!("Plugin '{}' only runs builds on this host", run.plugin));
+    }
 
-    // Wait for the command to complete
-    let status = child.wait().map_err(|e| format!("Failed to wait for command: {}", e))?;
+    // Resolve the node, the container engine or network isolation before touching the
+    // filesystem so an unsupported host fails early. Container builds are isolated by the
+    // engine instead; node builds use the node's network.
+    let node = job
+        .node
+        .as_deref()
+        .map(|id| NodeRun::prepare(id, &job.cwd))
+        .transpose()?;
+    if let (Some(id), true) = (&job.node, security_config.enable_sandbox && security_config.network_isolation) {
+        sink(
+            OutputStream::Stderr,
+            format!("Warning: network isolation does not apply on node {}; the build uses the node's network", id),
+        );
+    }
+    let container = job
+        .container
+        .as_deref()
+        .map(|image| ContainerRun::prepare(image, security_config))
+        .transpose()?;
+    let wasm = job.plugin.as_ref().and_then(|run| run.wasm.as_ref().map(|settings| (run, settings)));
+    // WebAssembly plugins run in the embedded runtime, which has no network
+    let namespace = if node.is_some() || container.is_some() || wasm.is_some() {
+        Vec::new()
+    } else {
+        security::network_isolation_prefix(security_config)?
+    };
+    let adapter = job.language.as_deref().and_then(language::adapter);
+    if node.is_none() && container.is_none() && wasm.is_none() {
+        // Node and container images bring their own tools, and WebAssembly plugins use none
+        if let Some(adapter) = adapter {
+            adapter.check_tools()?;
+        }
+    }
+
+    // Create sandboxed environment, or sync the project into the node's workspace
+    let sandbox_dir = match &node {
+        Some(run) => {
+            let paths = if security_config.enable_sandbox {
+                security_config.allowed_paths.clone()
+            } else {
+                vec![".".to_string()]
+            };
+            // Dependencies and build caches of the host are no use on the node
+            let caches = adapter.map(|a| a.cache_dirs).unwrap_or_default();
+            security::sync_project(Path::new(&job.cwd), run.host_dir(), &paths, caches, LinkStrategy::Copy)
+                .map_err(|e| format!("Failed to sync project to node: {}", e))?;
+            run.host_dir().to_string_lossy().to_string()
+        }
+        None => {
+            // The build keeps its own caches in the sandbox between runs
+            let caches = adapter.map(|a| a.cache_dirs).unwrap_or_default();
+            security::create_sandbox(&job.cwd, job.build_name.as_deref(), caches, security_config)
+                .map_err(|e| format!("Failed to create sandbox: {}", e))?
+        }
+    };
+    // The toolchain only sets environment variables. Containers and nodes get them passed
+    // in, without the PATH additions, which name directories on this host.
+    let toolchain_vars = job.toolchain.env();
+    if !job.toolchain.is_native() {
+        let target = job.toolchain.target.as_deref().map(|t| format!(" ({})", t)).unwrap_or_default();
+        sink(OutputStream::Stdout, format!("Toolchain: {}{}", job.toolchain.name, target));
+    }
+    let launcher = match (&node, &container) {
+        (Some(run), _) => run.launcher(&toolchain_vars),
+        (_, Some(run)) => run.launcher(Path::new(&sandbox_dir), &toolchain_vars),
+        _ => namespace,
+    };
+
+    // Nodes and containers decide about the network for the whole build, so they run the fetch
+    // step as part of the command; local builds run it first, outside the network namespace
+    let local = node.is_none() && container.is_none();
+    let command = match (&job.fetch, local) {
+        (Some(fetch), false) => format!("{} && {}", fetch, job.command),
+        _ => job.command.clone(),
+    };
+    let full_command = match &node {
+        Some(run) => run.wrap(&command),
+        None => command,
+    };
+
+    let work_dir = Path::new(&sandbox_dir);
+    let started = Instant::now();
+    if let (Some(fetch), true) = (&job.fetch, local) {
+        sink(OutputStream::Stdout, format!("Fetching dependencies: {}", fetch));
+        let mut cmd = shell_command(fetch, &[]);
+        cmd.envs(toolchain_env(&job.toolchain)?);
+        cmd.current_dir(work_dir);
+        let outcome = run_streamed(cmd, security_config.max_build_time, cancel, &sink)?;
+        if !matches!(outcome, BuildOutcome::Success) {
+            return Ok((outcome, PathBuf::from(sandbox_dir), Vec::new()));
+        }
+    }
+    // The fetch step counts towards the build's time limit
+    let max_build_time = security_config
+        .max_build_time
+        .map(|secs| secs.saturating_sub(started.elapsed().as_secs() as u32));
 
-    // Clean up sandbox
-    if let Err(e) = security::cleanup_sandbox(&sandbox_dir) {
-        let _ = window.emit("build-error", format!("Failed to clean up sandbox: {}", e));
+    if let Some(run) = &job.plugin {
+        std::fs::create_dir_all(run.output_dir(work_dir))
+            .map_err(|e| format!("Failed to create plugin output directory: {}", e))?;
+    }
+    // WebAssembly modules and JSON-RPC servers are driven by Build Studio rather than waited on
+    let hosted = match (&job.plugin, wasm) {
+        (_, Some((run, settings))) => Some(wasm::run(run, settings, work_dir, sink.clone(), cancel, max_build_time)?),
+        (Some(run @ PluginRun { rpc: Some(method), .. }), None) => Some(rpc::run(
+            run,
+            *method,
+            work_dir,
+            toolchain_env(&job.toolchain)?,
+            &launcher,
+            sink.clone(),
+            cancel,
+            max_build_time,
+        )?),
+        _ => None,
+    };
+    if let Some((outcome, reported)) = hosted {
+        if let Err(e) = security::collect_sandbox_outputs(&job.cwd, &sandbox_dir, security_config) {
+            sink(OutputStream::Stderr, format!("Failed to collect build outputs: {}", e));
+        }
+        return Ok((outcome, PathBuf::from(sandbox_dir), reported));
     }
 
-    if status.success() {
-        window.emit("build-finished", Some("success".to_string())).ok();
-        Ok(())
+    let mut cmd = match &job.plugin {
+        Some(run) => {
+            let mut cmd = launch_command(run.argv(work_dir), &launcher);
+            cmd.envs(run.env(work_dir));
+            cmd
+        }
+        None => shell_command(&full_command, &launcher),
+    };
+    if local {
+        cmd.envs(toolchain_env(&job.toolchain)?);
+    }
+
+    // Set the working directory to sandbox
+    cmd.current_dir(&sandbox_dir);
+
+    let outcome = run_streamed(cmd, max_build_time, cancel, &sink)?;
+    if let BuildOutcome::Timeout | BuildOutcome::Cancelled = outcome {
+        if let Some(run) = &container {
+            run.remove();
+        }
+        if let Some(run) = &node {
+            run.kill();
+        }
+    }
+
+    // Copy build outputs out of the sandbox or the node
+    if let Err(e) = security::collect_sandbox_outputs(&job.cwd, &sandbox_dir, security_config) {
+        sink(OutputStream::Stderr, format!("Failed to collect build outputs: {}", e));
+    }
+
+    Ok((outcome, PathBuf::from(sandbox_dir), Vec::new()))
+}
+
+/// Name to record for a build in `cwd`: the registered project's name, or the directory name
+fn project_name(projects: &ProjectManager, cwd: &str) -> String {
+    projects
+        .find_by_path(Path::new(cwd))
+        .map(|p| p.name.clone())
+        .or_else(|| Path::new(cwd).file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| cwd.to_string())
+}
+
+/// Store the result of a recorded build and update the project's last build status
+fn record_result(
+    history: &BuildHistory,
+    projects: &Mutex<ProjectManager>,
+    record: &BuildRecord,
+    outcome: &Result<BuildOutcome, String>,
+    artifacts_dir: Option<&Path>,
+) -> Result<(), String> {
+    let (status, exit_code) = match outcome {
+        Ok(outcome) => (outcome.status(), outcome.exit_code()),
+        Err(_) => ("failure", None),
+    };
+    if let Some(finished) = history.finish(&record.id, status, exit_code, artifacts_dir)? {
+        let finished_at = finished.finished_at.unwrap_or_default();
+        projects.lock().unwrap().record_build(Path::new(&record.project_dir), &finished_at, status);
+    }
+    Ok(())
+}
+
+/// Record a validated job in the build history, run it and collect its artifacts
+fn run_recorded(
+    job: &BuildJob,
+    security_config: &SecurityConfig,
+    cancel: &AtomicBool,
+    sink: OutputSink,
+    history: &BuildHistory,
+    projects: &Mutex<ProjectManager>,
+) -> Result<BuildOutcome, String> {
+    let project = project_name(&projects.lock().unwrap(), &job.cwd);
+    let mut record = BuildRecord::start(project, job.cwd.clone(), job.build_name.clone(), job.platform.clone(), job.command.clone());
+    // Record the toolchain the build runs with; in locked mode it must match the lockfile
+    let lock = if job.locked {
+        lockfile::read_lockfile(Path::new(&job.cwd)).and_then(|lockfile| {
+            let lockfile = lockfile.ok_or_else(|| format!("A locked build needs a {} in {}; run `lock` first", lockfile::LOCK_FILE, job.cwd))?;
+            lockfile::verify_job(&lockfile, job)
+        })
     } else {
-        window.emit("build-finished", Some("failure".to_string())).ok();
-        Err(format!("Command exited with status: {}", status))
+        Ok(lockfile::lock_job(job))
+    };
+    record.lock = lock.as_ref().ok().cloned();
+    record.matrix = job.matrix.clone();
+    let sink = history.start(&record, sink)?;
+
+    let mut artifacts_dir = None;
+    let outcome = lock
+        .and_then(|_| execute_build(job, security_config, cancel, sink.clone()))
+        .and_then(|(outcome, work_dir, reported)| {
+            // Keep the declared and the reported artifacts of successful builds
+            let patterns: Vec<String> = job.artifacts.iter().cloned().chain(reported).collect();
+            if matches!(outcome, BuildOutcome::Success) && !patterns.is_empty() {
+                match artifacts::collect_artifacts(
+                    &work_dir,
+                    Path::new(&job.cwd),
+                    &record.id,
+                    job.build_name.as_deref(),
+                    job.platform.as_deref(),
+                    &patterns,
+                )? {
+                    Some((dir, manifest)) => {
+                        sink(
+                            OutputStream::Stdout,
+                            format!("Collected {} artifact(s) into {}", manifest.artifacts.len(), dir.display()),
+                        );
+                        artifacts_dir = Some(dir);
+                    }
+                    None => sink(
+                        OutputStream::Stderr,
+                        format!("Warning: no files matched the artifact patterns: {}", patterns.join(", ")),
+                    ),
+                }
+            }
+            Ok(outcome)
+        });
+    if let Err(e) = &outcome {
+        sink(OutputStream::Stderr, e.clone());
+    }
+    if let Err(e) = record_result(history, projects, &record, &outcome, artifacts_dir.as_deref()) {
+        sink(OutputStream::Stderr, format!("Failed to record build history: {}", e));
+    }
+    outcome
+}
+
+/// Merge the system and user security policies with the project's and the build's settings
+pub fn resolve_security(
+    app_config_dir: Option<&Path>,
+    project_config: Option<&BuildStudioConfig>,
+    build_name: Option<&str>,
+) -> Result<EffectiveSecurity, String> {
+    resolve_security_from(SecurityConfig::default(), app_config_dir, project_config, build_name)
+}
+
+/// Security for builds started from the CLI, which runs builds in the project directory
+/// unless a policy, the project or the build turns `enable_sandbox` on
+pub fn resolve_cli_security(
+    app_config_dir: Option<&Path>,
+    project_config: Option<&BuildStudioConfig>,
+    build_name: Option<&str>,
+) -> Result<EffectiveSecurity, String> {
+    let base = SecurityConfig { enable_sandbox: false, ..SecurityConfig::default() };
+    resolve_security_from(base, app_config_dir, project_config, build_name)
+}
+
+fn resolve_security_from(
+    mut base: SecurityConfig,
+    app_config_dir: Option<&Path>,
+    project_config: Option<&BuildStudioConfig>,
+    build_name: Option<&str>,
+) -> Result<EffectiveSecurity, String> {
+    let policy = match app_config_dir {
+        Some(dir) => SecurityPolicy::load(dir)?,
+        None => SecurityPolicy::default(),
+    };
+    let project = project_config.and_then(|c| c.security.as_ref());
+    let configured = project_config
+        .zip(build_name)
+        .and_then(|(c, name)| c.builds.iter().find(|b| b.name == name));
+    // Build systems read manifests and sources from all over the project; a language build
+    // gets all of it, without its caches, unless a policy or the config sets `allowed_paths`
+    if configured.and_then(|b| b.language.as_deref()).and_then(language::adapter).is_some() {
+        base.allowed_paths = vec![".".to_string()];
     }
+    let build = configured.and_then(|b| b.security.as_ref());
+    let effective = EffectiveSecurity::resolve_from(base, &policy, project, build);
+
+    // The fetch step of a local build runs outside the network namespace. Once the policy locks
+    // network isolation, only the language's own fetch command may do that.
+    let isolated = effective.config.enable_sandbox && effective.config.network_isolation;
+    if let (Some(build), true) = (configured, isolated && policy.locked.iter().any(|l| l == "network_isolation")) {
+        let fetch = build.fetch.as_deref().filter(|fetch| !fetch.trim().is_empty());
+        let builtin = build.language.as_deref().and_then(language::adapter).and_then(|a| a.fetch_command);
+        if build.container.is_none() && fetch.is_some() && fetch != builtin {
+            return Err(format!(
+                "Build '{}' sets its own fetch command, which would run with network access, \
+                 but the security policy locks network_isolation",
+                build.name
+            ));
+        }
+    }
+    Ok(effective)
 }
 
-/// Synchronous build runner for CLI (no Tauri window)
-pub fn run_build_no_window(command: String, cwd: String, platform: Option<String>) -> Result<(), String> {
-    use std::process::{Command, Stdio};
-    use std::io::{BufRead, BufReader};
-    let toolchain = platform
+/// Read the config of a project directory if it has one, with the selected profile applied
+pub(crate) fn read_project_config(cwd: &str, profile: Option<&str>) -> Result<Option<BuildStudioConfig>, String> {
+    match config::find_config(Path::new(cwd)) {
+        Some(path) => config::load_config_with(&path, profile, &Default::default())
+            .map(Some)
+            .map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+/// Validate a command from the GUI against the security policy; `ask` rules need the caller's approval
+pub(crate) fn check_policy(command: &str, security_config: &SecurityConfig, approved: bool) -> Result<(), String> {
+    match security::validate_command(command, security_config) {
+        PolicyDecision::Allow => Ok(()),
+        PolicyDecision::Ask(_) if approved => Ok(()),
+        PolicyDecision::Ask(violation) | PolicyDecision::Deny(violation) => Err(violation.message),
+    }
+}
+
+/// How the security policy treats a build's command, so the GUI can ask for confirmation before
+/// starting it. A configured build is checked with the command it runs, e.g. its plugin's.
+#[tauri::command]
+pub fn check_build_policy(
+    window: Window,
+    cwd: String,
+    command: String,
+    build_name: Option<String>,
+    profile: Option<String>,
+) -> Result<PolicyDecision, String> {
+    let app_config_dir = window.app_handle().path().app_config_dir().ok();
+    let project_config = read_project_config(&cwd, profile.as_deref())?;
+    let effective = resolve_security(app_config_dir.as_deref(), project_config.as_ref(), build_name.as_deref())?;
+    let configured = project_config
         .as_ref()
-        .map(|p| get_toolchain_for_platform(p))
-        .unwrap_or(Toolchain::Native);
-    let prefix = toolchain.command_prefix();
-    let full_command = if prefix.is_empty() {
-        command.clone()
-    } else {
-        format!("{}{}", prefix.join(""), command)
+        .zip(build_name.as_deref())
+        .and_then(|(config, name)| config.builds.iter().find(|b| b.name == name).map(|build| (config, build)));
+    let command = match configured {
+        Some((config, build)) => BuildJob::from_config(config, build, &cwd)?.policy_command(),
+        None => command,
     };
+    Ok(security::validate_command(&command, &effective.config))
+}
 
-    let mut cmd = if cfg!(target_os = "windows") {
-        let mut c = Command::new("cmd");
-        c.args(["/C", &full_command]);
-        c
-    } else {
-        let mut c = Command::new("sh");
-        c.args(["-c", &full_command]);
-        c
+/// Security settings a build would run with, for display before it starts
+#[tauri::command]
+pub fn get_effective_security(
+    window: Window,
+    cwd: String,
+    build_name: Option<String>,
+    profile: Option<String>,
+) -> Result<EffectiveSecurity, String> {
+    let app_config_dir = window.app_handle().path().app_config_dir().ok();
+    let project_config = read_project_config(&cwd, profile.as_deref())?;
+    resolve_security(app_config_dir.as_deref(), project_config.as_ref(), build_name.as_deref())
+}
+
+/// Resolve security for a job started from the GUI, validate it and run it,
+/// emitting the `build-*` events
+pub(crate) async fn run_gui_job(
+    window: Window,
+    state: State<'_, BuildManager>,
+    project_config: Option<&BuildStudioConfig>,
+    job: BuildJob,
+    build_id: Option<String>,
+    approved: bool,
+) -> Result<(), String> {
+    // Merge the user, project and build security settings and show them before starting
+    let app_config_dir = window.app_handle().path().app_config_dir().ok();
+    let effective = resolve_security(app_config_dir.as_deref(), project_config, job.build_name.as_deref())?;
+    window.emit("build-security", effective.clone()).ok();
+    let security_config = effective.config;
+
+    // Validate command against security policy
+    check_policy(&job.policy_command(), &security_config, approved)?;
+
+    // Register the build so it can be cancelled while running
+    let build_id = build_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let cancel = state.register(&build_id);
+    window.emit("build-started", build_id.clone()).ok();
+
+    let sink = window_sink(&window, None);
+    let app = window.app_handle().clone();
+    let max_build_time = security_config.max_build_time;
+    let outcome = tokio::task::spawn_blocking(move || {
+        let history = app.state::<BuildHistory>();
+        let projects = app.state::<Mutex<ProjectManager>>();
+        run_recorded(&job, &security_config, &cancel, sink, &history, &projects)
+    })
+    .await
+    .map_err(|e| format!("Failed to wait for command: {}", e))
+    .and_then(|outcome| outcome);
+    state.finish(&build_id);
+
+    let outcome = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            window.emit("build-finished", Some("failure".to_string())).ok();
+            return Err(e);
+        }
     };
-    cmd.current_dir(&cwd);
-    cmd.stdout(Stdio::piped());
-    cmd.stderr(Stdio::piped());
 
-    let mut child = cmd.spawn().map_err(|e| e.to_string())?;
-    let stdout = child.stdout.take().unwrap();
-    let stderr = child.stderr.take().unwrap();
+    window.emit("build-finished", Some(outcome.status().to_string())).ok();
+    outcome.into_result(max_build_time)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_build(
+    window: Window,
+    state: State<'_, BuildManager>,
+    command: String,
+    cwd: String,
+    platform: Option<String>,
+    build_id: Option<String>,
+    build_name: Option<String>,
+    approved: Option<bool>,
+    profile: Option<String>,
+    locked: Option<bool>,
+) -> Result<(), String> {
+    // Artifacts, container image, language and toolchain come from the project config entry the build was started from
+    let project_config = read_project_config(&cwd, profile.as_deref())?;
+    let configured = project_config
+        .as_ref()
+        .zip(build_name.as_deref())
+        .and_then(|(config, name)| config.builds.iter().find(|b| b.name == name));
+    let toolchain = toolchain::resolve_profile(
+        platform.as_deref(),
+        configured.and_then(|b| b.target.as_deref()),
+        configured.and_then(|b| b.toolchain.as_deref()),
+        project_config.as_ref().map(|c| c.toolchains.as_slice()).unwrap_or_default(),
+    )?;
+    let job = BuildJob {
+        fetch: configured.and_then(|b| b.fetch.clone()).filter(|fetch| !fetch.trim().is_empty()),
+        plugin: None,
+        artifacts: configured.map(|b| b.artifacts.clone()).unwrap_or_default(),
+        container: configured.and_then(|b| b.container.clone()),
+        language: configured.and_then(|b| b.language.clone()),
+        node: None,
+        toolchain,
+        locked: locked.unwrap_or(false),
+        matrix: configured.and_then(|b| b.matrix_cell.as_ref()).map(|cell| cell.values.clone()).unwrap_or_default(),
+        command,
+        cwd,
+        platform,
+        build_name,
+    }
+    .with_plugin(configured.and_then(|b| b.plugin.as_deref()))?;
+    run_gui_job(window, state, project_config.as_ref(), job, build_id, approved.unwrap_or(false)).await
+}
+
+/// Run one of the project's configured builds on a local Docker node
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_build_on_node(
+    window: Window,
+    state: State<'_, BuildManager>,
+    node_id: String,
+    cwd: String,
+    build: String,
+    build_id: Option<String>,
+    approved: Option<bool>,
+    profile: Option<String>,
+    locked: Option<bool>,
+) -> Result<(), String> {
+    let project_config = read_project_config(&cwd, profile.as_deref())?.ok_or_else(|| format!("No build config in {}", cwd))?;
+    let configured = project_config
+        .builds
+        .iter()
+        .find(|b| b.name == build)
+        .ok_or_else(|| format!("Unknown build: {}", build))?;
+    let mut job = BuildJob::from_config(&project_config, configured, &cwd)?;
+    job.node = Some(node_id);
+    job.locked = locked.unwrap_or(false);
+    run_gui_job(window, state, Some(&project_config), job, build_id, approved.unwrap_or(false)).await
+}
+
+/// Run a project's builds, or `targets` and their dependencies, in dependency order with up to
+/// `jobs` at a time. Cancelling `graph_id` cancels every build of the run.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_build_graph(
+    window: Window,
+    state: State<'_, BuildManager>,
+    cwd: String,
+    targets: Option<Vec<String>>,
+    jobs: Option<usize>,
+    keep_going: Option<bool>,
+    graph_id: Option<String>,
+    approved: Option<bool>,
+    profile: Option<String>,
+    locked: Option<bool>,
+) -> Result<Vec<BuildSummary>, String> {
+    let config = read_project_config(&cwd, profile.as_deref())?.ok_or_else(|| format!("No build config in {}", cwd))?;
+    let targets = targets.unwrap_or_default();
+    // Report unknown builds and cycles before anything runs
+    BuildGraph::new(&config.builds)?.select(&targets)?;
+
+    let app_config_dir = window.app_handle().path().app_config_dir().ok();
+    let jobs = jobs.unwrap_or_else(num_cpus::get);
+    let mode = if keep_going.unwrap_or(false) { FailureMode::KeepGoing } else { FailureMode::FailFast };
+
+    let graph_id = graph_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let cancel = state.register(&graph_id);
+    window.emit("build-started", graph_id.clone()).ok();
+
+    let app = window.app_handle().clone();
+    let graph_window = window.clone();
+    let summaries = tokio::task::spawn_blocking(move || {
+        let history = app.state::<BuildHistory>();
+        let projects = app.state::<Mutex<ProjectManager>>();
+        let graph = BuildGraph::new(&config.builds)?;
+        let selected = graph.select(&targets)?;
+        Ok::<_, String>(graph.run(&selected, jobs, mode, |build| {
+            let effective = resolve_security(app_config_dir.as_deref(), Some(&config), Some(&build.name))?;
+            graph_window.emit("build-security", effective.clone()).ok();
+            let mut job = BuildJob::from_config(&config, build, &cwd)?;
+            job.locked = locked.unwrap_or(false);
+            check_policy(&job.policy_command(), &effective.config, approved.unwrap_or(false))?;
+            let sink = window_sink(&graph_window, (jobs > 1).then(|| build.name.clone()));
+            run_recorded(&job, &effective.config, &cancel, sink, &history, &projects)?
+                .into_result(effective.config.max_build_time)
+        }))
+    })
+    .await
+    .map_err(|e| format!("Failed to wait for builds: {}", e))
+    .and_then(|summaries| summaries);
+    state.finish(&graph_id);
+
+    let status = match &summaries {
+        Ok(summaries) if summaries.iter().all(|s| s.status == "success") => "success",
+        _ => "failure",
+    };
+    window.emit("build-finished", Some(status.to_string())).ok();
+    summaries
+}
+
+/// Request cancellation of a running build started with `run_build` or `run_build_graph`
+#[tauri::command]
+pub fn cancel_build(state: State<'_, BuildManager>, build_id: String) -> Result<(), String> {
+    state.cancel(&build_id)
+}
+
+/// Set by the CLI's Ctrl-C handler; checked by `run_build_no_window`
+static CLI_CANCEL: AtomicBool = AtomicBool::new(false);
+static CLI_CTRL_C: Once = Once::new();
+
+/// Install a process-wide Ctrl-C handler that cancels the running CLI build
+fn install_ctrl_c_handler() {
+    CLI_CTRL_C.call_once(|| {
+        thread::spawn(|| {
+            let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(rt) => rt,
+                Err(_) => return,
+            };
+            while runtime.block_on(tokio::signal::ctrl_c()).is_ok() {
+                // A second Ctrl-C while the build is still being torn down aborts the CLI
+                if CLI_CANCEL.swap(true, Ordering::SeqCst) {
+                    std::process::exit(130);
+                }
+            }
+        });
+    });
+}
 
-    // Print stdout
-    let stdout_reader = BufReader::new(stdout);
-    for line in stdout_reader.lines() {
-        if let Ok(line) = line {
-            println!("{}", line);
+/// Held while asking on the terminal so that builds running in parallel ask one at a time
+static CLI_PROMPT: Mutex<()> = Mutex::new(());
+
+/// Ask on the terminal whether a command matched by an `ask` rule may run
+fn confirm_on_terminal(message: &str) -> bool {
+    if !std::io::stdin().is_terminal() {
+        return false;
+    }
+    let _prompt = CLI_PROMPT.lock().unwrap();
+    eprint!("{}\nRun it anyway? [y/N] ", message);
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Synchronous build runner for CLI (no Tauri window), writing output to `sink`
+pub fn run_build_no_window(job: &BuildJob, security_config: &SecurityConfig, sink: OutputSink) -> Result<(), String> {
+    install_ctrl_c_handler();
+    if CLI_CANCEL.load(Ordering::SeqCst) {
+        return Err("Build cancelled".to_string());
+    }
+
+    match security::validate_command(&job.policy_command(), security_config) {
+        PolicyDecision::Allow => {}
+        PolicyDecision::Ask(violation) if confirm_on_terminal(&violation.message) => {}
+        PolicyDecision::Ask(violation) | PolicyDecision::Deny(violation) => return Err(violation.message),
+    }
+
+    // Record the run in the same history the GUI uses
+    let app_config_dir = crate::fsutils::app_config_dir().ok_or("Could not determine the app config directory")?;
+    std::fs::create_dir_all(&app_config_dir).map_err(|e| format!("Failed to create app config dir: {}", e))?;
+    let history = BuildHistory::new(&app_config_dir);
+    let mut projects = ProjectManager::default();
+    projects.load_projects(&app_config_dir);
+
+    match run_recorded(job, security_config, &CLI_CANCEL, sink, &history, &Mutex::new(projects))? {
+        BuildOutcome::Failure(_) => Err("Build failed".to_string()),
+        other => other.into_result(security_config.max_build_time),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn quiet() -> OutputSink {
+        Arc::new(|_, _| {})
+    }
+
+    /// Whether process `pid` has exited; one whose parent was killed may linger as a zombie
+    #[cfg(target_os = "linux")]
+    fn has_exited(pid: &str) -> bool {
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => stat.rsplit(')').next().is_some_and(|state| matches!(state.trim_start().chars().next(), Some('Z' | 'X'))),
+            Err(_) => true,
         }
     }
-    // Print stderr
-    let stderr_reader = BufReader::new(stderr);
-    for line in stderr_reader.lines() {
-        if let Ok(line) = line {
-            eprintln!("{}", line);
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn timed_out_builds_are_killed_with_their_children() {
+        let pid_file = std::env::temp_dir().join(format!("build-studio-sleep-{}.pid", uuid::Uuid::new_v4()));
+        let command = format!("sleep 30 & echo $! > '{}'; wait", pid_file.display());
+        let started = Instant::now();
+        let outcome = run_streamed(shell_command(&command, &[]), Some(1), &AtomicBool::new(false), &quiet()).unwrap();
+        assert_eq!(outcome.status(), "timeout");
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        std::fs::remove_file(&pid_file).unwrap();
+        let killed = Instant::now();
+        while !has_exited(pid.trim()) {
+            assert!(killed.elapsed() < Duration::from_secs(5), "sleep {} outlived its build", pid.trim());
+            thread::sleep(POLL_INTERVAL);
         }
     }
 
-    let status = child.wait().map_err(|e| e.to_string())?;
-    if status.success() {
-        Ok(())
-    } else {
-        Err("Build failed".to_string())
+    #[test]
+    fn cancel_build_stops_a_running_build() {
+        let manager = BuildManager::default();
+        let cancel = manager.register("build-1");
+        let build = thread::spawn(move || run_streamed(shell_command("sleep 30", &[]), None, &cancel, &quiet()));
+        thread::sleep(Duration::from_millis(300));
+        manager.cancel("build-1").unwrap();
+        assert_eq!(build.join().unwrap().unwrap().status(), "cancelled");
+
+        manager.finish("build-1");
+        assert_eq!(manager.cancel("build-1"), Err("No running build with id build-1".to_string()));
+    }
+
+    #[test]
+    fn locked_network_isolation_refuses_a_project_fetch() {
+        let app_config_dir = std::env::temp_dir().join(format!("build-studio-fetch-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&app_config_dir).unwrap();
+        let project = |fetch: &str| -> BuildStudioConfig {
+            let mut config: BuildStudioConfig = serde_yaml::from_str(&format!(
+                "builds:\n  - name: app\n    platform: linux\n    language: rust\n{}",
+                fetch
+            ))
+            .unwrap();
+            config.apply_language_defaults().unwrap();
+            config
+        };
+        let resolve = |config: &BuildStudioConfig| resolve_security(Some(&app_config_dir), Some(config), Some("app"));
+        let custom = project("    fetch: curl -sSf https://example.com/deps.sh | sh\n");
+
+        assert!(resolve(&custom).is_ok());
+        std::fs::write(
+            app_config_dir.join(security::SECURITY_POLICY_FILE),
+            r#"{"defaults": {"network_isolation": true}, "locked": ["network_isolation"]}"#,
+        )
+        .unwrap();
+        let error = resolve(&custom).unwrap_err();
+        assert!(error.contains("locks network_isolation"), "{}", error);
+        // The language's own fetch step, or none at all, is fine
+        assert!(resolve(&project("")).is_ok());
+        assert!(resolve(&project("    fetch: cargo fetch\n")).is_ok());
+        assert!(resolve(&project("    fetch: ''\n")).is_ok());
+        std::fs::remove_dir_all(app_config_dir).unwrap();
+    }
+
+    #[test]
+    fn cli_refuses_blocked_commands_without_a_sandbox() {
+        let security = resolve_cli_security(None, None, None).unwrap().config;
+        assert!(!security.enable_sandbox);
+        let job = BuildJob { command: "make && rm -rf /".to_string(), cwd: ".".to_string(), ..Default::default() };
+        let error = run_build_no_window(&job, &security, quiet()).unwrap_err();
+        assert!(error.contains("rm"), "{}", error);
     }
 }
@@ -0,0 +1,286 @@
+//! Config composition for Build Studio
+//! Resolves a config's `include:` files, the selected entry of its `profiles:` and its builds'
+//! `extends:` into a single config. Maps merge key by key with the later value winning; lists and
+//! other values are replaced whole, except `builds`, which merge by `name`.
+
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config::{self, ConfigError};
+
+/// Whether a parsed config includes other files, has profiles or has builds that extend others
+pub fn is_composed(config: &Value) -> bool {
+    let has = |key: &str| config.get(key).is_some();
+    let extends = config
+        .get("builds")
+        .and_then(Value::as_array)
+        .is_some_and(|builds| builds.iter().any(|b| b.get("extends").is_some()));
+    has("include") || has("profiles") || extends
+}
+
+/// Merge `overlay` into `base`: maps key by key, everything else replaced
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => merge_map(base, overlay),
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn merge_map(base: &mut Map<String, Value>, overlay: Map<String, Value>) {
+    for (key, value) in overlay {
+        match base.get_mut(&key) {
+            Some(existing) => merge(existing, value),
+            None => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Merge a config (or profile) over another; builds with the same name merge, new builds are
+/// appended in order
+fn merge_config(base: &mut Map<String, Value>, mut overlay: Map<String, Value>) {
+    match (base.get_mut("builds"), overlay.remove("builds")) {
+        (Some(Value::Array(builds)), Some(Value::Array(overlay_builds))) => {
+            for build in overlay_builds {
+                let name = build.get("name").cloned();
+                match builds.iter_mut().find(|b| name.is_some() && b.get("name") == name.as_ref()) {
+                    Some(existing) => merge(existing, build),
+                    None => builds.push(build),
+                }
+            }
+        }
+        (_, Some(overlay_builds)) => {
+            base.insert("builds".to_string(), overlay_builds);
+        }
+        (_, None) => {}
+    }
+    merge_map(base, overlay);
+}
+
+fn as_object(value: Value, path: &Path) -> Result<Map<String, Value>, ConfigError> {
+    match value {
+        Value::Object(map) => Ok(map),
+        _ => Err(ConfigError::new(path, "A config must be a map of settings")),
+    }
+}
+
+/// `config` with the files it includes merged beneath it, in the order they are listed.
+/// Included files may include others; paths are relative to the file that names them.
+fn with_includes(value: Value, path: &Path, stack: &mut Vec<PathBuf>) -> Result<Map<String, Value>, ConfigError> {
+    let mut config = as_object(value, path)?;
+    let includes = match config.remove("include") {
+        None => Vec::new(),
+        Some(Value::String(include)) => vec![include],
+        Some(Value::Array(includes)) => includes
+            .into_iter()
+            .map(|i| i.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| ConfigError::new(path, "include: must list file paths"))?,
+        Some(_) => return Err(ConfigError::new(path, "include: must list file paths")),
+    };
+
+    let mut merged = Map::new();
+    for include in includes {
+        if include.contains("://") {
+            return Err(ConfigError::new(path, format!("Only local files can be included: {}", include)));
+        }
+        let file = path.parent().unwrap_or(Path::new(".")).join(&include);
+        let canonical = fs::canonicalize(&file)
+            .map_err(|e| ConfigError::new(path, format!("Failed to read included config {}: {}", include, e)))?;
+        if stack.contains(&canonical) {
+            let chain: Vec<String> = stack.iter().chain([&canonical]).map(|p| p.display().to_string()).collect();
+            return Err(ConfigError::new(path, format!("Configs include each other: {}", chain.join(" -> "))));
+        }
+        let content = fs::read_to_string(&file)
+            .map_err(|e| ConfigError::new(path, format!("Failed to read included config {}: {}", include, e)))?;
+        let value = config::parse_value(&content, &file)?;
+        stack.push(canonical);
+        let included = with_includes(value, &file, stack)?;
+        stack.pop();
+        merge_config(&mut merged, included);
+    }
+    merge_config(&mut merged, config);
+    Ok(merged)
+}
+
+/// A build with the builds it extends merged beneath it
+fn extended_build(name: &str, builds: &[Value], stack: &mut Vec<String>) -> Result<Value, String> {
+    let build = builds
+        .iter()
+        .find(|b| b.get("name").and_then(Value::as_str) == Some(name))
+        .ok_or_else(|| format!("Build '{}' extends unknown build '{}'", stack.last().map_or("", |s| s), name))?;
+    let Some(parent) = build.get("extends").and_then(Value::as_str) else {
+        return Ok(build.clone());
+    };
+    if stack.iter().any(|s| s == parent) || parent == name {
+        stack.push(name.to_string());
+        stack.push(parent.to_string());
+        return Err(format!("Builds extend each other in a cycle: {}", stack.join(" -> ")));
+    }
+    stack.push(name.to_string());
+    let mut extended = extended_build(parent, builds, stack)?;
+    stack.pop();
+    let mut own = build.clone();
+    if let Some(own) = own.as_object_mut() {
+        own.remove("extends");
+    }
+    merge(&mut extended, own);
+    Ok(extended)
+}
+
+fn resolve_extends(config: &mut Map<String, Value>, path: &Path) -> Result<(), ConfigError> {
+    let Some(Value::Array(builds)) = config.get("builds") else {
+        return Ok(());
+    };
+    let mut resolved = Vec::with_capacity(builds.len());
+    for build in builds {
+        match build.get("name").and_then(Value::as_str) {
+            Some(name) if build.get("extends").is_some() => resolved.push(
+                extended_build(name, builds, &mut Vec::new()).map_err(|e| ConfigError::new(path, e))?,
+            ),
+            _ => resolved.push(build.clone()),
+        }
+    }
+    config.insert("builds".to_string(), Value::Array(resolved));
+    Ok(())
+}
+
+/// Names of the profiles a config defines, its included files' profiles among them
+pub fn profile_names(value: Value, path: &Path) -> Result<Vec<String>, ConfigError> {
+    let config = with_includes(value, path, &mut fs::canonicalize(path).into_iter().collect())?;
+    Ok(config
+        .get("profiles")
+        .and_then(Value::as_object)
+        .map(|profiles| profiles.keys().cloned().collect())
+        .unwrap_or_default())
+}
+
+/// Resolve a parsed config: merge its includes beneath it, then the selected `profile` over it,
+/// then each build over the build it extends
+pub fn resolve(value: Value, path: &Path, profile: Option<&str>) -> Result<Value, ConfigError> {
+    let mut config = with_includes(value, path, &mut fs::canonicalize(path).into_iter().collect())?;
+    let profiles = match config.remove("profiles") {
+        Some(Value::Object(profiles)) => profiles,
+        None => Map::new(),
+        Some(_) => return Err(ConfigError::new(path, "profiles: must map profile names to settings")),
+    };
+    if let Some(name) = profile {
+        let overlay = profiles.get(name).cloned().ok_or_else(|| {
+            let known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+            let message = if known.is_empty() {
+                format!("Unknown profile '{}'; the config defines no profiles", name)
+            } else {
+                format!("Unknown profile '{}'; the config defines {}", name, known.join(", "))
+            };
+            ConfigError::new(path, message).suggest(config::closest(name, known))
+        })?;
+        let overlay = as_object(overlay, path)?;
+        if overlay.contains_key("include") || overlay.contains_key("profiles") {
+            return Err(ConfigError::new(path, format!("Profile '{}' cannot include files or define profiles", name)));
+        }
+        merge_config(&mut config, overlay);
+    }
+    resolve_extends(&mut config, path)?;
+    Ok(Value::Object(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Write `files` into a new project directory and return it
+    fn project(files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("build-studio-compose-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, content) in files {
+            fs::write(dir.join(name), content).unwrap();
+        }
+        dir
+    }
+
+    fn resolved(dir: &Path, file: &str, profile: Option<&str>) -> Result<Value, ConfigError> {
+        let path = dir.join(file);
+        let value = config::parse_value(&fs::read_to_string(&path).unwrap(), &path)?;
+        resolve(value, &path, profile)
+    }
+
+    #[test]
+    fn builds_merge_by_name_across_include_and_profile() {
+        let dir = project(&[
+            ("common.yaml", "builds:\n  - name: app\n    platform: linux\n    command: make\n"),
+            (
+                "buildstudio.config.yaml",
+                "include: common.yaml\nbuilds:\n  - name: app\n    artifacts: [\"out/*\"]\n  - name: docs\n    platform: linux\n    command: make docs\nprofiles:\n  release:\n    builds:\n      - name: app\n        command: make release\n",
+            ),
+        ]);
+        let config = resolved(&dir, "buildstudio.config.yaml", None).unwrap();
+        assert_eq!(
+            config["builds"],
+            json!([
+                {"name": "app", "platform": "linux", "command": "make", "artifacts": ["out/*"]},
+                {"name": "docs", "platform": "linux", "command": "make docs"},
+            ])
+        );
+        let release = resolved(&dir, "buildstudio.config.yaml", Some("release")).unwrap();
+        assert_eq!(
+            release["builds"][0],
+            json!({"name": "app", "platform": "linux", "command": "make release", "artifacts": ["out/*"]})
+        );
+        assert_eq!(release["builds"].as_array().unwrap().len(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn lists_are_replaced_not_appended() {
+        let dir = project(&[
+            ("common.yaml", "builds:\n  - name: app\n    artifacts: [\"a/*\", \"b/*\"]\n    depends_on: [lib]\n"),
+            (
+                "buildstudio.config.yaml",
+                "include: common.yaml\nbuilds:\n  - name: app\n    artifacts: [\"c/*\"]\nprofiles:\n  ci:\n    builds:\n      - name: app\n        depends_on: []\n",
+            ),
+        ]);
+        let config = resolved(&dir, "buildstudio.config.yaml", Some("ci")).unwrap();
+        assert_eq!(config["builds"][0]["artifacts"], json!(["c/*"]));
+        assert_eq!(config["builds"][0]["depends_on"], json!([]));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let dir = project(&[
+            ("buildstudio.config.yaml", "include: a.yaml\n"),
+            ("a.yaml", "include: b.yaml\n"),
+            ("b.yaml", "include: a.yaml\n"),
+        ]);
+        let error = resolved(&dir, "buildstudio.config.yaml", None).unwrap_err();
+        assert!(error.message.starts_with("Configs include each other: "), "{}", error.message);
+        let a = fs::canonicalize(dir.join("a.yaml")).unwrap();
+        assert!(error.message.ends_with(&format!("b.yaml -> {}", a.display())), "{}", error.message);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn extends_cycles_are_errors() {
+        let dir = project(&[(
+            "buildstudio.config.yaml",
+            "builds:\n  - name: a\n    extends: b\n  - name: b\n    extends: c\n  - name: c\n    extends: a\n",
+        )]);
+        let error = resolved(&dir, "buildstudio.config.yaml", None).unwrap_err();
+        assert_eq!(error.message, "Builds extend each other in a cycle: a -> b -> c -> a");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_profiles_suggest_the_closest_one() {
+        let dir = project(&[(
+            "buildstudio.config.yaml",
+            "builds: []\nprofiles:\n  debug: {}\n  release: {}\n",
+        )]);
+        let error = resolved(&dir, "buildstudio.config.yaml", Some("relase")).unwrap_err();
+        assert_eq!(error.message, "Unknown profile 'relase'; the config defines debug, release");
+        assert_eq!(error.suggestion.as_deref(), Some("release"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
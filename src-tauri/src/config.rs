@@ -1,31 +1,698 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject, SubschemaValidation};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
+use std::path::{Path, PathBuf};
+use crate::compose;
+use crate::graph::BuildGraph;
+use crate::language;
+use crate::matrix::{self, Matrix, MatrixCell};
+use crate::packaging;
+use crate::security::SecurityOverrides;
+use crate::toolchain::{self, Toolchain, ToolchainProfile};
+use crate::vars::Variables;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Config file names looked for in a project directory, in order
+pub const CONFIG_FILES: &[&str] = &[
+    "buildstudio.config.yaml",
+    "buildstudio.config.yml",
+    "buildstudio.config.json",
+    "buildstudio.config.toml",
+];
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct BuildConfig {
+    /// Unique name of the build, used by `depends_on` and `buildstudio-cli build`
     pub name: String,
+    /// Name of a build whose fields this one inherits and overrides; resolved when the config loads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// Platform to build for, such as `linux`, `windows` or `linux-aarch64`; selects the default toolchain
+    #[schemars(schema_with = "platform_schema")]
     pub platform: String,
+    /// Language or build system whose defaults fill in `command` and `artifacts`
+    #[serde(default)]
+    #[schemars(schema_with = "language_schema")]
     pub language: Option<String>,
+    /// Shell command that runs the build, with `${NAME}` variables. Defaults to the language
+    /// adapter's command when omitted
+    #[serde(default)]
     pub command: String,
+    /// Shell command that downloads the build's dependencies, with `${NAME}` variables. It runs
+    /// before `command` with network access, even when the build itself is isolated. Defaults to
+    /// the language adapter's when `command` is omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetch: Option<String>,
+    /// Plugin command to run instead of `command`, as `<plugin>/<command>`, e.g.
+    /// `Sample Go Toolchain/build-go`; plugins are found in the project's `plugins/` directory
+    /// and then the app's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>,
+    /// Container image to run the build in, with `${NAME}` variables
+    #[serde(default)]
     pub container: Option<String>,
+    /// Toolchain to build with: a built-in (`mingw`, `android-ndk`, `emscripten`, `wasm-pack`,
+    /// `gcc`, `zig`, `cross`, `native`) or a profile from `toolchains:`. Defaults to the
+    /// platform's toolchain.
+    #[serde(default)]
+    #[schemars(schema_with = "toolchain_schema")]
+    pub toolchain: Option<String>,
+    /// Target triple to cross-compile for, e.g. `aarch64-unknown-linux-musl`
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Globs, relative to the build directory, of files to keep after a successful run
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    /// Names of builds that must succeed before this one starts
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Security settings for this build only; merged over the project's `security:` section
+    #[serde(default)]
+    pub security: Option<SecurityOverrides>,
+    /// Axes to build every combination of, e.g. `platform: [linux, windows]`; each combination
+    /// becomes a build with `${matrix.<axis>}` replaced by its values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<Matrix>,
+    /// For a build generated from a matrix, the build declaring it and the combination's values
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    #[schemars(skip)]
+    pub matrix_cell: Option<MatrixCell>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct PackageConfig {
+    /// Kind of package to create
+    #[serde(default)]
+    #[schemars(schema_with = "package_type_schema")]
     pub r#type: Option<String>,
+    /// Package name; defaults to the project name
+    #[serde(default)]
     pub name: Option<String>,
+    #[serde(default)]
     pub version: Option<String>,
+    /// Packages the created package depends on
+    #[serde(default)]
     pub dependencies: Option<Vec<String>>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct BuildStudioConfig {
+    /// Config files merged beneath this one, as paths relative to it; resolved when the config loads
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Named overlays such as `debug` and `release`, each shaped like this config, selected with
+    /// `--profile`; resolved when the config loads
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, serde_json::Value>,
+    /// Variables for `${NAME}` in commands, container images and the package section; the
+    /// environment and `.env` override them
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    /// Builds of the project, run in dependency order
     pub builds: Vec<BuildConfig>,
+    /// How to package the build artifacts
+    #[serde(default)]
     pub package: Option<PackageConfig>,
+    /// Project-wide security settings, merged over the user's defaults
+    #[serde(default)]
+    pub security: Option<SecurityOverrides>,
+    /// Custom cross-compilation profiles, selected by a build's `toolchain:` or `platform:`
+    #[serde(default)]
+    pub toolchains: Vec<ToolchainProfile>,
+}
+
+impl BuildConfig {
+    /// Fill in the command and artifacts of a build that only declares a `language:` (and a `target:`)
+    fn apply_language_defaults(&mut self) -> Result<(), String> {
+        let adapter = self.language.as_deref().and_then(language::adapter);
+        // Rust needs the triple on its command line; other languages get it from the toolchain
+        let target = self.target.clone().or_else(|| toolchain::platform_target(&self.platform));
+        let target = target.as_deref();
+        if let Some(reference) = &self.plugin {
+            if !self.command.trim().is_empty() {
+                return Err(format!("Build '{}' runs plugin {} and cannot also have a command", self.name, reference));
+            }
+            crate::plugin::parse_reference(reference).map_err(|e| format!("Build '{}' plugin: {}", self.name, e))?;
+        } else if self.command.trim().is_empty() {
+            self.command = match (adapter, &self.language) {
+                (Some(adapter), _) => adapter.command_for(target),
+                (None, Some(language)) => {
+                    return Err(format!("Build '{}' has no command and unknown language: {}", self.name, language))
+                }
+                (None, None) => return Err(format!("Build '{}' needs a command or a language", self.name)),
+            };
+            if self.fetch.is_none() {
+                self.fetch = adapter.and_then(|a| a.fetch_command).map(str::to_string);
+            }
+            // cross takes the same arguments as cargo. A command written out in the config is
+            // run as it is, so it calls `cross` itself.
+            if self.toolchain.as_deref() == Some("cross") {
+                let rest = self.command.strip_prefix("cargo ").ok_or_else(|| {
+                    format!("Build '{}' uses the cross toolchain, which only runs cargo builds", self.name)
+                })?;
+                self.command = format!("cross {}", rest);
+            }
+        }
+        if self.artifacts.is_empty() {
+            if let Some(adapter) = adapter {
+                self.artifacts = adapter.artifacts_for(target);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BuildStudioConfig {
+    /// Fill in the command and artifacts of builds that only declare a `language:`
+    pub fn apply_language_defaults(&mut self) -> Result<(), String> {
+        self.builds.iter_mut().try_for_each(BuildConfig::apply_language_defaults)
+    }
+
+    /// Replace builds that declare a `matrix:` with one build per combination. Returns the names
+    /// of the builds whose matrix is invalid, which are left as they are, with why.
+    pub fn expand_matrices(&mut self) -> Vec<(String, String)> {
+        let mut errors = Vec::new();
+        let mut builds = Vec::with_capacity(self.builds.len());
+        for build in self.builds.drain(..) {
+            match matrix::expand(&build) {
+                Ok(cells) => builds.extend(cells),
+                Err(e) => {
+                    errors.push((build.name.clone(), e));
+                    builds.push(build);
+                }
+            }
+        }
+        self.builds = builds;
+        errors
+    }
+
+    /// Replace `${NAME}` variables in the package section, then in each build's command and
+    /// container. Returns every failure with the text it occurred in.
+    pub fn interpolate(&mut self, variables: &mut Variables) -> Vec<(String, String)> {
+        let mut errors = Vec::new();
+        let mut expand = |text: &mut String, build: Option<&BuildConfig>, variables: &Variables| {
+            match variables.expand(text, build) {
+                Ok(expanded) => *text = expanded,
+                Err(e) => errors.push((text.clone(), e)),
+            }
+        };
+        if let Some(package) = &mut self.package {
+            let fields = [&mut package.r#type, &mut package.name, &mut package.version];
+            for field in fields.into_iter().flatten() {
+                expand(field, None, variables);
+            }
+            for dependency in package.dependencies.iter_mut().flatten() {
+                expand(dependency, None, variables);
+            }
+        }
+        variables.set_package_version(self.package.as_ref().and_then(|p| p.version.clone()));
+        for build in &mut self.builds {
+            let context = build.clone();
+            expand(&mut build.command, Some(&context), variables);
+            for field in [&mut build.fetch, &mut build.container].into_iter().flatten() {
+                expand(field, Some(&context), variables);
+            }
+        }
+        errors
+    }
+}
+
+/// A string schema offering `names` for completion while accepting any other string
+fn names_or_any(names: &[&str], optional: bool) -> Schema {
+    let string = |enum_values: Option<Vec<serde_json::Value>>| SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        enum_values,
+        ..Default::default()
+    };
+    let mut any = string(None);
+    if optional {
+        any.instance_type = Some(vec![InstanceType::String, InstanceType::Null].into());
+    }
+    let known = string(Some(names.iter().map(|name| (*name).into()).collect()));
+    SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(vec![known.into(), any.into()]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+fn platform_schema(_: &mut SchemaGenerator) -> Schema {
+    names_or_any(toolchain::PLATFORMS, false)
+}
+
+fn language_schema(_: &mut SchemaGenerator) -> Schema {
+    let names: Vec<&str> = language::ADAPTERS
+        .iter()
+        .flat_map(|a| std::iter::once(a.name).chain(a.aliases.iter().copied()))
+        .collect();
+    names_or_any(&names, true)
+}
+
+fn toolchain_schema(_: &mut SchemaGenerator) -> Schema {
+    // Custom profiles from `toolchains:` are accepted too
+    names_or_any(toolchain::TOOLCHAIN_NAMES, true)
+}
+
+fn package_type_schema(_: &mut SchemaGenerator) -> Schema {
+    let mut types: Vec<serde_json::Value> = packaging::PACKAGE_TYPES.iter().map(|t| (*t).into()).collect();
+    types.push(serde_json::Value::Null);
+    SchemaObject {
+        instance_type: Some(vec![InstanceType::String, InstanceType::Null].into()),
+        enum_values: Some(types),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// JSON Schema of the config file, for editor completion and validation
+pub fn config_schema() -> serde_json::Value {
+    let mut schema = serde_json::to_value(schemars::schema_for!(BuildStudioConfig)).unwrap_or_default();
+    if let Some(root) = schema.as_object_mut() {
+        root.insert("title".into(), "Build Studio config".into());
+    }
+    schema
+}
+
+/// Syntax of a config file, chosen by its extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+            Some("yaml") | Some("yml") => Ok(ConfigFormat::Yaml),
+            Some("json") => Ok(ConfigFormat::Json),
+            Some("toml") => Ok(ConfigFormat::Toml),
+            _ => Err(format!("Unsupported config format: {} (use .yaml, .yml, .json or .toml)", path.display())),
+        }
+    }
 }
 
+/// A problem in a config file, positioned where it is known (1-based line and column)
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigError {
+    pub file: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+    /// A likely intended value, e.g. the field name a misspelt one resembles
+    pub suggestion: Option<String>,
+}
+
+impl ConfigError {
+    pub(crate) fn new(path: &Path, message: impl Into<String>) -> Self {
+        Self {
+            file: path.display().to_string(),
+            line: None,
+            column: None,
+            message: message.into(),
+            suggestion: None,
+        }
+    }
+
+    fn at(mut self, position: Option<(usize, usize)>) -> Self {
+        if let Some((line, column)) = position {
+            self.line = Some(line);
+            self.column = Some(column);
+        }
+        self
+    }
+
+    pub(crate) fn suggest(mut self, suggestion: Option<String>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{}`?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// The candidate closest to `value`, if it is close enough to be a likely typo
+pub(crate) fn closest<'a>(value: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    candidates
+        .into_iter()
+        .map(|c| (strsim::jaro_winkler(value, c), c))
+        .filter(|(score, _)| *score >= 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, c)| c.to_string())
+}
+
+/// Suggestion for serde's "unknown field `x`, expected one of `a`, `b`" and "unknown variant" errors
+fn suggest_from_message(message: &str) -> Option<String> {
+    let re = regex::Regex::new(r"unknown (?:field|variant) `([^`]*)`, expected (.*)").ok()?;
+    let captures = re.captures(message)?;
+    let expected = captures.get(2)?.as_str();
+    let candidates = expected.split('`').skip(1).step_by(2);
+    closest(captures.get(1)?.as_str(), candidates)
+}
+
+/// 1-based line and column of a byte offset in `content`
+fn position_of(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map(|l| l.chars().count()).unwrap_or(0) + 1;
+    (line, column)
+}
+
+/// Line and column of the first occurrence of `needle` in `content`
+fn find_position(content: &str, needle: &str) -> Option<(usize, usize)> {
+    content.find(needle).map(|offset| position_of(content, offset))
+}
+
+/// Line and column of a `key`, written as `key:`, `"key":` or `key =`, and of its `value` when one
+/// is given, e.g. the `app` of `name: app`, `"name": "app"` or `name = "app"`
+fn find_key_position(content: &str, key: &str, value: Option<&str>) -> Option<(usize, usize)> {
+    let key = format!(r#"(?:^|[\s{{,-])["']?({})["']?\s*[:=]"#, regex::escape(key));
+    let pattern = match value {
+        Some(value) => format!(r#"(?m){}\s*["']?({})["']?\s*(?:[,}}#]|$)"#, key, regex::escape(value)),
+        None => format!("(?m){}", key),
+    };
+    let captures = regex::Regex::new(&pattern).ok()?.captures(content)?;
+    let found = captures.get(if value.is_some() { 2 } else { 1 })?;
+    Some(position_of(content, found.start()))
+}
+
+/// Position of a build's name, or of the name of the matrix build it was generated from
+fn build_position(content: &str, build: &BuildConfig) -> Option<(usize, usize)> {
+    let name = build.matrix_cell.as_ref().map_or(build.name.as_str(), |cell| cell.build.as_str());
+    find_key_position(content, "name", Some(name))
+}
+
+/// Parse `content` in the format its path names; configs reject unknown fields
+fn parse_as<T: DeserializeOwned>(content: &str, path: &Path) -> Result<T, ConfigError> {
+    let format = ConfigFormat::from_path(path).map_err(|e| ConfigError::new(path, e))?;
+    // The parsers append " at line X column Y" to their messages; the position is kept separately
+    let strip_position = |message: String| match message.find(" at line ") {
+        Some(i) => message[..i].to_string(),
+        None => message,
+    };
+    let (message, position) = match format {
+        ConfigFormat::Yaml => match serde_yaml::from_str(content) {
+            Ok(config) => return Ok(config),
+            Err(e) => (strip_position(e.to_string()), e.location().map(|l| (l.line(), l.column()))),
+        },
+        ConfigFormat::Json => match serde_json::from_str(content) {
+            Ok(config) => return Ok(config),
+            Err(e) => (strip_position(e.to_string()), Some((e.line(), e.column()))),
+        },
+        ConfigFormat::Toml => match toml::from_str(content) {
+            Ok(config) => return Ok(config),
+            Err(e) => (e.message().to_string(), e.span().map(|span| position_of(content, span.start))),
+        },
+    };
+    let suggestion = suggest_from_message(&message);
+    Err(ConfigError::new(path, message).at(position).suggest(suggestion))
+}
+
+/// Parse a config file without checking its fields, for merging it with others
+pub(crate) fn parse_value(content: &str, path: &Path) -> Result<serde_json::Value, ConfigError> {
+    parse_as(content, path)
+}
+
+/// Parse a config, resolving its includes, the selected `profile` and builds' `extends:`
+fn deserialize(content: &str, path: &Path, profile: Option<&str>) -> Result<BuildStudioConfig, ConfigError> {
+    let value = parse_value(content, path)?;
+    if profile.is_none() && !compose::is_composed(&value) {
+        // Parsing the text directly keeps the positions of errors
+        return parse_as(content, path);
+    }
+    let value = compose::resolve(value, path, profile)?;
+    serde_json::from_value(value).map_err(|e| {
+        let message = e.to_string();
+        // The merged config has no positions; point at the first use of an unknown field
+        let position = message
+            .strip_prefix("unknown field `")
+            .and_then(|rest| rest.split('`').next())
+            .and_then(|field| find_key_position(content, field, None));
+        let suggestion = suggest_from_message(&message);
+        ConfigError::new(path, message).at(position).suggest(suggestion)
+    })
+}
+
+/// Problems a config parses fine with but cannot run: missing commands, unknown toolchains,
+/// duplicate names and dependency errors
+fn check_config(config: &mut BuildStudioConfig, content: &str, path: &Path) -> Vec<ConfigError> {
+    let mut errors = Vec::new();
+    let custom: Vec<&str> = config.toolchains.iter().map(|t| t.name.as_str()).collect();
+    for build in &mut config.builds {
+        let position = build_position(content, build);
+        if let Err(e) = build.apply_language_defaults() {
+            let suggestion = build
+                .language
+                .as_deref()
+                .and_then(|l| closest(l, language::ADAPTERS.iter().map(|a| a.name)));
+            errors.push(ConfigError::new(path, e).at(position).suggest(suggestion));
+        }
+        if let Some(name) = build.toolchain.as_deref().filter(|name| !custom.contains(name)) {
+            match Toolchain::from_name(name, build.target.as_deref(), Some(&build.platform)) {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    let names = toolchain::TOOLCHAIN_NAMES.iter().chain(&custom).copied();
+                    errors.push(
+                        ConfigError::new(path, format!("Build '{}' has unknown toolchain: {}", build.name, name))
+                            .at(position)
+                            .suggest(closest(name, names)),
+                    );
+                }
+                Err(e) => errors.push(ConfigError::new(path, e).at(position)),
+            }
+        }
+    }
+    if let Err((i, e)) = BuildGraph::resolve(&config.builds) {
+        errors.push(ConfigError::new(path, e).at(build_position(content, &config.builds[i])));
+    }
+    errors
+}
+
+/// Replace the config's `${NAME}` variables, positioning each failure at the text it occurred in
+fn expand_variables(
+    config: &mut BuildStudioConfig,
+    content: &str,
+    path: &Path,
+    overrides: &BTreeMap<String, String>,
+) -> Vec<ConfigError> {
+    let project_dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut variables = match Variables::new(project_dir, &config.vars, overrides) {
+        Ok(variables) => variables,
+        Err(e) => return vec![ConfigError::new(path, e)],
+    };
+    config
+        .interpolate(&mut variables)
+        .into_iter()
+        .map(|(text, e)| ConfigError::new(path, e).at(find_position(content, &text)))
+        .collect()
+}
+
+/// Problems in the config `content` resolved with `profile`
+fn validate_profile(content: &str, path: &Path, profile: Option<&str>) -> Vec<ConfigError> {
+    match deserialize(content, path, profile) {
+        Ok(mut config) => {
+            let mut errors: Vec<ConfigError> = config
+                .expand_matrices()
+                .into_iter()
+                .map(|(name, e)| ConfigError::new(path, e).at(find_key_position(content, "name", Some(&name))))
+                .collect();
+            errors.extend(expand_variables(&mut config, content, path, &BTreeMap::new()));
+            errors.extend(check_config(&mut config, content, path));
+            errors
+        }
+        Err(e) => vec![e],
+    }
+}
+
+/// Every problem in the config `content` read from `path`, on its own and with each of its profiles
+pub fn validate(content: &str, path: &Path) -> Vec<ConfigError> {
+    let mut errors = validate_profile(content, path, None);
+    let profiles = parse_value(content, path).and_then(|value| compose::profile_names(value, path));
+    for profile in profiles.unwrap_or_default() {
+        for mut error in validate_profile(content, path, Some(&profile)) {
+            // Problems of the config itself are reported once
+            if !errors.iter().any(|e| e.message == error.message) {
+                error.message = format!("Profile '{}': {}", profile, error.message);
+                errors.push(error);
+            }
+        }
+    }
+    errors
+}
+
+/// Parse the config `content` read from `path` with the selected `profile`, replace its variables
+/// (`overrides` first) and fill in language defaults
+pub fn parse_config_with(
+    content: &str,
+    path: &Path,
+    profile: Option<&str>,
+    overrides: &BTreeMap<String, String>,
+) -> Result<BuildStudioConfig, ConfigError> {
+    let mut config = deserialize(content, path, profile)?;
+    if let Some((name, e)) = config.expand_matrices().into_iter().next() {
+        return Err(ConfigError::new(path, e).at(find_key_position(content, "name", Some(&name))));
+    }
+    if let Some(error) = expand_variables(&mut config, content, path, overrides).into_iter().next() {
+        return Err(error);
+    }
+    config.apply_language_defaults().map_err(|e| ConfigError::new(path, e))?;
+    Ok(config)
+}
+
+/// Parse the config `content` read from `path`
+pub fn parse_config(content: &str, path: &Path) -> Result<BuildStudioConfig, ConfigError> {
+    parse_config_with(content, path, None, &BTreeMap::new())
+}
+
+/// Read and parse a config file with the selected `profile`, with variables from `--var` taking
+/// precedence
+pub fn load_config_with(
+    path: &Path,
+    profile: Option<&str>,
+    overrides: &BTreeMap<String, String>,
+) -> Result<BuildStudioConfig, ConfigError> {
+    let content = fs::read_to_string(path).map_err(|e| ConfigError::new(path, format!("Failed to read config: {}", e)))?;
+    parse_config_with(&content, path, profile, overrides)
+}
+
+/// Read and parse a config file
+pub fn load_config(path: &Path) -> Result<BuildStudioConfig, ConfigError> {
+    load_config_with(path, None, &BTreeMap::new())
+}
+
+/// Names of the profiles a config file defines, including those of the files it includes
+pub fn load_profiles(path: &Path) -> Result<Vec<String>, ConfigError> {
+    let content = fs::read_to_string(path).map_err(|e| ConfigError::new(path, format!("Failed to read config: {}", e)))?;
+    compose::profile_names(parse_value(&content, path)?, path)
+}
+
+/// The config file of a project directory, in any of the supported formats
+pub fn find_config(project_dir: &Path) -> Option<PathBuf> {
+    CONFIG_FILES.iter().map(|name| project_dir.join(name)).find(|path| path.is_file())
+}
+
+/// The resolved config, with `profile` applied when one is selected
 #[tauri::command]
-pub fn read_config(path: String) -> Result<BuildStudioConfig, String> {
-    let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-    serde_yaml::from_str(&content).map_err(|e| e.to_string())
+pub fn read_config(path: String, profile: Option<String>) -> Result<BuildStudioConfig, String> {
+    load_config_with(Path::new(&path), profile.as_deref(), &BTreeMap::new()).map_err(|e| e.to_string())
+}
+
+/// Profiles the config defines, for the profile picker
+#[tauri::command]
+pub fn get_config_profiles(path: String) -> Result<Vec<String>, String> {
+    load_profiles(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+/// JSON Schema of `buildstudio.config.*`, for the config editor
+#[tauri::command]
+pub fn get_config_schema() -> serde_json::Value {
+    config_schema()
+}
+
+/// Problems in a config file, or in `content` when the editor has unsaved changes
+#[tauri::command]
+pub fn validate_config(path: String, content: Option<String>) -> Vec<ConfigError> {
+    let path = Path::new(&path);
+    match content.map(Ok).unwrap_or_else(|| fs::read_to_string(path)) {
+        Ok(content) => validate(&content, path),
+        Err(e) => vec![ConfigError::new(path, format!("Failed to read config: {}", e))],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(json: serde_json::Value) -> BuildConfig {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn cross_rewrites_the_default_cargo_command() {
+        let mut rust = build(serde_json::json!({
+            "name": "arm", "platform": "linux", "language": "rust",
+            "target": "aarch64-unknown-linux-gnu", "toolchain": "cross",
+        }));
+        rust.apply_language_defaults().unwrap();
+        assert_eq!(rust.command, "cross build --release --offline --target aarch64-unknown-linux-gnu");
+        assert_eq!(rust.fetch.as_deref(), Some("cargo fetch"));
+
+        let mut go = build(serde_json::json!({
+            "name": "arm", "platform": "linux", "language": "go", "toolchain": "cross",
+        }));
+        assert_eq!(
+            go.apply_language_defaults(),
+            Err("Build 'arm' uses the cross toolchain, which only runs cargo builds".to_string())
+        );
+
+        let mut written = build(serde_json::json!({
+            "name": "arm", "platform": "linux", "command": "make cross", "toolchain": "cross",
+        }));
+        written.apply_language_defaults().unwrap();
+        assert_eq!(written.command, "make cross");
+    }
+
+    fn positions(content: &str, file: &str) -> Vec<(Option<usize>, Option<usize>, String)> {
+        validate(content, Path::new(file)).into_iter().map(|e| (e.line, e.column, e.message)).collect()
+    }
+
+    #[test]
+    fn build_errors_point_at_the_build_name() {
+        // "web" also appears in the first build's command, before the second build's name
+        let yaml = "builds:\n  - name: app\n    platform: linux\n    command: make web\n  - name: web\n    platform: linux\n    toolchain: nope\n    command: make\n";
+        assert_eq!(
+            positions(yaml, "buildstudio.config.yaml"),
+            vec![(Some(5), Some(11), "Build 'web' has unknown toolchain: nope".to_string())]
+        );
+
+        let json = r#"{"builds": [
+  {"name": "app", "platform": "linux", "command": "web"},
+  {"name": "web", "platform": "linux", "command": "make", "toolchain": "nope"}
+]}"#;
+        assert_eq!(positions(json, "buildstudio.config.json")[0].0, Some(3));
+
+        let toml = "[[builds]]\nname = \"app\"\nplatform = \"linux\"\ncommand = \"web\"\n\n[[builds]]\nname = \"web\"\nplatform = \"linux\"\ncommand = \"make\"\ntoolchain = \"nope\"\n";
+        assert_eq!(positions(toml, "buildstudio.config.toml")[0].0, Some(7));
+    }
+
+    #[test]
+    fn dependency_errors_have_positions() {
+        let cycle = "builds:\n  - name: a\n    platform: linux\n    command: make\n    depends_on: [b]\n  - name: b\n    platform: linux\n    command: make\n    depends_on: [a]\n";
+        assert_eq!(
+            positions(cycle, "buildstudio.config.yaml"),
+            vec![(Some(2), Some(11), "Build dependencies form a cycle: a -> b -> a".to_string())]
+        );
+
+        let unknown = "builds:\n  - name: a\n    platform: linux\n    command: make\n  - name: b\n    platform: linux\n    command: make\n    depends_on: [c]\n";
+        assert_eq!(positions(unknown, "buildstudio.config.yaml")[0].0, Some(5));
+    }
+
+    #[test]
+    fn matrix_builds_point_at_the_declaring_build() {
+        let yaml = "builds:\n  - name: app\n    platform: linux\n    command: make\n  - name: cli\n    platform: linux\n    command: make\n    toolchain: nope\n    matrix:\n      arch: [x64, arm64]\n";
+        let errors = positions(yaml, "buildstudio.config.yaml");
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|(line, _, _)| *line == Some(5)));
+    }
 }
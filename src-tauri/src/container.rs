@@ -0,0 +1,243 @@
+//! Container builds for Build Studio
+//! Runs a build inside a Docker or Podman image, or on a Docker build node, with its build
+//! directory mounted at `/workspace`
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use sha2::{Digest, Sha256};
+use crate::fsutils::safe_file_name;
+use crate::local_nodes;
+use crate::security::SecurityConfig;
+
+/// Mount point of the build directory inside the container
+pub const WORKSPACE: &str = "/workspace";
+
+/// One build's container: the engine that runs it and the name it runs under
+pub struct ContainerRun {
+    engine: &'static str,
+    image: String,
+    name: String,
+    network: bool,
+}
+
+impl ContainerRun {
+    /// Pick the container engine for a build in `image`, failing if none is installed
+    pub fn prepare(image: &str, security_config: &SecurityConfig) -> Result<Self, String> {
+        // The image is passed as an argument to the engine; never let it read as an option
+        if image.is_empty() || image.starts_with('-') {
+            return Err(format!("Invalid container image: {}", image));
+        }
+        let engine = local_nodes::container_engine().ok_or_else(|| {
+            format!("Build requires container image {} but neither Docker nor Podman is available", image)
+        })?;
+        Ok(Self {
+            engine,
+            image: image.to_string(),
+            name: format!("build-studio-build-{}", uuid::Uuid::new_v4()),
+            network: !(security_config.enable_sandbox && security_config.network_isolation),
+        })
+    }
+
+    /// Arguments that start the container with `env` set, to be followed by the shell command
+    pub fn launcher(&self, work_dir: &Path, env: &[(String, String)]) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            self.engine.to_string(),
+            "run".to_string(),
+            "--rm".to_string(),
+            "--name".to_string(),
+            self.name.clone(),
+            "-v".to_string(),
+            format!("{}:{}", work_dir.display(), WORKSPACE),
+            "-w".to_string(),
+            WORKSPACE.to_string(),
+        ];
+        args.extend(self.user_mapping(work_dir));
+        if !self.network {
+            args.extend(["--network".to_string(), "none".to_string()]);
+        }
+        args.extend(env_args(env));
+        args.push(self.image.clone());
+        args
+    }
+
+    /// Run as the owner of the build directory so that outputs are not owned by root.
+    /// Rootless Podman maps the calling user itself; Docker is given the uid and gid.
+    #[cfg(unix)]
+    fn user_mapping(&self, work_dir: &Path) -> Vec<String> {
+        use std::os::unix::fs::MetadataExt;
+        if self.engine == "podman" {
+            return vec!["--userns=keep-id".to_string()];
+        }
+        match std::fs::metadata(work_dir) {
+            // An arbitrary uid has no home directory in most images; tools such as cargo need one
+            Ok(meta) => vec![
+                "--user".to_string(),
+                format!("{}:{}", meta.uid(), meta.gid()),
+                "-e".to_string(),
+                "HOME=/tmp".to_string(),
+            ],
+            Err(_) => vec![],
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn user_mapping(&self, _work_dir: &Path) -> Vec<String> {
+        vec![]
+    }
+
+    /// Stop and remove the container after its build was cancelled or timed out.
+    /// Killing the engine's client process does not stop the container itself.
+    pub fn remove(&self) {
+        let _ = Command::new(self.engine)
+            .args(["rm", "-f", &self.name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+/// `-e KEY=VALUE` arguments for `docker run` and `docker exec`
+fn env_args(env: &[(String, String)]) -> Vec<String> {
+    env.iter().flat_map(|(k, v)| ["-e".to_string(), format!("{}={}", k, v)]).collect()
+}
+
+/// Quote `value` for a POSIX shell
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Directory name of a project in a node's workspace: its directory name, followed by a hash
+/// of its full path so that projects with the same directory name get separate copies
+fn workspace_name(project: &Path) -> String {
+    let project = project.canonicalize().unwrap_or_else(|_| project.to_path_buf());
+    let name = project
+        .file_name()
+        .map(|n| safe_file_name(&n.to_string_lossy()))
+        .unwrap_or_else(|| "project".to_string());
+    let hash = format!("{:x}", Sha256::digest(project.to_string_lossy().as_bytes()));
+    format!("{}-{}", name, &hash[..12])
+}
+
+/// Owner of `path` as `uid:gid`, which builds on a node run as
+#[cfg(unix)]
+fn owner(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|meta| format!("{}:{}", meta.uid(), meta.gid()))
+}
+
+#[cfg(not(unix))]
+fn owner(_path: &Path) -> Option<String> {
+    None
+}
+
+/// A build on a long-lived Docker node created by `local_nodes`. The project is synced into
+/// the node's workspace on the host and the command runs there with `docker exec`.
+pub struct NodeRun {
+    container: String,
+    /// Project copy on the host, inside the directory mounted at `/workspace`
+    host_dir: PathBuf,
+    /// The same directory inside the container
+    dir: String,
+    /// File inside the container holding the build's process group id
+    pid_file: String,
+    user: Option<String>,
+}
+
+impl NodeRun {
+    /// Check that the node is running and prepare its workspace for the project in `project_dir`.
+    /// Builds share the node's network, so network isolation does not apply to them.
+    pub fn prepare(node_id: &str, project_dir: &str) -> Result<Self, String> {
+        if node_id.is_empty() || !node_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(format!("Invalid node id: {}", node_id));
+        }
+
+        let container = local_nodes::docker_node_container(node_id);
+        let inspect = Command::new("docker")
+            .args(["inspect", "-f", "{{.State.Running}}", &container])
+            .output()
+            .map_err(|e| format!("Failed to run docker: {}", e))?;
+        if String::from_utf8_lossy(&inspect.stdout).trim() != "true" {
+            return Err(format!("Node {} is not running", node_id));
+        }
+
+        let project = Path::new(project_dir);
+        let name = workspace_name(project);
+        let host_dir = local_nodes::docker_node_workspace(node_id).join(&name);
+        let dir = format!("{}/{}", WORKSPACE, name);
+        let user = owner(project);
+
+        // Workspaces of older nodes were created by the Docker daemon and belong to root;
+        // create the project directory from inside the node and hand it to the user
+        if !host_dir.exists() {
+            let mut mkdir = Command::new("docker");
+            mkdir.args(["exec", &container, "sh", "-c", "mkdir -p \"$0\" && chown \"$1\" \"$0\"", &dir]);
+            mkdir.arg(user.as_deref().unwrap_or("0:0"));
+            let status = mkdir.status().map_err(|e| format!("Failed to run docker: {}", e))?;
+            if !status.success() {
+                return Err(format!("Failed to create {} on node {}", dir, node_id));
+            }
+        }
+
+        Ok(Self {
+            container,
+            host_dir,
+            dir,
+            pid_file: format!("/tmp/build-studio-{}.pid", uuid::Uuid::new_v4()),
+            user,
+        })
+    }
+
+    /// Directory on the host the build runs in
+    pub fn host_dir(&self) -> &Path {
+        &self.host_dir
+    }
+
+    /// Arguments that enter the node with `env` set, to be followed by the shell command
+    pub fn launcher(&self, env: &[(String, String)]) -> Vec<String> {
+        let mut args = vec!["docker".to_string(), "exec".to_string(), "-w".to_string(), self.dir.clone()];
+        if let Some(user) = &self.user {
+            args.extend(["--user".to_string(), user.clone(), "-e".to_string(), "HOME=/tmp".to_string()]);
+        }
+        args.extend(env_args(env));
+        args.push(self.container.clone());
+        args
+    }
+
+    /// Run `command` in its own session where `setsid` is available, recording its process
+    /// group id so that the whole build can be killed from outside. The exit status of
+    /// `command` is passed through.
+    pub fn wrap(&self, command: &str) -> String {
+        let command = shell_quote(command);
+        let pid_file = shell_quote(&self.pid_file);
+        format!(
+            "if command -v setsid >/dev/null 2>&1; then setsid sh -c {command} & else sh -c {command} & fi; \
+             echo $! > {pid_file}; wait $!; status=$?; rm -f {pid_file}; exit $status"
+        )
+    }
+
+    /// Kill the build after it was cancelled or timed out.
+    /// Killing `docker exec` does not stop the processes it started in the node.
+    pub fn kill(&self) {
+        let _ = Command::new("docker")
+            .args(["exec", &self.container, "sh", "-c", "kill -KILL -$(cat \"$0\") 2>/dev/null || kill -KILL $(cat \"$0\"); rm -f \"$0\"", &self.pid_file])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workspace_name_tells_same_named_projects_apart() {
+        let first = workspace_name(Path::new("/home/a/projects/app"));
+        let second = workspace_name(Path::new("/home/b/projects/app"));
+        assert!(first.starts_with("app-") && second.starts_with("app-"));
+        assert_eq!(first.len(), "app-".len() + 12);
+        assert_ne!(first, second);
+        assert_eq!(first, workspace_name(Path::new("/home/a/projects/app")));
+        assert!(workspace_name(Path::new("/home/a/my app")).starts_with("my_app-"));
+    }
+}
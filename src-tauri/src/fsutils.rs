@@ -1,4 +1,77 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Bundle identifier from tauri.conf.json, which names the app config dir
+const APP_IDENTIFIER: &str = "com.build-studio.app";
+
+/// The directory Tauri resolves as `app_config_dir`, for callers without an app handle (the CLI)
+pub fn app_config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+/// Machine-wide config directory, writable only by administrators
+pub fn system_config_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        std::env::var_os("ProgramData").map(|dir| PathBuf::from(dir).join(APP_IDENTIFIER))
+    } else if cfg!(target_os = "macos") {
+        Some(Path::new("/Library/Application Support").join(APP_IDENTIFIER))
+    } else {
+        Some(Path::new("/etc").join(APP_IDENTIFIER))
+    }
+}
+
+/// Per-user cache directory for files Build Studio can recreate at any time
+pub fn app_cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+/// Keep a name such as a platform or build name usable as a single file or directory name
+pub fn safe_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect()
+}
+
+/// Full path of an executable found on the `PATH`
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let dirs: Vec<PathBuf> = std::env::split_paths(&std::env::var_os("PATH")?).collect();
+    find_executable_in(name, &dirs)
+}
+
+/// Full path of an executable found in one of `dirs`
+pub fn find_executable_in(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    let extensions: Vec<String> = if cfg!(target_os = "windows") {
+        std::env::var("PATHEXT")
+            .unwrap_or_else(|_| ".EXE;.CMD;.BAT".to_string())
+            .split(';')
+            .map(str::to_string)
+            .chain(std::iter::once(String::new()))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    dirs.iter().find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{}{}", name, ext)))
+            .find(|path| is_executable(path))
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
 
 #[tauri::command]
 pub async fn read_text_file(path: String) -> Result<String, String> {
@@ -9,3 +82,312 @@ pub async fn read_text_file(path: String) -> Result<String, String> {
 pub async fn write_text_file(path: String, content: String) -> Result<(), String> {
     fs::write(&path, content).map_err(|e| e.to_string())
 }
+
+/// How files are placed in a destination tree
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStrategy {
+    /// Reflink (copy-on-write clone) where the filesystem supports it. Otherwise read-only files
+    /// are hard linked, since a build cannot rewrite them in place without first changing their
+    /// permissions, and everything else is copied
+    #[default]
+    Auto,
+    /// Reflink only; fails on filesystems without copy-on-write clones
+    Reflink,
+    /// Hard link to the source file. Fast, but a build that rewrites a file
+    /// in place also changes the original
+    Hardlink,
+    /// Plain copy
+    Copy,
+}
+
+/// Clone `src` to `dest` sharing data blocks (btrfs, XFS, bcachefs, ...)
+#[cfg(target_os = "linux")]
+fn reflink_file(src: &Path, dest: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let source = fs::File::open(src)?;
+    let target = fs::File::create(dest)?;
+    // SAFETY: both descriptors are valid open files for the duration of the call
+    let result = unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) };
+    if result == 0 {
+        Ok(())
+    } else {
+        let error = io::Error::last_os_error();
+        drop(target);
+        let _ = fs::remove_file(dest);
+        Err(error)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink_file(_src: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "reflinks are not supported on this platform"))
+}
+
+/// Places files into a tree, remembering whether reflinks work so that a
+/// filesystem without them is only probed once
+pub struct TreeSync {
+    strategy: LinkStrategy,
+    reflink_supported: bool,
+    excluded: Vec<PathBuf>,
+    links: Option<LinkScope>,
+    /// Canonical source directories being synced, to stop at symlinks back into them
+    visiting: Vec<PathBuf>,
+}
+
+/// Where symlinks may point to be recreated in the destination tree
+struct LinkScope {
+    /// Canonical root of the source tree
+    source: PathBuf,
+    /// Where `source` is mirrored to
+    dest: PathBuf,
+    /// Canonical paths inside `source` that recreated links may point into
+    roots: Vec<PathBuf>,
+}
+
+impl LinkScope {
+    /// Target for a link at `link` in the destination that points to the canonical `target`,
+    /// relative so that it stays inside the destination
+    fn link_target(&self, target: &Path, link: &Path) -> Option<PathBuf> {
+        if !self.roots.iter().any(|root| target.starts_with(root)) {
+            return None;
+        }
+        let mirrored = self.dest.join(target.strip_prefix(&self.source).ok()?);
+        let from: Vec<_> = link.parent()?.components().collect();
+        let to: Vec<_> = mirrored.components().collect();
+        let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+        let mut relative: PathBuf = from[common..].iter().map(|_| "..").collect();
+        relative.extend(&to[common..]);
+        Some(relative)
+    }
+}
+
+impl TreeSync {
+    pub fn new(strategy: LinkStrategy) -> Self {
+        Self { strategy, reflink_supported: true, excluded: Vec::new(), links: None, visiting: Vec::new() }
+    }
+
+    /// Never descend into `path`, e.g. a sandbox that lives inside the tree being synced
+    pub fn excluding(mut self, path: PathBuf) -> Self {
+        self.excluded.push(path);
+        self
+    }
+
+    /// Recreate symlinks that point into one of `roots`, paths inside the tree `source` that
+    /// is mirrored to `dest`. Other symlinks, and all of them without this, are copied through
+    /// as the file or directory they point to.
+    pub fn linking_within(mut self, source: &Path, dest: &Path, roots: &[PathBuf]) -> Self {
+        self.links = fs::canonicalize(source).ok().map(|source| LinkScope {
+            source,
+            dest: dest.to_path_buf(),
+            roots: roots.iter().filter_map(|root| fs::canonicalize(root).ok()).collect(),
+        });
+        self
+    }
+
+    fn place_file(&mut self, src: &Path, dest: &Path) -> io::Result<()> {
+        match self.strategy {
+            LinkStrategy::Hardlink => return fs::hard_link(src, dest),
+            LinkStrategy::Reflink => reflink_file(src, dest)?,
+            LinkStrategy::Auto if self.reflink_supported && reflink_file(src, dest).is_ok() => {}
+            LinkStrategy::Auto => {
+                self.reflink_supported = false;
+                // Hard links fail across filesystems; fall back to a copy then
+                if !(is_read_only(src) && fs::hard_link(src, dest).is_ok()) {
+                    fs::copy(src, dest)?;
+                }
+            }
+            LinkStrategy::Copy => {
+                fs::copy(src, dest)?;
+            }
+        }
+        // Keep the source's mtime so unchanged files can be skipped next time.
+        // A read-only handle is enough on Unix; Windows needs write access.
+        let modified = fs::metadata(src)?.modified()?;
+        if fs::File::open(dest)?.set_modified(modified).is_err() {
+            fs::OpenOptions::new().write(true).open(dest)?.set_modified(modified)?;
+        }
+        Ok(())
+    }
+
+    /// Bring `dest` up to date with `src`, transferring only files whose size or
+    /// modification time differ. Returns the synced paths relative to `dest`.
+    pub fn sync(&mut self, src: &Path, dest: &Path) -> io::Result<BTreeSet<PathBuf>> {
+        let mut synced = BTreeSet::new();
+        if src.is_dir() {
+            self.sync_dir(src, dest, Path::new(""), &mut synced)?;
+        } else if src.is_file() {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            self.sync_file(src, dest)?;
+            synced.insert(PathBuf::new());
+        }
+        Ok(synced)
+    }
+
+    fn sync_dir(&mut self, src: &Path, dest: &Path, relative: &Path, synced: &mut BTreeSet<PathBuf>) -> io::Result<()> {
+        if dest.symlink_metadata().is_ok_and(|meta| !meta.is_dir()) {
+            fs::remove_file(dest)?;
+        }
+        fs::create_dir_all(dest)?;
+        self.visiting.push(fs::canonicalize(src)?);
+        let result = self.sync_entries(src, dest, relative, synced);
+        self.visiting.pop();
+        result
+    }
+
+    fn sync_entries(&mut self, src: &Path, dest: &Path, relative: &Path, synced: &mut BTreeSet<PathBuf>) -> io::Result<()> {
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let name = entry.file_name();
+            let relative = relative.join(&name);
+            if self.excluded.contains(&entry.path()) {
+                continue;
+            }
+            if file_type.is_dir() {
+                self.sync_dir(&entry.path(), &dest.join(&name), &relative, synced)?;
+            } else if file_type.is_file() {
+                self.sync_file(&entry.path(), &dest.join(&name))?;
+                synced.insert(relative);
+            } else if file_type.is_symlink() {
+                self.sync_link(&entry.path(), &dest.join(&name), &relative, synced)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn sync_link(&mut self, src: &Path, dest: &Path, relative: &Path, synced: &mut BTreeSet<PathBuf>) -> io::Result<()> {
+        // A dangling link has nothing to copy
+        let Ok(target) = fs::canonicalize(src) else {
+            return Ok(());
+        };
+        #[cfg(unix)]
+        if let Some(link) = self.links.as_ref().and_then(|scope| scope.link_target(&target, dest)) {
+            if fs::read_link(dest).ok().as_ref() != Some(&link) {
+                remove_entry(dest)?;
+                std::os::unix::fs::symlink(&link, dest)?;
+            }
+            synced.insert(relative.to_path_buf());
+            return Ok(());
+        }
+        if target.is_dir() {
+            // A link to a directory that is being synced would recurse forever
+            if self.visiting.iter().any(|dir| dir.starts_with(&target)) {
+                return Ok(());
+            }
+            self.sync_dir(&target, dest, relative, synced)
+        } else {
+            self.sync_file(&target, dest)?;
+            synced.insert(relative.to_path_buf());
+            Ok(())
+        }
+    }
+
+    fn sync_file(&mut self, src: &Path, dest: &Path) -> io::Result<()> {
+        if is_unchanged(src, dest) {
+            return Ok(());
+        }
+        remove_entry(dest)?;
+        self.place_file(src, dest)
+    }
+}
+
+/// Remove the file, link or directory at `path`, without following a link
+fn remove_entry(path: &Path) -> io::Result<()> {
+    match path.symlink_metadata() {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Whether nobody may write to `path`
+fn is_read_only(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).is_ok_and(|meta| meta.permissions().mode() & 0o222 == 0)
+    }
+    #[cfg(not(unix))]
+    {
+        fs::metadata(path).is_ok_and(|meta| meta.permissions().readonly())
+    }
+}
+
+/// Same size and modification time, and `dest` is a file rather than a link
+fn is_unchanged(src: &Path, dest: &Path) -> bool {
+    match (fs::metadata(src), fs::symlink_metadata(dest)) {
+        (Ok(s), Ok(d)) => {
+            d.is_file() && s.len() == d.len() && s.modified().ok().is_some() && s.modified().ok() == d.modified().ok()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn temp_tree(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("build-studio-{}-{}", name, uuid::Uuid::new_v4()));
+        let src = dir.join("src");
+        fs::create_dir_all(&src).unwrap();
+        (dir.clone(), src, dir.join("dest"))
+    }
+
+    fn set_modified(path: &Path, time: std::time::SystemTime) {
+        fs::OpenOptions::new().write(true).open(path).unwrap().set_modified(time).unwrap();
+    }
+
+    #[test]
+    fn only_files_with_a_new_size_or_mtime_are_transferred() {
+        let (dir, src, dest) = temp_tree("sync");
+        fs::write(src.join("main.c"), "int main;").unwrap();
+        let mut sync = TreeSync::new(LinkStrategy::Copy);
+        assert_eq!(sync.sync(&src, &dest).unwrap(), BTreeSet::from([PathBuf::from("main.c")]));
+        assert_eq!(fs::read_to_string(dest.join("main.c")).unwrap(), "int main;");
+
+        // Same size and mtime: the copy is not touched again
+        let modified = fs::metadata(src.join("main.c")).unwrap().modified().unwrap();
+        fs::write(dest.join("main.c"), "int copy;").unwrap();
+        set_modified(&dest.join("main.c"), modified);
+        sync.sync(&src, &dest).unwrap();
+        assert_eq!(fs::read_to_string(dest.join("main.c")).unwrap(), "int copy;");
+
+        let later = modified + Duration::from_secs(60);
+        set_modified(&src.join("main.c"), later);
+        sync.sync(&src, &dest).unwrap();
+        assert_eq!(fs::read_to_string(dest.join("main.c")).unwrap(), "int main;");
+
+        fs::write(src.join("main.c"), "int main();").unwrap();
+        set_modified(&src.join("main.c"), later);
+        sync.sync(&src, &dest).unwrap();
+        assert_eq!(fs::read_to_string(dest.join("main.c")).unwrap(), "int main();");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn auto_hard_links_read_only_files_and_copies_the_rest_without_reflinks() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        let (dir, src, dest) = temp_tree("auto");
+        fs::write(src.join("main.c"), "int main;").unwrap();
+        if reflink_file(&src.join("main.c"), &dir.join("probe")).is_ok() {
+            eprintln!("skipped: this filesystem supports reflinks");
+            fs::remove_dir_all(dir).unwrap();
+            return;
+        }
+        fs::write(src.join("version.h"), "#define VERSION 1").unwrap();
+        fs::set_permissions(src.join("version.h"), fs::Permissions::from_mode(0o444)).unwrap();
+
+        TreeSync::new(LinkStrategy::Auto).sync(&src, &dest).unwrap();
+        let inode = |path: PathBuf| fs::metadata(path).unwrap().ino();
+        assert_eq!(inode(src.join("version.h")), inode(dest.join("version.h")));
+        assert_ne!(inode(src.join("main.c")), inode(dest.join("main.c")));
+        assert_eq!(fs::read_to_string(dest.join("main.c")).unwrap(), "int main;");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,368 @@
+//! Build graph for Build Studio
+//! Orders a project's builds by `depends_on` and runs independent builds concurrently
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+use crate::config::BuildConfig;
+
+/// What to do with the rest of the graph once a build fails
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureMode {
+    /// Start no further builds; builds already running are allowed to finish
+    #[default]
+    FailFast,
+    /// Keep running every build that does not depend on the failed one
+    KeepGoing,
+}
+
+/// Result of one build in a graph run
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BuildSummary {
+    pub name: String,
+    /// "success", "failure" or "skipped"
+    pub status: String,
+    pub duration_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// A project's builds with their dependencies resolved to indices
+pub struct BuildGraph<'a> {
+    builds: &'a [BuildConfig],
+    deps: Vec<Vec<usize>>,
+    /// Builds generated from each matrix, by the name of the build declaring it
+    matrices: HashMap<&'a str, Vec<usize>>,
+}
+
+impl<'a> BuildGraph<'a> {
+    /// Resolve `depends_on` names, rejecting unknown names and cycles. Naming a matrix build
+    /// stands for every build generated from it.
+    pub fn new(builds: &'a [BuildConfig]) -> Result<Self, String> {
+        Self::resolve(builds).map_err(|(_, e)| e)
+    }
+
+    /// Like `new`, with the index of the build an error is about
+    pub fn resolve(builds: &'a [BuildConfig]) -> Result<Self, (usize, String)> {
+        let mut index = HashMap::new();
+        let mut matrices: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, build) in builds.iter().enumerate() {
+            if index.insert(build.name.as_str(), i).is_some() {
+                return Err((i, format!("Duplicate build name: {}", build.name)));
+            }
+            if let Some(cell) = &build.matrix_cell {
+                matrices.entry(cell.build.as_str()).or_default().push(i);
+            }
+        }
+
+        let mut deps = Vec::with_capacity(builds.len());
+        for (i, build) in builds.iter().enumerate() {
+            let mut resolved = Vec::new();
+            for dep in &build.depends_on {
+                match (index.get(dep.as_str()), matrices.get(dep.as_str())) {
+                    (Some(&i), _) => resolved.push(i),
+                    (None, Some(cells)) => resolved.extend(cells),
+                    (None, None) => return Err((i, format!("Build '{}' depends on unknown build '{}'", build.name, dep))),
+                }
+            }
+            deps.push(resolved);
+        }
+
+        let graph = Self { builds, deps, matrices };
+        if let Some(cycle) = graph.find_cycle() {
+            let names: Vec<&str> = cycle.iter().map(|&i| builds[i].name.as_str()).collect();
+            return Err((cycle[0], format!("Build dependencies form a cycle: {}", names.join(" -> "))));
+        }
+        Ok(graph)
+    }
+
+    /// A dependency cycle as a path that starts and ends with the same build
+    fn find_cycle(&self) -> Option<Vec<usize>> {
+        // 0 = unvisited, 1 = on the current path, 2 = done
+        let mut state = vec![0u8; self.builds.len()];
+        let mut path = Vec::new();
+        for start in 0..self.builds.len() {
+            if let Some(cycle) = self.visit(start, &mut state, &mut path) {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+
+    fn visit(&self, node: usize, state: &mut [u8], path: &mut Vec<usize>) -> Option<Vec<usize>> {
+        match state[node] {
+            2 => return None,
+            1 => {
+                let start = path.iter().position(|&n| n == node).unwrap_or_default();
+                let mut cycle = path[start..].to_vec();
+                cycle.push(node);
+                return Some(cycle);
+            }
+            _ => {}
+        }
+        state[node] = 1;
+        path.push(node);
+        for &dep in &self.deps[node] {
+            if let Some(cycle) = self.visit(dep, state, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        state[node] = 2;
+        None
+    }
+
+    /// Indices of the named builds and everything they depend on; all builds if `targets` is empty.
+    /// A matrix build's name selects every build generated from it.
+    pub fn select(&self, targets: &[String]) -> Result<HashSet<usize>, String> {
+        if targets.is_empty() {
+            return Ok((0..self.builds.len()).collect());
+        }
+        let mut selected = HashSet::new();
+        let mut stack = Vec::new();
+        for target in targets {
+            match self.builds.iter().position(|b| &b.name == target) {
+                Some(i) => stack.push(i),
+                None => stack.extend(self.matrices.get(target.as_str()).ok_or_else(|| format!("Unknown build: {}", target))?),
+            }
+        }
+        while let Some(i) = stack.pop() {
+            if selected.insert(i) {
+                stack.extend(&self.deps[i]);
+            }
+        }
+        Ok(selected)
+    }
+
+    /// Run the `selected` builds, at most `jobs` at a time, each once all of its dependencies
+    /// have succeeded. Summaries are returned in config order.
+    pub fn run<F>(&self, selected: &HashSet<usize>, jobs: usize, mode: FailureMode, run: F) -> Vec<BuildSummary>
+    where
+        F: Fn(&BuildConfig) -> Result<(), String> + Sync,
+    {
+        let jobs = jobs.max(1);
+        let mut results: HashMap<usize, BuildSummary> = HashMap::new();
+        let mut started = HashSet::new();
+        let mut failed = false;
+
+        thread::scope(|scope| {
+            let (done_tx, done_rx) = mpsc::channel();
+            let mut running = 0;
+            loop {
+                // Skip builds whose dependencies did not succeed, and then their dependents
+                let mut changed = true;
+                while changed {
+                    changed = false;
+                    for i in 0..self.builds.len() {
+                        if !selected.contains(&i) || started.contains(&i) {
+                            continue;
+                        }
+                        let blocked = self.deps[i]
+                            .iter()
+                            .find(|d| results.get(d).is_some_and(|r| r.status != "success"));
+                        if let Some(&dep) = blocked {
+                            let reason = format!("dependency '{}' did not succeed", self.builds[dep].name);
+                            started.insert(i);
+                            results.insert(i, skipped(&self.builds[i], reason));
+                            changed = true;
+                        }
+                    }
+                }
+
+                // Start every ready build while there are free job slots
+                let stop = failed && mode == FailureMode::FailFast;
+                for i in 0..self.builds.len() {
+                    if stop || running >= jobs {
+                        break;
+                    }
+                    let ready = selected.contains(&i)
+                        && !started.contains(&i)
+                        && self.deps[i].iter().all(|d| results.get(d).is_some_and(|r| r.status == "success"));
+                    if !ready {
+                        continue;
+                    }
+                    started.insert(i);
+                    running += 1;
+                    let done_tx = done_tx.clone();
+                    let build = &self.builds[i];
+                    let run = &run;
+                    scope.spawn(move || {
+                        let start = Instant::now();
+                        let result = run(build);
+                        let _ = done_tx.send((i, result, start.elapsed().as_millis() as u64));
+                    });
+                }
+
+                if running == 0 {
+                    break;
+                }
+                let Ok((i, result, duration_ms)) = done_rx.recv() else { break };
+                running -= 1;
+                failed |= result.is_err();
+                results.insert(i, BuildSummary {
+                    name: self.builds[i].name.clone(),
+                    status: if result.is_ok() { "success" } else { "failure" }.to_string(),
+                    duration_ms: Some(duration_ms),
+                    error: result.err(),
+                });
+            }
+        });
+
+        // Anything never started was held back by fail-fast
+        (0..self.builds.len())
+            .filter(|i| selected.contains(i))
+            .map(|i| {
+                results
+                    .remove(&i)
+                    .unwrap_or_else(|| skipped(&self.builds[i], "an earlier build failed".to_string()))
+            })
+            .collect()
+    }
+}
+
+fn skipped(build: &BuildConfig, reason: String) -> BuildSummary {
+    BuildSummary {
+        name: build.name.clone(),
+        status: "skipped".to_string(),
+        duration_ms: None,
+        error: Some(reason),
+    }
+}
+
+/// Plain-text table of graph results for the terminal
+pub fn summary_table(summaries: &[BuildSummary]) -> String {
+    let width = summaries.iter().map(|s| s.name.len()).max().unwrap_or(0).max("Build".len());
+    let mut table = format!("{:<width$}  {:<8}  {:>9}\n", "Build", "Status", "Duration", width = width);
+    for summary in summaries {
+        let duration = summary
+            .duration_ms
+            .map(|ms| format!("{:.1}s", ms as f64 / 1000.0))
+            .unwrap_or_else(|| "-".to_string());
+        table.push_str(&format!(
+            "{:<width$}  {:<8}  {:>9}",
+            summary.name,
+            summary.status,
+            duration,
+            width = width
+        ));
+        if let Some(error) = &summary.error {
+            table.push_str(&format!("  {}", error));
+        }
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Builds by name and the names they depend on
+    fn builds(specs: &[(&str, &[&str])]) -> Vec<BuildConfig> {
+        specs
+            .iter()
+            .map(|(name, deps)| {
+                serde_json::from_value(serde_json::json!({
+                    "name": name, "platform": "linux", "command": "make", "depends_on": deps,
+                }))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    /// Run every build of `graph`, failing the ones named in `failing`
+    fn run(graph: &BuildGraph, jobs: usize, mode: FailureMode, failing: &[&str]) -> Vec<(String, String)> {
+        let selected = graph.select(&[]).unwrap();
+        graph
+            .run(&selected, jobs, mode, |build| {
+                if failing.contains(&build.name.as_str()) {
+                    Err("exit code 1".to_string())
+                } else {
+                    Ok(())
+                }
+            })
+            .into_iter()
+            .map(|summary| (summary.name, summary.status))
+            .collect()
+    }
+
+    fn statuses(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected.iter().map(|(name, status)| (name.to_string(), status.to_string())).collect()
+    }
+
+    #[test]
+    fn fail_fast_starts_nothing_after_a_failure() {
+        let builds = builds(&[("a", &[]), ("b", &[]), ("c", &[])]);
+        let graph = BuildGraph::new(&builds).unwrap();
+        assert_eq!(
+            run(&graph, 1, FailureMode::FailFast, &["a"]),
+            statuses(&[("a", "failure"), ("b", "skipped"), ("c", "skipped")])
+        );
+        assert_eq!(
+            run(&graph, 1, FailureMode::KeepGoing, &["a"]),
+            statuses(&[("a", "failure"), ("b", "success"), ("c", "success")])
+        );
+    }
+
+    #[test]
+    fn dependents_of_a_failed_build_are_skipped() {
+        let builds = builds(&[("core", &[]), ("lib", &["core"]), ("app", &["lib"]), ("docs", &[])]);
+        let graph = BuildGraph::new(&builds).unwrap();
+        let selected = graph.select(&[]).unwrap();
+        let summaries = graph.run(&selected, 4, FailureMode::KeepGoing, |build| match build.name.as_str() {
+            "core" => Err("exit code 1".to_string()),
+            _ => Ok(()),
+        });
+        let result: Vec<_> = summaries.iter().map(|s| (s.name.as_str(), s.status.as_str(), s.error.as_deref())).collect();
+        assert_eq!(
+            result,
+            [
+                ("core", "failure", Some("exit code 1")),
+                ("lib", "skipped", Some("dependency 'core' did not succeed")),
+                ("app", "skipped", Some("dependency 'lib' did not succeed")),
+                ("docs", "success", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn jobs_limits_concurrent_builds() {
+        let builds = builds(&[("a", &[]), ("b", &[]), ("c", &[]), ("d", &[]), ("e", &[])]);
+        let graph = BuildGraph::new(&builds).unwrap();
+        let selected = graph.select(&[]).unwrap();
+        for jobs in [1, 2, 5] {
+            let (running, most) = (AtomicUsize::new(0), AtomicUsize::new(0));
+            graph.run(&selected, jobs, FailureMode::FailFast, |_| {
+                most.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(50));
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            });
+            assert_eq!(most.load(Ordering::SeqCst), jobs);
+        }
+    }
+
+    #[test]
+    fn select_pulls_in_dependencies() {
+        let builds = builds(&[("core", &[]), ("lib", &["core"]), ("app", &["lib"]), ("docs", &[])]);
+        let graph = BuildGraph::new(&builds).unwrap();
+        assert_eq!(graph.select(&["app".to_string()]).unwrap(), HashSet::from([0, 1, 2]));
+        assert_eq!(graph.select(&["docs".to_string()]).unwrap(), HashSet::from([3]));
+        assert_eq!(graph.select(&[]).unwrap().len(), 4);
+        assert_eq!(graph.select(&["nope".to_string()]), Err("Unknown build: nope".to_string()));
+
+        // Dependencies run first, and only the selected builds run
+        let order = Mutex::new(Vec::new());
+        let selected = graph.select(&["app".to_string()]).unwrap();
+        graph.run(&selected, 4, FailureMode::FailFast, |build| {
+            order.lock().unwrap().push(build.name.clone());
+            Ok(())
+        });
+        assert_eq!(order.into_inner().unwrap(), ["core", "lib", "app"]);
+    }
+}
@@ -0,0 +1,286 @@
+//! Build history for Build Studio
+//! Stores one record per build run, plus its captured output, in the app config dir
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use crate::artifacts::{self, ArtifactManifest};
+use crate::build::{OutputSink, OutputStream};
+use crate::lockfile::LockedBuild;
+
+/// Directory inside the app config dir holding the history
+pub const HISTORY_DIR: &str = "history";
+
+/// A single build run
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BuildRecord {
+    pub id: String,
+    /// Project name, or the directory name for projects not added to Build Studio
+    pub project: String,
+    pub project_dir: String,
+    pub build_name: Option<String>,
+    pub platform: Option<String>,
+    pub command: String,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub exit_code: Option<i32>,
+    /// "running", "success", "failure", "timeout" or "cancelled"
+    pub status: String,
+    /// Directory the build's artifacts were collected into
+    #[serde(default)]
+    pub artifacts_dir: Option<String>,
+    /// Toolchain the build ran with, as it would be written to `buildstudio.lock`
+    #[serde(default)]
+    pub lock: Option<LockedBuild>,
+    /// Axis values of a build generated from a matrix
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub matrix: BTreeMap<String, String>,
+}
+
+impl BuildRecord {
+    /// A new record for a build that is about to start
+    pub fn start(project: String, project_dir: String, build_name: Option<String>, platform: Option<String>, command: String) -> Self {
+        // Store the canonical directory so relative CLI paths and GUI paths match
+        let project_dir = fs::canonicalize(&project_dir)
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or(project_dir);
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            project,
+            project_dir,
+            build_name,
+            platform,
+            command,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            finished_at: None,
+            duration_ms: None,
+            exit_code: None,
+            status: "running".to_string(),
+            artifacts_dir: None,
+            lock: None,
+            matrix: BTreeMap::new(),
+        }
+    }
+}
+
+/// One captured line of build output
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LogLine {
+    /// "stdout" or "stderr"
+    pub stream: String,
+    pub line: String,
+}
+
+/// Build history store: `history/builds.json` holds the records and
+/// `history/logs/<id>.jsonl` the output of each run
+pub struct BuildHistory {
+    dir: PathBuf,
+}
+
+impl BuildHistory {
+    pub fn new(app_config_dir: &Path) -> Self {
+        Self {
+            dir: app_config_dir.join(HISTORY_DIR),
+        }
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.dir.join("builds.json")
+    }
+
+    fn log_path(&self, id: &str) -> Result<PathBuf, String> {
+        // Ids come from the frontend and CLI; never let them name another file
+        uuid::Uuid::parse_str(id).map_err(|_| format!("Invalid build id: {}", id))?;
+        Ok(self.dir.join("logs").join(format!("{}.jsonl", id)))
+    }
+
+    fn load(&self) -> Vec<BuildRecord> {
+        fs::read_to_string(self.index_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, records: &[BuildRecord]) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(records).map_err(|e| e.to_string())?;
+        // Replace the index in one step so readers never see a partly written file
+        let temp = self.dir.join("builds.json.tmp");
+        fs::write(&temp, content)
+            .and_then(|_| fs::rename(&temp, self.index_path()))
+            .map_err(|e| format!("Failed to save build history: {}", e))
+    }
+
+    /// Re-read the index, apply `update` and write it back. The CLI and the GUI may both be
+    /// recording builds, so the index is re-read every time under a lock on `builds.lock`
+    /// that holds across processes.
+    fn update<T>(&self, update: impl FnOnce(&mut Vec<BuildRecord>) -> T) -> Result<T, String> {
+        fs::create_dir_all(&self.dir).map_err(|e| format!("Failed to create history directory: {}", e))?;
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.dir.join("builds.lock"))
+            .and_then(|file| file.lock().map(|_| file))
+            .map_err(|e| format!("Failed to lock build history: {}", e))?;
+        let mut records = self.load();
+        let result = update(&mut records);
+        self.save(&records)?;
+        drop(lock);
+        Ok(result)
+    }
+
+    /// Store `record` as running and return a sink that captures its output before passing it on
+    pub fn start(&self, record: &BuildRecord, sink: OutputSink) -> Result<OutputSink, String> {
+        self.update(|records| records.push(record.clone()))?;
+
+        let log_path = self.log_path(&record.id)?;
+        if let Some(parent) = log_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create log directory: {}", e))?;
+        }
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .map_err(|e| format!("Failed to open build log: {}", e))?;
+        let log = Arc::new(Mutex::new(log));
+
+        Ok(Arc::new(move |stream, line| {
+            let entry = LogLine {
+                stream: match stream {
+                    OutputStream::Stdout => "stdout",
+                    OutputStream::Stderr => "stderr",
+                }
+                .to_string(),
+                line,
+            };
+            if let Ok(json) = serde_json::to_string(&entry) {
+                let _ = writeln!(log.lock().unwrap(), "{}", json);
+            }
+            sink(stream, entry.line);
+        }))
+    }
+
+    /// Mark a running build as finished
+    pub fn finish(
+        &self,
+        id: &str,
+        status: &str,
+        exit_code: Option<i32>,
+        artifacts_dir: Option<&Path>,
+    ) -> Result<Option<BuildRecord>, String> {
+        self.update(|records| {
+            let record = records.iter_mut().find(|r| r.id == id)?;
+            let finished = chrono::Utc::now();
+            record.duration_ms = chrono::DateTime::parse_from_rfc3339(&record.started_at)
+                .ok()
+                .and_then(|started| (finished - started.with_timezone(&chrono::Utc)).to_std().ok())
+                .map(|d| d.as_millis() as u64);
+            record.finished_at = Some(finished.to_rfc3339());
+            record.status = status.to_string();
+            record.exit_code = exit_code;
+            record.artifacts_dir = artifacts_dir.map(|dir| dir.to_string_lossy().to_string());
+            Some(record.clone())
+        })
+    }
+
+    /// Records, newest first, optionally only those of one project (by name or directory)
+    pub fn list(&self, project: Option<&str>) -> Vec<BuildRecord> {
+        let project_dir = project.and_then(|p| fs::canonicalize(p).ok()).map(|dir| dir.to_string_lossy().to_string());
+        let mut records: Vec<BuildRecord> = self
+            .load()
+            .into_iter()
+            .filter(|r| {
+                project.is_none_or(|p| r.project == p || r.project_dir == p)
+                    || project_dir.as_ref().is_some_and(|dir| &r.project_dir == dir)
+            })
+            .collect();
+        records.reverse();
+        records
+    }
+
+    /// Captured output of a build
+    pub fn log(&self, id: &str) -> Result<Vec<LogLine>, String> {
+        let file = File::open(self.log_path(id)?).map_err(|_| format!("No log for build {}", id))?;
+        Ok(BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect())
+    }
+
+    /// Manifest of the artifacts a build collected
+    pub fn artifacts(&self, id: &str) -> Result<ArtifactManifest, String> {
+        let record = self
+            .load()
+            .into_iter()
+            .find(|r| r.id == id)
+            .ok_or_else(|| format!("Build {} not found", id))?;
+        let dir = record.artifacts_dir.ok_or_else(|| format!("Build {} collected no artifacts", id))?;
+        artifacts::read_manifest(Path::new(&dir))
+    }
+
+    /// Remove a build's record and log
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        let log_path = self.log_path(id)?;
+        let removed = self.update(|records| {
+            let len = records.len();
+            records.retain(|r| r.id != id);
+            len != records.len()
+        })?;
+        let _ = fs::remove_file(log_path);
+        if removed {
+            Ok(())
+        } else {
+            Err(format!("Build {} not found", id))
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_builds(state: State<'_, BuildHistory>, project: Option<String>) -> Vec<BuildRecord> {
+    state.list(project.as_deref())
+}
+
+#[tauri::command]
+pub fn get_build_log(state: State<'_, BuildHistory>, id: String) -> Result<Vec<LogLine>, String> {
+    state.log(&id)
+}
+
+#[tauri::command]
+pub fn get_build_artifacts(state: State<'_, BuildHistory>, id: String) -> Result<ArtifactManifest, String> {
+    state.artifacts(&id)
+}
+
+#[tauri::command]
+pub fn delete_build(state: State<'_, BuildHistory>, id: String) -> Result<(), String> {
+    state.delete(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each thread opens the history on its own, like the CLI and the GUI do
+    #[test]
+    fn concurrent_writers_keep_every_record() {
+        let dir = std::env::temp_dir().join(format!("build-studio-history-{}", uuid::Uuid::new_v4()));
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let history = BuildHistory::new(&dir);
+                    for _ in 0..25 {
+                        let record = BuildRecord::start("p".into(), ".".into(), None, None, "make".into());
+                        history.update(|records| records.push(record)).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(BuildHistory::new(&dir).list(None).len(), 100);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,605 @@
+//! Plugin installation for Build Studio
+//! Plugins install from a local `.zip`, `.tar.gz` or git checkout into
+//! `<app config>/plugins/<id>/<version>/`. `plugins.json` records each plugin's active version,
+//! whether it is enabled and the version its last upgrade replaced, which is kept for rollback.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use crate::fsutils;
+use crate::plugin::{self, Plugin, PluginKind, PluginProtocol, MANIFEST_FILES, PLUGINS_DIR};
+use crate::requirement;
+
+/// Installed plugins, in the app config dir
+pub const REGISTRY_FILE: &str = "plugins.json";
+
+/// A plugin installed into the app config dir
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InstalledPlugin {
+    /// Directory under `plugins/`, derived from the plugin name
+    pub id: String,
+    pub name: String,
+    /// Active version
+    pub version: String,
+    /// Version the last upgrade replaced, kept for `rollback_plugin`
+    #[serde(default)]
+    pub previous: Option<String>,
+    pub enabled: bool,
+    /// Archive or checkout the active version was installed from
+    pub source: String,
+    pub installed_at: String,
+}
+
+impl InstalledPlugin {
+    /// Whether `name` is this plugin's name or id
+    pub fn matches(&self, name: &str) -> bool {
+        self.id == name || self.name == name
+    }
+}
+
+/// Directory name for a plugin: its name in lower case with anything but letters and digits as `-`
+pub fn plugin_id(name: &str) -> String {
+    let id: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    id.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-")
+}
+
+/// A relative path inside an archive; entries that would land outside the install directory
+/// are refused
+fn entry_path(name: &Path) -> Result<PathBuf, String> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return Err(format!("Archive entry {} points outside the plugin directory", name.display())),
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: Option<u32>) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    match mode {
+        Some(mode) => fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: Option<u32>) -> io::Result<()> {
+    Ok(())
+}
+
+fn extract_zip(archive: &Path, dest: &Path) -> Result<(), String> {
+    let file = File::open(archive).map_err(|e| format!("Failed to open {}: {}", archive.display(), e))?;
+    let mut zip = zip::ZipArchive::new(file).map_err(|e| format!("Failed to read {}: {}", archive.display(), e))?;
+    for index in 0..zip.len() {
+        let mut entry = zip
+            .by_index(index)
+            .map_err(|e| format!("Failed to read {}: {}", archive.display(), e))?;
+        let target = dest.join(entry_path(Path::new(entry.name()))?);
+        if entry.is_symlink() {
+            return Err(format!("Archive entry {} is a link; plugins cannot contain links", entry.name()));
+        }
+        let written = if entry.is_dir() {
+            fs::create_dir_all(&target)
+        } else {
+            target
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| File::create(&target))
+                .and_then(|mut out| io::copy(&mut entry, &mut out))
+                .and_then(|_| set_mode(&target, entry.unix_mode()))
+        };
+        written.map_err(|e| format!("Failed to extract {}: {}", entry.name(), e))?;
+    }
+    Ok(())
+}
+
+fn extract_tar_gz(archive: &Path, dest: &Path) -> Result<(), String> {
+    let file = File::open(archive).map_err(|e| format!("Failed to open {}: {}", archive.display(), e))?;
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    let entries = tar.entries().map_err(|e| format!("Failed to read {}: {}", archive.display(), e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Failed to read {}: {}", archive.display(), e))?;
+        let name = entry
+            .path()
+            .map_err(|e| format!("Failed to read {}: {}", archive.display(), e))?
+            .to_path_buf();
+        let target = dest.join(entry_path(&name)?);
+        let kind = entry.header().entry_type();
+        let written = if kind.is_dir() {
+            fs::create_dir_all(&target)
+        } else if kind.is_file() {
+            target
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| entry.unpack(&target).map(|_| ()))
+        } else if kind.is_pax_global_extensions() {
+            // `git archive` records the commit here
+            Ok(())
+        } else {
+            return Err(format!(
+                "Archive entry {} is a link or special file; plugins cannot contain those",
+                name.display()
+            ));
+        };
+        written.map_err(|e| format!("Failed to extract {}: {}", name.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Refuse symbolic links anywhere under `dir`, which could point outside the plugin
+fn reject_links(dir: &Path) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let kind = entry.file_type().map_err(|e| format!("Failed to read {}: {}", entry.path().display(), e))?;
+        if kind.is_symlink() {
+            return Err(format!("{} is a link; plugins cannot contain links", entry.path().display()));
+        }
+        if kind.is_dir() {
+            reject_links(&entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Clone the committed files of a git checkout, without its history
+fn clone_checkout(checkout: &Path, dest: &Path) -> Result<(), String> {
+    let output = Command::new("git")
+        .arg("clone")
+        .arg("--quiet")
+        .arg(checkout)
+        .arg(dest)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to clone {}: {}",
+            checkout.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    fs::remove_dir_all(dest.join(".git")).map_err(|e| format!("Failed to remove the clone's .git: {}", e))?;
+    reject_links(dest)
+}
+
+/// Unpack `source` into `dest`, by its kind
+fn unpack(source: &Path, dest: &Path) -> Result<(), String> {
+    let name = source.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if source.is_dir() && source.join(".git").exists() {
+        clone_checkout(source, &dest.join("checkout"))
+    } else if source.is_file() && name.ends_with(".zip") {
+        extract_zip(source, dest)
+    } else if source.is_file() && (name.ends_with(".tar.gz") || name.ends_with(".tgz")) {
+        extract_tar_gz(source, dest)
+    } else {
+        Err(format!("{} is not a .zip, .tar.gz or git checkout", source.display()))
+    }
+}
+
+/// The directory of an unpacked plugin: the top level, or the single directory an archive wraps it in
+fn plugin_root(unpacked: &Path) -> Result<PathBuf, String> {
+    let has_manifest = |dir: &Path| MANIFEST_FILES.iter().any(|name| dir.join(name).is_file());
+    if has_manifest(unpacked) {
+        return Ok(unpacked.to_path_buf());
+    }
+    let children: Vec<PathBuf> = fs::read_dir(unpacked)
+        .map_err(|e| format!("Failed to read {}: {}", unpacked.display(), e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    match children.as_slice() {
+        [only] if only.is_dir() && has_manifest(only) => Ok(only.clone()),
+        _ => Err("No plugin.json or plugin.yaml at the top of the plugin".to_string()),
+    }
+}
+
+/// Check what installing relies on: a name, a version usable as a directory name, commands whose
+/// scripts are in the plugin and dependencies that parse
+fn check_manifest(plugin: &Plugin, root: &Path) -> Result<(), String> {
+    if plugin_id(&plugin.name).is_empty() {
+        return Err(format!("Plugin name '{}' needs at least one letter or digit", plugin.name));
+    }
+    let version = entry_path(Path::new(&plugin.version)).ok();
+    if version.is_none_or(|v| v.components().count() != 1) {
+        return Err(format!("Plugin version '{}' cannot be used as a directory name", plugin.version));
+    }
+    // A JSON-RPC plugin runs only its server; other plugins run a script per command
+    let scripts: Vec<(&str, String)> = match plugin.protocol {
+        PluginProtocol::Jsonrpc if plugin.kind == PluginKind::Wasm => {
+            return Err("A WebAssembly plugin cannot use protocol jsonrpc".to_string());
+        }
+        PluginProtocol::Jsonrpc => {
+            let server = plugin.server.as_deref().ok_or("A plugin using protocol jsonrpc needs a server script")?;
+            vec![(server, "the server".to_string())]
+        }
+        PluginProtocol::Args => plugin
+            .commands
+            .iter()
+            .map(|command| (command.script.as_str(), format!("command '{}'", command.name)))
+            .collect(),
+    };
+    for (script, owner) in scripts {
+        let path = entry_path(Path::new(script)).map_err(|_| format!("Script {} of {} is outside the plugin", script, owner))?;
+        if !root.join(&path).is_file() {
+            return Err(format!("Script {} of {} is missing", script, owner));
+        }
+        if plugin.kind == PluginKind::Wasm && path.extension().is_none_or(|ext| ext != "wasm") {
+            return Err(format!("Script {} of {} must be a .wasm module in a WebAssembly plugin", script, owner));
+        }
+    }
+    for dependency in &plugin.dependencies {
+        requirement::parse(dependency)?;
+    }
+    Ok(())
+}
+
+/// Plugins installed into one app config dir
+pub struct PluginRegistry {
+    app_config_dir: PathBuf,
+}
+
+impl PluginRegistry {
+    pub fn new(app_config_dir: &Path) -> Self {
+        Self {
+            app_config_dir: app_config_dir.to_path_buf(),
+        }
+    }
+
+    /// The registry of the app config dir, for callers without an app handle
+    pub fn open() -> Result<Self, String> {
+        fsutils::app_config_dir()
+            .map(|dir| Self::new(&dir))
+            .ok_or_else(|| "Could not determine the app config directory".to_string())
+    }
+
+    fn plugins_dir(&self) -> PathBuf {
+        self.app_config_dir.join(PLUGINS_DIR)
+    }
+
+    fn registry_path(&self) -> PathBuf {
+        self.app_config_dir.join(REGISTRY_FILE)
+    }
+
+    /// Directory of one installed version of a plugin
+    pub fn version_dir(&self, id: &str, version: &str) -> PathBuf {
+        self.plugins_dir().join(id).join(version)
+    }
+
+    /// Every installed plugin; an unreadable registry is an error rather than an empty one, so it
+    /// is never overwritten
+    pub fn list(&self) -> Result<Vec<InstalledPlugin>, String> {
+        let path = self.registry_path();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+
+    fn save(&self, plugins: &[InstalledPlugin]) -> Result<(), String> {
+        fs::create_dir_all(&self.app_config_dir).map_err(|e| format!("Failed to create app config dir: {}", e))?;
+        let content = serde_json::to_string_pretty(plugins).map_err(|e| e.to_string())?;
+        fs::write(self.registry_path(), content).map_err(|e| format!("Failed to save installed plugins: {}", e))
+    }
+
+    /// Apply `update` to the installed plugin named `name` and save the result
+    fn update(&self, name: &str, update: impl FnOnce(&mut InstalledPlugin) -> Result<(), String>) -> Result<InstalledPlugin, String> {
+        let mut plugins = self.list()?;
+        let plugin = plugins
+            .iter_mut()
+            .find(|p| p.matches(name))
+            .ok_or_else(|| format!("Plugin '{}' is not installed", name))?;
+        update(plugin)?;
+        let updated = plugin.clone();
+        self.save(&plugins)?;
+        Ok(updated)
+    }
+
+    /// The installed plugin named `name`
+    pub fn find(&self, name: &str) -> Option<InstalledPlugin> {
+        self.list().ok()?.into_iter().find(|p| p.matches(name))
+    }
+
+    /// Directories of the active versions of the enabled plugins
+    pub fn enabled_dirs(&self) -> Vec<PathBuf> {
+        self.list()
+            .unwrap_or_default()
+            .iter()
+            .filter(|p| p.enabled)
+            .map(|p| self.version_dir(&p.id, &p.version))
+            .collect()
+    }
+
+    /// Plugin directories copied straight into the app's `plugins/` rather than installed
+    pub fn unregistered_dirs(&self) -> Vec<PathBuf> {
+        let ids: Vec<String> = self.list().unwrap_or_default().into_iter().map(|p| p.id).collect();
+        plugin::plugins_in(&self.plugins_dir())
+            .into_iter()
+            .filter(|dir| !ids.iter().any(|id| dir.ends_with(id)))
+            .collect()
+    }
+
+    /// Install the plugin in `source`. Installing another version of an installed plugin makes
+    /// it the active one and keeps the version it replaces for rollback.
+    pub fn install(&self, source: &Path) -> Result<InstalledPlugin, String> {
+        let plugins_dir = self.plugins_dir();
+        fs::create_dir_all(&plugins_dir).map_err(|e| format!("Failed to create plugins directory: {}", e))?;
+        // Unpack next to the final location so that moving it there is a rename
+        let staging = plugins_dir.join(format!(".install-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&staging).map_err(|e| format!("Failed to create staging directory: {}", e))?;
+        let installed = self.install_from(source, &staging);
+        let _ = fs::remove_dir_all(&staging);
+        installed
+    }
+
+    fn install_from(&self, source: &Path, staging: &Path) -> Result<InstalledPlugin, String> {
+        unpack(source, staging)?;
+        let root = plugin_root(staging)?;
+        let plugin = plugin::load_plugin(&root)?;
+        check_manifest(&plugin, &root)?;
+
+        let mut plugins = self.list()?;
+        let id = plugin_id(&plugin.name);
+        let existing = plugins.iter().position(|p| p.id == id);
+        if existing.is_some_and(|i| plugins[i].version == plugin.version) {
+            return Err(format!("Plugin '{}' {} is already installed", plugin.name, plugin.version));
+        }
+        let target = self.version_dir(&id, &plugin.version);
+        if target.exists() {
+            // Left over from an earlier install of the same version
+            fs::remove_dir_all(&target).map_err(|e| format!("Failed to replace {}: {}", target.display(), e))?;
+        }
+        fs::create_dir_all(target.parent().unwrap_or(&target)).map_err(|e| format!("Failed to create plugin directory: {}", e))?;
+        fs::rename(&root, &target).map_err(|e| format!("Failed to install plugin into {}: {}", target.display(), e))?;
+
+        let installed_at = chrono::Utc::now().to_rfc3339();
+        let source = fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf()).display().to_string();
+        let installed = match existing {
+            Some(index) => {
+                let entry = &mut plugins[index];
+                // Only the version being replaced is kept
+                if let Some(dropped) = entry.previous.take().filter(|v| *v != plugin.version) {
+                    let _ = fs::remove_dir_all(self.version_dir(&id, &dropped));
+                }
+                entry.previous = Some(std::mem::replace(&mut entry.version, plugin.version.clone()));
+                entry.name = plugin.name.clone();
+                entry.source = source;
+                entry.installed_at = installed_at;
+                entry.clone()
+            }
+            None => {
+                let entry = InstalledPlugin {
+                    id,
+                    name: plugin.name.clone(),
+                    version: plugin.version.clone(),
+                    previous: None,
+                    enabled: true,
+                    source,
+                    installed_at,
+                };
+                plugins.push(entry.clone());
+                entry
+            }
+        };
+        self.save(&plugins)?;
+        Ok(installed)
+    }
+
+    /// Remove every installed version of a plugin
+    pub fn uninstall(&self, name: &str) -> Result<InstalledPlugin, String> {
+        let mut plugins = self.list()?;
+        let index = plugins
+            .iter()
+            .position(|p| p.matches(name))
+            .ok_or_else(|| format!("Plugin '{}' is not installed", name))?;
+        let removed = plugins.remove(index);
+        let dir = self.plugins_dir().join(&removed.id);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).map_err(|e| format!("Failed to remove {}: {}", dir.display(), e))?;
+        }
+        self.save(&plugins)?;
+        Ok(removed)
+    }
+
+    /// Enable or disable a plugin; disabled plugins stay installed but cannot be run
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> Result<InstalledPlugin, String> {
+        self.update(name, |plugin| {
+            plugin.enabled = enabled;
+            Ok(())
+        })
+    }
+
+    /// Make the version the last upgrade replaced active again. The version rolled back from is
+    /// kept, so rolling back twice returns to it.
+    pub fn rollback(&self, name: &str) -> Result<InstalledPlugin, String> {
+        self.update(name, |plugin| {
+            let previous = plugin
+                .previous
+                .take()
+                .ok_or_else(|| format!("Plugin '{}' has no earlier version to roll back to", plugin.name))?;
+            if !self.version_dir(&plugin.id, &previous).is_dir() {
+                return Err(format!("Version {} of plugin '{}' is no longer installed", previous, plugin.name));
+            }
+            plugin.previous = Some(std::mem::replace(&mut plugin.version, previous));
+            Ok(())
+        })
+    }
+}
+
+/// Install a plugin from a local `.zip`, `.tar.gz` or git checkout
+#[tauri::command]
+pub fn install_plugin(path: String) -> Result<InstalledPlugin, String> {
+    PluginRegistry::open()?.install(Path::new(&path))
+}
+
+#[tauri::command]
+pub fn uninstall_plugin(name: String) -> Result<InstalledPlugin, String> {
+    PluginRegistry::open()?.uninstall(&name)
+}
+
+#[tauri::command]
+pub fn enable_plugin(name: String) -> Result<InstalledPlugin, String> {
+    PluginRegistry::open()?.set_enabled(&name, true)
+}
+
+#[tauri::command]
+pub fn disable_plugin(name: String) -> Result<InstalledPlugin, String> {
+    PluginRegistry::open()?.set_enabled(&name, false)
+}
+
+#[tauri::command]
+pub fn rollback_plugin(name: String) -> Result<InstalledPlugin, String> {
+    PluginRegistry::open()?.rollback(&name)
+}
+
+#[tauri::command]
+pub fn list_installed_plugins() -> Result<Vec<InstalledPlugin>, String> {
+    PluginRegistry::open()?.list()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn manifest(name: &str, version: &str) -> String {
+        serde_json::json!({
+            "name": name, "version": version,
+            "commands": [{"name": "build", "script": "build.sh"}],
+        })
+        .to_string()
+    }
+
+    /// A registry in a new app config dir, and a directory for archives next to it
+    fn registry() -> (PathBuf, PluginRegistry) {
+        let root = std::env::temp_dir().join(format!("build-studio-installer-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(root.join("archives")).unwrap();
+        let registry = PluginRegistry::new(&root.join("config"));
+        (root, registry)
+    }
+
+    fn write_zip(path: &Path, files: &[(&str, &str)], links: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, content) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        for (name, target) in links {
+            zip.add_symlink(*name, *target, options).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /// A plugin archive whose manifest lists `build.sh`, with the script unless `complete` is false
+    fn plugin_zip(dir: &Path, name: &str, version: &str, complete: bool) -> PathBuf {
+        let path = dir.join(format!("{}-{}.zip", plugin_id(name), version));
+        let manifest = manifest(name, version);
+        let mut files = vec![("plugin.json", manifest.as_str())];
+        if complete {
+            files.push(("build.sh", "#!/bin/sh\n"));
+        }
+        write_zip(&path, &files, &[]);
+        path
+    }
+
+    fn write_tar_gz(path: &Path, link: tar::EntryType) {
+        let gz = flate2::write::GzEncoder::new(File::create(path).unwrap(), flate2::Compression::default());
+        let mut tar = tar::Builder::new(gz);
+        let manifest = manifest("Linked", "1.0.0");
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, "plugin.json", manifest.as_bytes()).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(link);
+        header.set_size(0);
+        header.set_mode(0o755);
+        tar.append_link(&mut header, "build.sh", "/bin/sh").unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn entry_paths_stay_inside_the_plugin() {
+        assert_eq!(entry_path(Path::new("bin/run.sh")), Ok(PathBuf::from("bin/run.sh")));
+        assert_eq!(entry_path(Path::new("./plugin.json")), Ok(PathBuf::from("plugin.json")));
+        for outside in ["../evil.sh", "bin/../../evil.sh", "/etc/passwd"] {
+            assert!(entry_path(Path::new(outside)).is_err(), "{}", outside);
+        }
+
+        let (root, registry) = registry();
+        let archive = root.join("archives/evil.zip");
+        write_zip(&archive, &[("plugin.json", &manifest("Evil", "1.0.0")), ("../evil.sh", "#!/bin/sh\n")], &[]);
+        let error = registry.install(&archive).unwrap_err();
+        assert!(error.contains("points outside the plugin directory"), "{}", error);
+        assert!(!root.join("config/evil.sh").exists());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn links_in_archives_are_refused() {
+        let (root, registry) = registry();
+        let archive = root.join("archives/linked.zip");
+        write_zip(&archive, &[("plugin.json", &manifest("Linked", "1.0.0"))], &[("build.sh", "/bin/sh")]);
+        let error = registry.install(&archive).unwrap_err();
+        assert!(error.contains("is a link"), "{}", error);
+
+        for (file, kind) in [("symlink.tar.gz", tar::EntryType::Symlink), ("hardlink.tar.gz", tar::EntryType::Link)] {
+            let archive = root.join("archives").join(file);
+            write_tar_gz(&archive, kind);
+            let error = registry.install(&archive).unwrap_err();
+            assert!(error.contains("is a link or special file"), "{}: {}", file, error);
+        }
+        assert!(registry.list().unwrap().is_empty());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn a_failed_upgrade_keeps_the_previous_version() {
+        let (root, registry) = registry();
+        let archives = root.join("archives");
+        registry.install(&plugin_zip(&archives, "Demo", "1.0.0", true)).unwrap();
+
+        let error = registry.install(&plugin_zip(&archives, "Demo", "2.0.0", false)).unwrap_err();
+        assert_eq!(error, "Script build.sh of command 'build' is missing");
+        let demo = registry.find("demo").unwrap();
+        assert_eq!((demo.version.as_str(), demo.previous), ("1.0.0", None));
+        assert!(registry.version_dir("demo", "1.0.0").join("build.sh").is_file());
+        assert!(!registry.version_dir("demo", "2.0.0").exists());
+        // The staging directory is cleaned up
+        assert_eq!(fs::read_dir(root.join("config").join(PLUGINS_DIR)).unwrap().count(), 1);
+
+        let upgraded = registry.install(&plugin_zip(&archives, "Demo", "2.0.0", true)).unwrap();
+        assert_eq!((upgraded.version.as_str(), upgraded.previous.as_deref()), ("2.0.0", Some("1.0.0")));
+        let rolled_back = registry.rollback("Demo").unwrap();
+        assert_eq!((rolled_back.version.as_str(), rolled_back.previous.as_deref()), ("1.0.0", Some("2.0.0")));
+        assert_eq!(registry.enabled_dirs(), [registry.version_dir("demo", "1.0.0")]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn uninstall_removes_only_that_plugin() {
+        let (root, registry) = registry();
+        let archives = root.join("archives");
+        registry.install(&plugin_zip(&archives, "Demo", "1.0.0", true)).unwrap();
+        registry.install(&plugin_zip(&archives, "Other Tool", "1.0.0", true)).unwrap();
+
+        assert_eq!(registry.uninstall("demo").unwrap().name, "Demo");
+        assert!(!root.join("config").join(PLUGINS_DIR).join("demo").exists());
+        assert!(registry.version_dir("other-tool", "1.0.0").join("plugin.json").is_file());
+        let names: Vec<String> = registry.list().unwrap().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, ["Other Tool"]);
+        assert_eq!(registry.uninstall("demo").unwrap_err(), "Plugin 'demo' is not installed");
+        fs::remove_dir_all(root).unwrap();
+    }
+}
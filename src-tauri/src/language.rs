@@ -0,0 +1,202 @@
+//! Language adapters for Build Studio
+//! Defaults for builds that only declare a `language:`: the commands that fetch dependencies
+//! and run the build, the artifacts to keep, the directories the build reuses between runs and
+//! the tools it needs
+
+use serde::Serialize;
+use crate::fsutils;
+
+/// Build defaults for one language or build system
+#[derive(Serialize, Clone, Debug)]
+pub struct LanguageAdapter {
+    pub name: &'static str,
+    /// Other names accepted in `language:`
+    pub aliases: &'static [&'static str],
+    /// Command used when a build has none. It runs without network access under the default
+    /// security settings, so it must not download anything
+    pub command: &'static str,
+    /// Command that downloads the dependencies before `command`, with network access
+    pub fetch_command: Option<&'static str>,
+    /// Artifact globs used when a build declares none
+    pub artifacts: &'static [&'static str],
+    /// Command for a build with a `target:` triple, with `{target}` standing for the triple
+    pub target_command: Option<&'static str>,
+    /// Artifact globs for a build with a `target:` triple
+    pub target_artifacts: &'static [&'static str],
+    /// Project directories the build reuses between runs (dependencies, incremental output).
+    /// They are not synced into the sandbox or onto a node; the copies there are kept instead.
+    pub cache_dirs: &'static [&'static str],
+    /// Executables that must be installed
+    pub tools: &'static [&'static str],
+}
+
+pub const ADAPTERS: &[LanguageAdapter] = &[
+    LanguageAdapter {
+        name: "rust",
+        aliases: &["cargo"],
+        command: "cargo build --release --offline",
+        fetch_command: Some("cargo fetch"),
+        artifacts: &["target/release/*"],
+        target_command: Some("cargo build --release --offline --target {target}"),
+        target_artifacts: &["target/{target}/release/*"],
+        cache_dirs: &["target"],
+        tools: &["cargo"],
+    },
+    LanguageAdapter {
+        name: "go",
+        aliases: &["golang"],
+        command: "go build -o bin/ ./...",
+        fetch_command: Some("go mod download"),
+        artifacts: &["bin/*"],
+        target_command: None,
+        target_artifacts: &[],
+        cache_dirs: &[],
+        tools: &["go"],
+    },
+    LanguageAdapter {
+        name: "node",
+        aliases: &["javascript", "typescript", "npm"],
+        command: "npm rebuild && npm run build",
+        fetch_command: Some("npm ci --ignore-scripts"),
+        artifacts: &["dist"],
+        target_command: None,
+        target_artifacts: &[],
+        cache_dirs: &["node_modules"],
+        tools: &["node", "npm"],
+    },
+    LanguageAdapter {
+        name: "python",
+        aliases: &["py"],
+        command: "python3 -m build --no-isolation",
+        fetch_command: None,
+        artifacts: &["dist/*"],
+        target_command: None,
+        target_artifacts: &[],
+        cache_dirs: &[".venv"],
+        tools: &["python3"],
+    },
+    LanguageAdapter {
+        name: "java",
+        aliases: &["maven"],
+        command: "mvn -B -o package",
+        fetch_command: Some("mvn -B dependency:go-offline"),
+        artifacts: &["target/*.jar"],
+        target_command: None,
+        target_artifacts: &[],
+        cache_dirs: &["target"],
+        tools: &["mvn", "java"],
+    },
+    LanguageAdapter {
+        name: "gradle",
+        aliases: &["kotlin"],
+        command: "gradle build --offline",
+        fetch_command: Some("gradle dependencies"),
+        artifacts: &["build/libs/*.jar"],
+        target_command: None,
+        target_artifacts: &[],
+        cache_dirs: &["build", ".gradle"],
+        tools: &["gradle", "java"],
+    },
+    LanguageAdapter {
+        name: "cmake",
+        aliases: &["c", "cpp", "c++"],
+        command: "cmake -S . -B build -DCMAKE_BUILD_TYPE=Release && cmake --build build",
+        fetch_command: None,
+        artifacts: &["build/*"],
+        target_command: None,
+        target_artifacts: &[],
+        cache_dirs: &["build"],
+        tools: &["cmake"],
+    },
+];
+
+/// The adapter for a `language:` value, matched case-insensitively by name or alias
+pub fn adapter(language: &str) -> Option<&'static LanguageAdapter> {
+    let language = language.trim().to_lowercase();
+    ADAPTERS
+        .iter()
+        .find(|a| a.name == language || a.aliases.contains(&language.as_str()))
+}
+
+impl LanguageAdapter {
+    /// Default command for a build, for its `target:` triple if it has one
+    pub fn command_for(&self, target: Option<&str>) -> String {
+        match (target, self.target_command) {
+            (Some(target), Some(command)) => command.replace("{target}", target),
+            _ => self.command.to_string(),
+        }
+    }
+
+    /// Default artifact globs for a build, for its `target:` triple if it has one
+    pub fn artifacts_for(&self, target: Option<&str>) -> Vec<String> {
+        match target {
+            Some(target) if !self.target_artifacts.is_empty() => {
+                self.target_artifacts.iter().map(|a| a.replace("{target}", target)).collect()
+            }
+            _ => self.artifacts.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    /// Required tools that are not on the `PATH`
+    pub fn missing_tools(&self) -> Vec<&'static str> {
+        self.tools
+            .iter()
+            .copied()
+            .filter(|tool| fsutils::find_executable(tool).is_none())
+            .collect()
+    }
+
+    /// Fail with a readable message when a required tool is missing
+    pub fn check_tools(&self) -> Result<(), String> {
+        let missing = self.missing_tools();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} builds need {}, which {} not installed or not on the PATH",
+                self.name,
+                missing.join(", "),
+                if missing.len() == 1 { "is" } else { "are" }
+            ))
+        }
+    }
+}
+
+/// Known languages, for the GUI
+#[tauri::command]
+pub fn get_language_adapters() -> Vec<LanguageAdapter> {
+    ADAPTERS.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::PolicyDecision;
+    use crate::security::{validate_command, SecurityConfig};
+
+    #[test]
+    fn defaults_pass_the_default_policy() {
+        let config = SecurityConfig::default();
+        for adapter in ADAPTERS {
+            let commands = [
+                Some(adapter.command_for(None)),
+                Some(adapter.command_for(Some("aarch64-unknown-linux-gnu"))),
+                adapter.fetch_command.map(str::to_string),
+            ];
+            for command in commands.into_iter().flatten() {
+                assert!(matches!(validate_command(&command, &config), PolicyDecision::Allow), "{}", command);
+            }
+        }
+    }
+
+    #[test]
+    fn target_command_builds_offline() {
+        let rust = adapter("Cargo").unwrap();
+        assert_eq!(rust.fetch_command, Some("cargo fetch"));
+        assert_eq!(
+            rust.command_for(Some("x86_64-pc-windows-gnu")),
+            "cargo build --release --offline --target x86_64-pc-windows-gnu"
+        );
+        assert_eq!(rust.artifacts_for(Some("x86_64-pc-windows-gnu")), ["target/x86_64-pc-windows-gnu/release/*"]);
+    }
+}
@@ -1,18 +1,33 @@
 use tauri::Manager;
 use tauri::State;
 
-mod project;
+pub mod project;
 pub mod config;
+pub mod compose;
 pub mod build;
 pub use crate::build::run_build_no_window;
 pub mod plugin;
+pub mod installer;
+pub mod requirement;
+pub mod wasm;
+pub mod rpc;
 pub mod packaging;
 mod cicd;
 mod remotenode;
-mod toolchain;
-mod fsutils;
-mod security;
+pub mod toolchain;
+pub mod fsutils;
+pub mod security;
+pub mod policy;
+pub mod history;
+pub mod artifacts;
+pub mod graph;
+pub mod language;
+pub mod probe;
+pub mod lockfile;
+pub mod matrix;
+pub mod vars;
 mod local_nodes;
+mod container;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
@@ -34,6 +49,7 @@ pub fn run() {
         .manage(remotenode::RemoteNodeManager::default())
         .manage(std::sync::Mutex::new(project::ProjectManager::default()))
         .manage(local_nodes::LocalNodeManager::new())
+        .manage(build::BuildManager::default())
         .setup(|app| {
             let handle = app.handle();
             let project_manager_state = handle.state::<std::sync::Mutex<project::ProjectManager>>();
@@ -48,6 +64,7 @@ pub fn run() {
             }
             
             project_manager.load_projects(&config_dir);
+            handle.manage(history::BuildHistory::new(&config_dir));
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -57,8 +74,32 @@ pub fn run() {
             project::add_project,
             project::remove_project,
             config::read_config,
+            config::validate_config,
+            config::get_config_schema,
+            config::get_config_profiles,
             build::run_build,
-            // plugin::list_plugins, // This will be called with plugin directory from frontend
+            build::run_build_graph,
+            build::run_build_on_node,
+            build::cancel_build,
+            build::get_effective_security,
+            build::check_build_policy,
+            security::clear_sandbox,
+            history::list_builds,
+            history::get_build_log,
+            history::get_build_artifacts,
+            history::delete_build,
+            artifacts::get_latest_artifacts,
+            language::get_language_adapters,
+            probe::get_toolchains,
+            plugin::list_plugins,
+            plugin::run_plugin_command,
+            plugin::plugin_provides,
+            installer::install_plugin,
+            installer::uninstall_plugin,
+            installer::enable_plugin,
+            installer::disable_plugin,
+            installer::rollback_plugin,
+            installer::list_installed_plugins,
             packaging::create_package,
             cicd::add_webhook,
             cicd::list_webhooks,
@@ -76,6 +117,11 @@ pub fn run() {
             local_nodes::remove_node,
             local_nodes::scan_local_nodes
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|_, event| {
+            if let tauri::RunEvent::Exit = event {
+                rpc::stop_all();
+            }
+        });
 }
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::process::Command;
 use tauri::State;
 use tokio::sync::Mutex;
@@ -280,6 +281,24 @@ fn check_docker_available() -> bool {
         .is_ok()
 }
 
+fn check_podman_available() -> bool {
+    Command::new("podman")
+        .args(["--version"])
+        .output()
+        .is_ok()
+}
+
+/// Engine for container builds: Docker if available, otherwise Podman
+pub fn container_engine() -> Option<&'static str> {
+    if check_docker_available() {
+        Some("docker")
+    } else if check_podman_available() {
+        Some("podman")
+    } else {
+        None
+    }
+}
+
 #[cfg(target_os = "windows")]
 fn check_wsl_available() -> bool {
     Command::new("wsl")
@@ -390,6 +409,16 @@ fn get_system_memory() -> u64 {
     8192 // Default fallback
 }
 
+/// Name of the container backing a Docker node
+pub fn docker_node_container(node_id: &str) -> String {
+    format!("build-studio-{}", node_id)
+}
+
+/// Host directory mounted at `/workspace` in a Docker node
+pub fn docker_node_workspace(node_id: &str) -> PathBuf {
+    PathBuf::from(format!("/tmp/build-studio-{}", node_id))
+}
+
 // Docker node management
 async fn create_docker_node(node_id: &str, config: &LocalNodeConfig) -> Result<(), String> {
     let image_name = match config.platform.as_str() {
@@ -401,7 +430,7 @@ async fn create_docker_node(node_id: &str, config: &LocalNodeConfig) -> Result<(
         _ => "ubuntu:22.04",
     };
     
-    let container_name = format!("build-studio-{}", node_id);
+    let container_name = docker_node_container(node_id);
     
     // Pull the image
     let pull_output = Command::new("docker")
@@ -416,7 +445,10 @@ async fn create_docker_node(node_id: &str, config: &LocalNodeConfig) -> Result<(
     // Create and start container
     let memory_arg = format!("{}m", config.memory);
     let cpu_arg = config.cpu_cores.to_string();
-    let volume_arg = format!("/tmp/build-studio-{}:/workspace", node_id);
+    // Create the workspace ourselves so that it is owned by the user rather than by the Docker daemon
+    let workspace = docker_node_workspace(node_id);
+    std::fs::create_dir_all(&workspace).map_err(|e| format!("Failed to create node workspace: {}", e))?;
+    let volume_arg = format!("{}:/workspace", workspace.display());
     
     let run_args = vec![
         "run", "-d", "--name", &container_name,
@@ -539,7 +571,7 @@ async fn create_auto_node(node_id: &str, config: &LocalNodeConfig) -> Result<(),
 
 // Node control functions (simplified implementations)
 async fn start_docker_node(node: &mut LocalNode) -> Result<(), String> {
-    let container_name = format!("build-studio-{}", node.id);
+    let container_name = docker_node_container(&node.id);
     let output = Command::new("docker")
         .args(&["start", &container_name])
         .output()
@@ -555,7 +587,7 @@ async fn start_docker_node(node: &mut LocalNode) -> Result<(), String> {
 }
 
 async fn stop_docker_node(node: &mut LocalNode) -> Result<(), String> {
-    let container_name = format!("build-studio-{}", node.id);
+    let container_name = docker_node_container(&node.id);
     let output = Command::new("docker")
         .args(&["stop", &container_name])
         .output()
@@ -570,7 +602,7 @@ async fn stop_docker_node(node: &mut LocalNode) -> Result<(), String> {
 }
 
 async fn remove_docker_node(node: &LocalNode) -> Result<(), String> {
-    let container_name = format!("build-studio-{}", node.id);
+    let container_name = docker_node_container(&node.id);
     Command::new("docker")
         .args(&["rm", "-f", &container_name])
         .output()
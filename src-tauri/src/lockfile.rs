@@ -0,0 +1,304 @@
+//! Toolchain lockfile for Build Studio
+//! `buildstudio.lock` records the tools each build resolved to on the host that wrote it (path,
+//! version and digest), so that later builds can be checked against it
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use crate::build::BuildJob;
+use crate::fsutils;
+use crate::language;
+use crate::local_nodes;
+use crate::probe;
+
+/// Lockfile written next to `buildstudio.config.yaml`
+pub const LOCK_FILE: &str = "buildstudio.lock";
+/// Format version of the lockfile
+const LOCK_VERSION: u32 = 1;
+
+/// One executable a build uses
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LockedTool {
+    /// Name the build invokes it by, or its path for toolchain wrapper scripts
+    pub name: String,
+    /// Resolved path, with symlinks followed
+    pub path: String,
+    pub version: Option<String>,
+    /// SHA-256 of the executable
+    pub sha256: String,
+}
+
+/// The toolchain one build resolved to
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LockedBuild {
+    pub name: String,
+    pub toolchain: String,
+    pub target: Option<String>,
+    /// Container image and the id it resolved to, for builds in a container
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub image_id: Option<String>,
+    /// Tools found on this host; empty for container and node builds, which bring their own
+    pub tools: Vec<LockedTool>,
+}
+
+/// Contents of `buildstudio.lock`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Lockfile {
+    pub version: u32,
+    pub created_at: String,
+    pub builds: Vec<LockedBuild>,
+}
+
+impl Lockfile {
+    pub fn new(builds: Vec<LockedBuild>) -> Self {
+        Self {
+            version: LOCK_VERSION,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            builds,
+        }
+    }
+
+    pub fn build(&self, name: &str) -> Option<&LockedBuild> {
+        self.builds.iter().find(|b| b.name == name)
+    }
+}
+
+pub fn lock_path(project_dir: &Path) -> PathBuf {
+    project_dir.join(LOCK_FILE)
+}
+
+/// Read the project's lockfile, if it has one
+pub fn read_lockfile(project_dir: &Path) -> Result<Option<Lockfile>, String> {
+    let path = lock_path(project_dir);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let lockfile: Lockfile =
+        serde_json::from_str(&content).map_err(|e| format!("Invalid lockfile {}: {}", path.display(), e))?;
+    if lockfile.version != LOCK_VERSION {
+        return Err(format!("Unsupported lockfile version {} in {}", lockfile.version, path.display()));
+    }
+    Ok(Some(lockfile))
+}
+
+pub fn write_lockfile(project_dir: &Path, lockfile: &Lockfile) -> Result<(), String> {
+    let path = lock_path(project_dir);
+    let content = serde_json::to_string_pretty(lockfile).map_err(|e| e.to_string())?;
+    fs::write(&path, content + "\n").map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Names of the executables a local job runs, and whether each is a tool whose version can be
+/// asked for. An arbitrary program from the build command is never run just to ask.
+fn job_tools(job: &BuildJob) -> Vec<(String, bool)> {
+    let profile = &job.toolchain;
+    let mut tools: Vec<(String, bool)> = Vec::new();
+    let compilers = [&profile.cc, &profile.cxx, &profile.ar, &profile.ld].into_iter().flatten();
+    for tool in compilers.chain(&profile.tools) {
+        tools.push((probe::command_program(tool).unwrap_or(tool).to_string(), true));
+    }
+    if let Some(adapter) = job.language.as_deref().and_then(language::adapter) {
+        tools.extend(adapter.tools.iter().map(|t| (t.to_string(), true)));
+    }
+    if let Some(program) = probe::command_program(&job.command) {
+        tools.push((program.to_string(), probe::is_known_tool(program)));
+    }
+    let mut seen = std::collections::HashSet::new();
+    tools.retain(|(name, _)| seen.insert(name.clone()));
+    tools
+}
+
+fn lock_tool(name: &str, query_version: bool, dirs: &[PathBuf]) -> Option<LockedTool> {
+    let found = fsutils::find_executable_in(name, dirs)?;
+    let path = fs::canonicalize(&found).unwrap_or(found);
+    Some(LockedTool {
+        name: name.to_string(),
+        version: if query_version { probe::version_line(&path, probe::version_args(name)) } else { None },
+        sha256: sha256_file(&path).ok()?,
+        path: path.to_string_lossy().to_string(),
+    })
+}
+
+/// Id of a local container image, if it has been pulled
+fn image_id(image: &str) -> Option<String> {
+    let engine = local_nodes::container_engine()?;
+    let output = Command::new(engine)
+        .args(["image", "inspect", "-f", "{{.Id}}", image])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !id.is_empty()).then_some(id)
+}
+
+/// Resolve the tools `job` would run with on this host. Tools that cannot be found are left out.
+pub fn lock_job(job: &BuildJob) -> LockedBuild {
+    let mut locked = LockedBuild {
+        name: job.build_name.clone().unwrap_or_else(|| job.command.clone()),
+        toolchain: job.toolchain.name.clone(),
+        target: job.toolchain.target.clone(),
+        image: job.container.clone(),
+        image_id: job.container.as_deref().and_then(image_id),
+        tools: Vec::new(),
+    };
+    if job.container.is_some() || job.node.is_some() {
+        return locked;
+    }
+
+    let mut dirs: Vec<PathBuf> = job.toolchain.path.iter().map(PathBuf::from).collect();
+    if let Some(path) = std::env::var_os("PATH") {
+        dirs.extend(std::env::split_paths(&path));
+    }
+    // Asking for versions starts a process per tool; do it in parallel
+    let tools = job_tools(job);
+    locked.tools = thread::scope(|scope| {
+        let handles: Vec<_> = tools
+            .iter()
+            .map(|(name, query)| scope.spawn(|| lock_tool(name, *query, &dirs)))
+            .collect();
+        handles.into_iter().filter_map(|h| h.join().ok().flatten()).collect()
+    });
+    locked
+}
+
+/// Differences between the locked toolchain of a build and the one found now
+pub fn differences(locked: &LockedBuild, current: &LockedBuild) -> Vec<String> {
+    let mut differences = Vec::new();
+    let mut compare = |what: &str, locked: &Option<String>, current: &Option<String>| {
+        if locked != current {
+            differences.push(format!(
+                "{} is {} but the lockfile has {}",
+                what,
+                current.as_deref().unwrap_or("none"),
+                locked.as_deref().unwrap_or("none")
+            ));
+        }
+    };
+    compare("toolchain", &Some(locked.toolchain.clone()), &Some(current.toolchain.clone()));
+    compare("target", &locked.target, &current.target);
+    compare("image", &locked.image, &current.image);
+    compare("image id", &locked.image_id, &current.image_id);
+
+    for tool in &locked.tools {
+        match current.tools.iter().find(|t| t.name == tool.name) {
+            None => differences.push(format!("{} is not installed", tool.name)),
+            Some(found) if found.version != tool.version => differences.push(format!(
+                "{} version is {} but the lockfile has {}",
+                tool.name,
+                found.version.as_deref().unwrap_or("unknown"),
+                tool.version.as_deref().unwrap_or("unknown")
+            )),
+            Some(found) if found.sha256 != tool.sha256 => {
+                differences.push(format!("{} at {} differs from the locked executable", tool.name, found.path))
+            }
+            Some(_) => {}
+        }
+    }
+    for tool in &current.tools {
+        if !locked.tools.iter().any(|t| t.name == tool.name) {
+            differences.push(format!("{} is not in the lockfile", tool.name));
+        }
+    }
+    differences
+}
+
+/// Check a job against the project's lockfile, failing with every difference found
+pub fn verify_job(lockfile: &Lockfile, job: &BuildJob) -> Result<LockedBuild, String> {
+    let current = lock_job(job);
+    let locked = lockfile
+        .build(&current.name)
+        .ok_or_else(|| format!("Build '{}' is not in {}", current.name, LOCK_FILE))?;
+    let differences = differences(locked, &current);
+    if differences.is_empty() {
+        Ok(current)
+    } else {
+        Err(format!(
+            "Build '{}' does not match {}:\n  {}",
+            current.name,
+            LOCK_FILE,
+            differences.join("\n  ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool(name: &str, version: &str, sha256: &str) -> LockedTool {
+        LockedTool {
+            name: name.to_string(),
+            path: format!("/usr/bin/{}", name),
+            version: Some(version.to_string()),
+            sha256: sha256.to_string(),
+        }
+    }
+
+    fn locked() -> LockedBuild {
+        LockedBuild {
+            name: "app".to_string(),
+            toolchain: "native".to_string(),
+            target: None,
+            image: None,
+            image_id: None,
+            tools: vec![tool("cargo", "cargo 1.80.0", "aa"), tool("rustc", "rustc 1.80.0", "bb")],
+        }
+    }
+
+    #[test]
+    fn matching_toolchains_have_no_differences() {
+        assert!(differences(&locked(), &locked()).is_empty());
+    }
+
+    #[test]
+    fn changed_versions_and_digests_are_reported() {
+        let mut current = locked();
+        current.tools[0] = tool("cargo", "cargo 1.81.0", "cc");
+        current.tools[1].sha256 = "dd".to_string();
+        assert_eq!(
+            differences(&locked(), &current),
+            [
+                "cargo version is cargo 1.81.0 but the lockfile has cargo 1.80.0",
+                "rustc at /usr/bin/rustc differs from the locked executable",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_and_extra_tools_are_reported() {
+        let mut current = locked();
+        current.tools[1] = tool("cross", "cross 0.2.5", "ee");
+        assert_eq!(
+            differences(&locked(), &current),
+            ["rustc is not installed", "cross is not in the lockfile"]
+        );
+    }
+
+    #[test]
+    fn changed_image_ids_are_reported() {
+        let image = |id: &str| LockedBuild {
+            image: Some("rust:1.80".to_string()),
+            image_id: Some(id.to_string()),
+            tools: Vec::new(),
+            ..locked()
+        };
+        assert!(differences(&image("sha256:1111"), &image("sha256:1111")).is_empty());
+        assert_eq!(
+            differences(&image("sha256:1111"), &image("sha256:2222")),
+            ["image id is sha256:2222 but the lockfile has sha256:1111"]
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! Build matrices for Build Studio
+//! A build with a `matrix:` expands into one build per combination of its axes' values, named
+//! like `app (linux, release)`, with `${matrix.<axis>}` replaced by the cell's values
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::config::BuildConfig;
+
+/// One value of a matrix axis; numbers and booleans are used as text
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum MatrixValue {
+    Text(String),
+    Number(serde_json::Number),
+    Bool(bool),
+}
+
+impl fmt::Display for MatrixValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatrixValue::Text(text) => write!(f, "{}", text),
+            MatrixValue::Number(number) => write!(f, "{}", number),
+            MatrixValue::Bool(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// A combination of values, one per key
+type Combination = BTreeMap<String, String>;
+
+/// Axes of a build matrix and the rules that adjust their cross product
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default)]
+pub struct Matrix {
+    /// Combinations to add after `exclude`. An entry whose axis values match existing
+    /// combinations adds its other keys to them; any other entry is added as a combination.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<BTreeMap<String, MatrixValue>>,
+    /// Combinations to leave out; an entry removes every combination it matches
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<BTreeMap<String, MatrixValue>>,
+    /// Axis names, each with the values it takes
+    #[serde(flatten)]
+    pub axes: BTreeMap<String, Vec<MatrixValue>>,
+}
+
+/// Where a build generated from a matrix came from
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MatrixCell {
+    /// Name of the build declaring the matrix
+    pub build: String,
+    /// Value of each axis for this build
+    pub values: BTreeMap<String, String>,
+}
+
+fn as_text(rule: &BTreeMap<String, MatrixValue>) -> Combination {
+    rule.iter().map(|(key, value)| (key.clone(), value.to_string())).collect()
+}
+
+/// Whether `combination` has every value `rule` names
+fn matches(combination: &Combination, rule: &Combination) -> bool {
+    rule.iter().all(|(key, value)| combination.get(key) == Some(value))
+}
+
+impl Matrix {
+    /// Cross product of the axes, less `exclude`, plus `include`, in a fixed order: axes
+    /// alphabetically, values as listed, included combinations last
+    pub fn combinations(&self) -> Result<Vec<Combination>, String> {
+        let mut combinations = vec![Combination::new()];
+        for (axis, values) in &self.axes {
+            if values.is_empty() {
+                return Err(format!("axis '{}' has no values", axis));
+            }
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.insert(axis.clone(), value.to_string());
+                        combination
+                    })
+                })
+                .collect();
+        }
+        if self.axes.is_empty() {
+            combinations.clear();
+        }
+
+        for rule in &self.exclude {
+            if let Some(key) = rule.keys().find(|key| !self.axes.contains_key(*key)) {
+                return Err(format!("exclude names unknown axis '{}'", key));
+            }
+            let rule = as_text(rule);
+            combinations.retain(|combination| !matches(combination, &rule));
+        }
+
+        for rule in &self.include {
+            let rule = as_text(rule);
+            let (on_axes, extra): (Combination, Combination) =
+                rule.clone().into_iter().partition(|(key, _)| self.axes.contains_key(key));
+            let mut matched = false;
+            for combination in combinations.iter_mut().filter(|c| !on_axes.is_empty() && matches(c, &on_axes)) {
+                for (key, value) in &extra {
+                    combination.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+                matched = true;
+            }
+            if !matched {
+                combinations.push(rule);
+            }
+        }
+
+        if combinations.is_empty() {
+            return Err("no combinations are left".to_string());
+        }
+        Ok(combinations)
+    }
+}
+
+/// Replace `${matrix.<key>}` in `text` with the cell's values, leaving escaped `$${` alone
+fn substitute(text: &str, values: &Combination) -> Result<String, String> {
+    let mut substituted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${matrix.") {
+        let escaped = rest[..start].ends_with('$');
+        let end = rest[start..].find('}').map(|end| start + end);
+        match end {
+            Some(end) if !escaped => {
+                let key = &rest[start + "${matrix.".len()..end];
+                let value = values.get(key).ok_or_else(|| format!("there is no matrix axis '{}'", key))?;
+                substituted.push_str(&rest[..start]);
+                substituted.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                let next = start + "${matrix.".len();
+                substituted.push_str(&rest[..next]);
+                rest = &rest[next..];
+            }
+        }
+    }
+    substituted.push_str(rest);
+    Ok(substituted)
+}
+
+/// The build for one combination of a matrix. Its name lists the combination's axis values, so
+/// keys added by `include` do not rename it.
+fn cell(build: &BuildConfig, matrix: &Matrix, values: Combination) -> Result<BuildConfig, String> {
+    let mut cell = build.clone();
+    let mut labels: Vec<&str> = matrix.axes.keys().filter_map(|axis| values.get(axis)).map(String::as_str).collect();
+    if labels.is_empty() {
+        labels = values.values().map(String::as_str).collect();
+    }
+    cell.name = format!("{} ({})", build.name, labels.join(", "));
+    cell.matrix = None;
+
+    let expand = |text: &mut String| -> Result<(), String> {
+        *text = substitute(text, &values)?;
+        Ok(())
+    };
+    expand(&mut cell.platform)?;
+    expand(&mut cell.command)?;
+    let optional = [
+        &mut cell.fetch,
+        &mut cell.language,
+        &mut cell.plugin,
+        &mut cell.container,
+        &mut cell.toolchain,
+        &mut cell.target,
+    ];
+    for field in optional.into_iter().flatten() {
+        expand(field)?;
+    }
+    for field in cell.artifacts.iter_mut().chain(cell.depends_on.iter_mut()) {
+        expand(field)?;
+    }
+    cell.matrix_cell = Some(MatrixCell {
+        build: build.name.clone(),
+        values,
+    });
+    Ok(cell)
+}
+
+/// The builds a build stands for: itself, or one per cell of its matrix
+pub fn expand(build: &BuildConfig) -> Result<Vec<BuildConfig>, String> {
+    let Some(matrix) = &build.matrix else {
+        return Ok(vec![build.clone()]);
+    };
+    let context = |e: String| format!("Build '{}' matrix: {}", build.name, e);
+    matrix
+        .combinations()
+        .map_err(context)?
+        .into_iter()
+        .map(|values| cell(build, matrix, values).map_err(context))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn build(matrix: serde_json::Value) -> BuildConfig {
+        serde_json::from_value(json!({
+            "name": "app",
+            "platform": "${matrix.os}",
+            "command": "make MODE=${matrix.mode}",
+            "artifacts": ["out/${matrix.os}/${matrix.mode}/*"],
+            "matrix": matrix,
+        }))
+        .unwrap()
+    }
+
+    fn names(builds: &[BuildConfig]) -> Vec<&str> {
+        builds.iter().map(|b| b.name.as_str()).collect()
+    }
+
+    #[test]
+    fn cells_are_named_after_their_axis_values() {
+        let builds = expand(&build(json!({"os": ["linux", "windows"], "mode": ["debug", "release"]}))).unwrap();
+        assert_eq!(
+            names(&builds),
+            ["app (debug, linux)", "app (debug, windows)", "app (release, linux)", "app (release, windows)"]
+        );
+        let cell = &builds[1];
+        assert_eq!(cell.platform, "windows");
+        assert_eq!(cell.command, "make MODE=debug");
+        assert_eq!(cell.artifacts, ["out/windows/debug/*"]);
+        assert!(cell.matrix.is_none());
+        assert_eq!(
+            cell.matrix_cell,
+            Some(MatrixCell {
+                build: "app".to_string(),
+                values: BTreeMap::from([("mode".to_string(), "debug".to_string()), ("os".to_string(), "windows".to_string())]),
+            })
+        );
+    }
+
+    #[test]
+    fn exclude_removes_every_matching_combination() {
+        let matrix = json!({
+            "os": ["linux", "windows", "macos"],
+            "mode": ["debug", "release"],
+            "exclude": [{"os": "windows"}, {"os": "macos", "mode": "debug"}],
+        });
+        assert_eq!(
+            names(&expand(&build(matrix)).unwrap()),
+            ["app (debug, linux)", "app (release, linux)", "app (release, macos)"]
+        );
+    }
+
+    #[test]
+    fn include_extends_matching_combinations_or_adds_new_ones() {
+        let matrix = json!({
+            "os": ["linux", "windows"],
+            "mode": ["release"],
+            "include": [{"os": "windows", "target": "x86_64-pc-windows-msvc"}, {"os": "macos", "mode": "release"}],
+        });
+        let builds = expand(&build(matrix)).unwrap();
+        // The extra key does not rename the cell it extends
+        assert_eq!(names(&builds), ["app (release, linux)", "app (release, windows)", "app (release, macos)"]);
+        let values = |i: usize| builds[i].matrix_cell.as_ref().unwrap().values.clone();
+        assert!(!values(0).contains_key("target"));
+        assert_eq!(values(1).get("target").map(String::as_str), Some("x86_64-pc-windows-msvc"));
+        assert_eq!(builds[2].platform, "macos");
+    }
+
+    #[test]
+    fn double_dollar_keeps_a_literal() {
+        let mut build = build(json!({"os": ["linux"], "mode": ["debug"]}));
+        build.command = "echo $${matrix.mode} ${matrix.mode}".to_string();
+        // The escape is left for variable expansion, which turns `$${` into `${`
+        assert_eq!(expand(&build).unwrap()[0].command, "echo $${matrix.mode} debug");
+    }
+
+    #[test]
+    fn unknown_axes_are_errors() {
+        let mut unknown = build(json!({"os": ["linux"], "mode": ["debug"]}));
+        unknown.command = "make ARCH=${matrix.arch}".to_string();
+        assert_eq!(expand(&unknown).unwrap_err(), "Build 'app' matrix: there is no matrix axis 'arch'");
+
+        let exclude = build(json!({"os": ["linux"], "mode": ["debug"], "exclude": [{"arch": "arm64"}]}));
+        assert_eq!(expand(&exclude).unwrap_err(), "Build 'app' matrix: exclude names unknown axis 'arch'");
+    }
+}
@@ -2,6 +2,9 @@
 use serde::{Deserialize, Serialize};
 use std::process::Command;
 
+/// Package types `create_package` can build
+pub const PACKAGE_TYPES: &[&str] = &["deb", "rpm", "msi", "exe", "dmg", "pkg", "apk", "wasm"];
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PackageConfig {
     pub name: String,
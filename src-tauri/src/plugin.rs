@@ -1,90 +1,601 @@
+//! Build Studio plugins
+//! A plugin is a directory with a `plugin.json` or `plugin.yaml` manifest listing the commands it
+//! provides. A command runs its script with the arguments the plugin SDK documents: the command
+//! name, the target platform, the source directory, the output directory, then any extra arguments.
+//! Scripts run on the host; commands of `kind: wasm` plugins are WebAssembly modules run by `wasm`.
+//! A `protocol: jsonrpc` plugin instead has one long-running server that `rpc` sends commands to.
 
 use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
 use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{Manager, State, Window};
+use crate::build::{self, BuildJob, BuildManager};
+use crate::installer::{self, PluginRegistry};
+use crate::requirement::{self, DependencyCheck, DependencyStatus};
+use crate::rpc::{self, Provides, RpcMethod};
+use crate::wasm::WasmSettings;
 
-#[derive(Clone, Serialize, Deserialize)]
+/// Manifest file names, in the order they are looked for
+pub const MANIFEST_FILES: [&str; 2] = ["plugin.json", "plugin.yaml"];
+
+/// Directory, relative to a project or the app config directory, that plugins are found in
+pub const PLUGINS_DIR: &str = "plugins";
+
+/// How a plugin's commands run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginKind {
+    /// Scripts run on the host with full access to it
+    #[default]
+    Script,
+    /// WASI modules run in the embedded runtime, which only sees the project and the plugin
+    Wasm,
+}
+
+/// How Build Studio talks to a plugin's scripts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginProtocol {
+    /// Each command runs its script with the documented arguments
+    #[default]
+    Args,
+    /// The `server` script stays up and gets commands as JSON-RPC requests on stdin
+    Jsonrpc,
+}
+
+/// A command a plugin provides
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PluginCommand {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Script, or `.wasm` module for a `kind: wasm` plugin, relative to the plugin directory;
+    /// unused by `protocol: jsonrpc` plugins
+    #[serde(default)]
+    pub script: String,
+    /// Request a `protocol: jsonrpc` plugin is sent for this command
+    #[serde(default)]
+    pub method: RpcMethod,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Plugin {
     pub name: String,
+    #[serde(default = "unknown_author")]
     pub author: String,
+    #[serde(default = "default_version")]
     pub version: String,
+    #[serde(default = "default_description")]
     pub description: String,
+    #[serde(default)]
+    pub kind: PluginKind,
+    /// Limits of a `kind: wasm` plugin
+    #[serde(default)]
+    pub wasm: WasmSettings,
+    #[serde(default)]
+    pub protocol: PluginProtocol,
+    /// Script serving a `protocol: jsonrpc` plugin, relative to the plugin directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// Plugin directory; set when the manifest is loaded
+    #[serde(default)]
     pub path: String,
+    #[serde(default)]
+    pub commands: Vec<PluginCommand>,
+    /// Platforms the commands can build for; empty means any
+    #[serde(default)]
+    pub platforms: Vec<String>,
+    /// Tools the commands need, such as `go>=1.18`
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Whether the dependencies are met on this host; filled in by `load_plugins`
+    #[serde(default = "ready", skip_deserializing)]
+    pub status: DependencyStatus,
+    /// What was found for each dependency; filled in by `load_plugins`
+    #[serde(default, skip_deserializing)]
+    pub dependency_checks: Vec<DependencyCheck>,
+    /// Whether the plugin was installed into the app config dir rather than found in a directory
+    #[serde(default, skip_deserializing)]
+    pub installed: bool,
+    /// Whether the plugin can run; only installed plugins can be disabled
+    #[serde(default = "enabled", skip_deserializing)]
+    pub enabled: bool,
 }
 
-#[tauri::command]
-pub fn list_plugins(plugin_dir: String) -> Vec<Plugin> {
-    let mut plugins = Vec::new();
-    
-    // Try to read the plugins directory
-    if let Ok(entries) = fs::read_dir(&plugin_dir) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                
-                // Check if it's a directory
-                if path.is_dir() {
-                    // Look for a plugin manifest file (plugin.json or plugin.yaml)
-                    let plugin_json = path.join("plugin.json");
-                    let plugin_yaml = path.join("plugin.yaml");
-                    
-                    let plugin_info = if plugin_json.exists() {
-                        // Parse JSON manifest
-                        if let Ok(content) = fs::read_to_string(&plugin_json) {
-                            serde_json::from_str(&content).unwrap_or_else(|_| {
-                                Plugin {
-                                    name: path.file_name().unwrap().to_string_lossy().to_string(),
-                                    author: "Unknown".to_string(),
-                                    version: "0.1.0".to_string(),
-                                    description: "Plugin description not available".to_string(),
-                                    path: path.to_string_lossy().to_string(),
-                                }
-                            })
-                        } else {
-                            Plugin {
-                                name: path.file_name().unwrap().to_string_lossy().to_string(),
-                                author: "Unknown".to_string(),
-                                version: "0.1.0".to_string(),
-                                description: "Plugin description not available".to_string(),
-                                path: path.to_string_lossy().to_string(),
-                            }
-                        }
-                    } else if plugin_yaml.exists() {
-                        // Parse YAML manifest
-                        if let Ok(content) = fs::read_to_string(&plugin_yaml) {
-                            serde_yaml::from_str(&content).unwrap_or_else(|_| {
-                                Plugin {
-                                    name: path.file_name().unwrap().to_string_lossy().to_string(),
-                                    author: "Unknown".to_string(),
-                                    version: "0.1.0".to_string(),
-                                    description: "Plugin description not available".to_string(),
-                                    path: path.to_string_lossy().to_string(),
-                                }
-                            })
-                        } else {
-                            Plugin {
-                                name: path.file_name().unwrap().to_string_lossy().to_string(),
-                                author: "Unknown".to_string(),
-                                version: "0.1.0".to_string(),
-                                description: "Plugin description not available".to_string(),
-                                path: path.to_string_lossy().to_string(),
-                            }
-                        }
-                    } else {
-                        // No manifest file, create a default plugin entry
-                        Plugin {
-                            name: path.file_name().unwrap().to_string_lossy().to_string(),
-                            author: "Unknown".to_string(),
-                            version: "0.1.0".to_string(),
-                            description: "Plugin description not available".to_string(),
-                            path: path.to_string_lossy().to_string(),
-                        }
-                    };
-                    
-                    plugins.push(plugin_info);
-                }
+fn ready() -> DependencyStatus {
+    DependencyStatus::Ready
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn unknown_author() -> String {
+    "Unknown".to_string()
+}
+
+fn default_version() -> String {
+    "0.1.0".to_string()
+}
+
+fn default_description() -> String {
+    "Plugin description not available".to_string()
+}
+
+impl Plugin {
+    /// Entry for a plugin directory whose manifest is missing or unreadable
+    fn unnamed(dir: &Path) -> Self {
+        Self {
+            name: dir_name(dir),
+            author: unknown_author(),
+            version: default_version(),
+            description: default_description(),
+            kind: PluginKind::Script,
+            wasm: WasmSettings::default(),
+            protocol: PluginProtocol::Args,
+            server: None,
+            path: dir.to_string_lossy().to_string(),
+            commands: Vec::new(),
+            platforms: Vec::new(),
+            dependencies: Vec::new(),
+            status: DependencyStatus::Ready,
+            dependency_checks: Vec::new(),
+            installed: false,
+            enabled: true,
+        }
+    }
+
+    /// Check the dependencies on this host and record the result
+    pub fn check_dependencies(&mut self) {
+        self.dependency_checks = requirement::check_all(&self.dependencies);
+        self.status = requirement::overall_status(&self.dependency_checks);
+    }
+
+    /// Fail with every unmet dependency unless all are met
+    pub fn require_dependencies(&self) -> Result<(), String> {
+        let checks = requirement::check_all(&self.dependencies);
+        let unmet: Vec<String> = checks.into_iter().filter_map(|check| check.message).collect();
+        if unmet.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Plugin '{}' cannot run: it {}", self.name, unmet.join("; it ")))
+        }
+    }
+
+    /// The command named `name`
+    pub fn command(&self, name: &str) -> Result<&PluginCommand, String> {
+        self.commands.iter().find(|c| c.name == name).ok_or_else(|| {
+            let known: Vec<&str> = self.commands.iter().map(|c| c.name.as_str()).collect();
+            if known.is_empty() {
+                format!("Plugin '{}' has no commands", self.name)
+            } else {
+                format!("Plugin '{}' has no command '{}'; it has {}", self.name, name, known.join(", "))
             }
+        })
+    }
+
+    /// The plugin directory and `script` in it, which must be a file inside it
+    fn resolve_script(&self, script: &str) -> Result<(PathBuf, PathBuf), String> {
+        let plugin_dir = fs::canonicalize(&self.path)
+            .map_err(|e| format!("Failed to read plugin directory {}: {}", self.path, e))?;
+        let path = fs::canonicalize(plugin_dir.join(script))
+            .map_err(|e| format!("Failed to find script {} of plugin '{}': {}", script, self.name, e))?;
+        if !path.starts_with(&plugin_dir) || !path.is_file() {
+            return Err(format!("Script {} of plugin '{}' must be a file inside the plugin directory", script, self.name));
+        }
+        Ok((plugin_dir, path))
+    }
+
+    /// The plugin directory and server script of a `protocol: jsonrpc` plugin
+    pub fn server(&self) -> Result<(PathBuf, PathBuf), String> {
+        match (&self.server, self.protocol) {
+            (Some(server), PluginProtocol::Jsonrpc) if self.kind == PluginKind::Script => self.resolve_script(server),
+            (None, PluginProtocol::Jsonrpc) => Err(format!("Plugin '{}' uses protocol jsonrpc but names no server", self.name)),
+            (_, PluginProtocol::Jsonrpc) => Err(format!("WebAssembly plugin '{}' cannot use protocol jsonrpc", self.name)),
+            _ => Err(format!("Plugin '{}' does not use protocol jsonrpc", self.name)),
+        }
+    }
+
+    /// Whether the plugin builds for `platform`; `wasm` and `webassembly` are the same platform
+    pub fn supports(&self, platform: &str) -> bool {
+        let canonical = |p: &str| if p == "webassembly" { "wasm".to_string() } else { p.to_string() };
+        self.platforms.is_empty() || self.platforms.iter().any(|p| canonical(p) == canonical(platform))
+    }
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Read the manifest of the plugin in `dir`
+pub fn load_plugin(dir: &Path) -> Result<Plugin, String> {
+    let manifest = MANIFEST_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| format!("No plugin.json or plugin.yaml in {}", dir.display()))?;
+    let content = fs::read_to_string(&manifest)
+        .map_err(|e| format!("Failed to read {}: {}", manifest.display(), e))?;
+    let mut plugin: Plugin = if manifest.extension().is_some_and(|e| e == "json") {
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", manifest.display(), e))?
+    } else {
+        serde_yaml::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", manifest.display(), e))?
+    };
+    plugin.path = dir.to_string_lossy().to_string();
+    Ok(plugin)
+}
+
+/// The plugin directories in `dir`
+pub fn plugins_in(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && !dir_name(path).starts_with('.'))
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Directories of the plugins a project can use: those in its `plugins/` directory, then the
+/// enabled installed plugins and those copied into the app's `plugins/` without installing them
+pub fn plugin_dirs(project_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = plugins_in(&project_dir.join(PLUGINS_DIR));
+    if let Ok(registry) = PluginRegistry::open() {
+        dirs.extend(registry.enabled_dirs());
+        dirs.extend(registry.unregistered_dirs());
+    }
+    dirs
+}
+
+/// The first plugin of `dirs` named `name` by its manifest, the id it installs under or, unless
+/// it is installed, its directory
+pub fn find_plugin(dirs: &[PathBuf], name: &str) -> Result<Plugin, String> {
+    find_plugin_in(dirs, name, PluginRegistry::open().ok().as_ref())
+}
+
+fn find_plugin_in(dirs: &[PathBuf], name: &str, registry: Option<&PluginRegistry>) -> Result<Plugin, String> {
+    // An installed plugin's directory is named after its version, which is no name for it
+    let installed = registry.map(|r| r.enabled_dirs()).unwrap_or_default();
+    let mut known = Vec::new();
+    for path in dirs {
+        let plugin = load_plugin(path);
+        let named = |p: &Plugin| p.name == name || installer::plugin_id(&p.name) == name;
+        if (dir_name(path) == name && !installed.contains(path)) || plugin.as_ref().is_ok_and(named) {
+            return plugin;
         }
+        known.push(plugin.map(|p| p.name).unwrap_or_else(|_| dir_name(path)));
+    }
+    if let Some(disabled) = registry.and_then(|r| r.find(name)).filter(|p| !p.enabled) {
+        return Err(format!(
+            "Plugin '{}' is disabled; enable it with `buildstudio-cli plugin enable {}`",
+            disabled.name, disabled.id
+        ));
+    }
+    if known.is_empty() {
+        Err(format!("No plugin '{}'; no plugins are installed or in the project's plugins/ directory", name))
+    } else {
+        Err(format!("No plugin '{}'; found {}", name, known.join(", ")))
+    }
+}
+
+/// Split a `plugin: <name>/<command>` reference; the plugin name may itself contain `/`
+pub fn parse_reference(reference: &str) -> Result<(&str, &str), String> {
+    match reference.rsplit_once('/') {
+        Some((plugin, command)) if !plugin.trim().is_empty() && !command.trim().is_empty() => {
+            Ok((plugin.trim(), command.trim()))
+        }
+        _ => Err(format!("Expected <plugin>/<command>, got: {}", reference)),
+    }
+}
+
+/// Whether `path` can be run directly
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata().is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.extension().is_some_and(|e| e == "exe" || e == "bat" || e == "cmd")
+    }
+}
+
+/// Program (and its arguments) a script that cannot be run directly is run with: its `#!` line,
+/// or the usual interpreter for its extension
+pub(crate) fn interpreter(script: &Path) -> Vec<String> {
+    if is_executable(script) {
+        return Vec::new();
+    }
+    let first_line = fs::read_to_string(script)
+        .ok()
+        .and_then(|content| content.lines().next().map(str::to_string))
+        .unwrap_or_default();
+    if let Some(shebang) = first_line.strip_prefix("#!") {
+        let mut words: Vec<String> = shebang.split_whitespace().map(str::to_string).collect();
+        // `/bin/bash` and the like only exist on Unix; elsewhere look the program up on PATH
+        if cfg!(target_os = "windows") {
+            if let Some(program) = words.first_mut() {
+                *program = Path::new(program.as_str()).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            }
+            if words.first().is_some_and(|p| p == "env") {
+                words.remove(0);
+            }
+        }
+        if !words.is_empty() {
+            return words;
+        }
+    }
+    let extension = script.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let program: &[&str] = match extension.as_str() {
+        "sh" => &["sh"],
+        "py" if cfg!(target_os = "windows") => &["python"],
+        "py" => &["python3"],
+        "js" => &["node"],
+        "ps1" => &["powershell", "-ExecutionPolicy", "Bypass", "-File"],
+        _ => &[],
+    };
+    program.iter().map(|p| p.to_string()).collect()
+}
+
+/// A plugin command ready to run for one platform
+#[derive(Clone, Debug)]
+pub struct PluginRun {
+    pub plugin: String,
+    pub command: String,
+    pub platform: String,
+    pub plugin_dir: PathBuf,
+    pub script: PathBuf,
+    /// Program the script is run with, if it cannot be run directly
+    pub interpreter: Vec<String>,
+    /// Limits the module runs with, for a `kind: wasm` plugin
+    pub wasm: Option<WasmSettings>,
+    /// Request the command is sent as, for a `protocol: jsonrpc` plugin; `script` is its server
+    pub rpc: Option<RpcMethod>,
+    /// Extra arguments passed after the documented ones
+    pub args: Vec<String>,
+}
+
+impl PluginRun {
+    /// Prepare `command` of `plugin` for `platform`, checking that the plugin supports the
+    /// platform, that its dependencies are met and that the command's script lies inside the
+    /// plugin directory
+    pub fn new(plugin: &Plugin, command: &str, platform: &str, args: Vec<String>) -> Result<Self, String> {
+        let entry = plugin.command(command)?;
+        if !plugin.supports(platform) {
+            return Err(format!(
+                "Plugin '{}' does not build for {}; it supports {}",
+                plugin.name,
+                platform,
+                plugin.platforms.join(", ")
+            ));
+        }
+        plugin.require_dependencies()?;
+        let rpc = (plugin.protocol == PluginProtocol::Jsonrpc).then_some(entry.method);
+        let (plugin_dir, script) = match rpc {
+            Some(_) => plugin.server()?,
+            None => plugin.resolve_script(&entry.script)?,
+        };
+        let wasm = (plugin.kind == PluginKind::Wasm).then(|| plugin.wasm.clone());
+        if wasm.is_some() && script.extension().is_none_or(|ext| ext != "wasm") {
+            return Err(format!("Command '{}' of WebAssembly plugin '{}' must name a .wasm module", command, plugin.name));
+        }
+        Ok(Self {
+            plugin: plugin.name.clone(),
+            command: command.to_string(),
+            platform: platform.to_string(),
+            interpreter: if wasm.is_some() { Vec::new() } else { interpreter(&script) },
+            wasm,
+            rpc,
+            plugin_dir,
+            script,
+            args,
+        })
+    }
+
+    /// Prepare the command a `plugin: <name>/<command>` reference names, from the plugins a
+    /// project in `project_dir` can use
+    pub fn from_reference(reference: &str, project_dir: &Path, platform: &str) -> Result<Self, String> {
+        let (plugin, command) = parse_reference(reference)?;
+        Self::new(&find_plugin(&plugin_dirs(project_dir), plugin)?, command, platform, Vec::new())
+    }
+
+    /// Directory, inside `work_dir`, the command writes its output to
+    pub fn output_dir(&self, work_dir: &Path) -> PathBuf {
+        work_dir.join("builds").join(&self.platform)
+    }
+
+    /// Program and arguments that run the command on the sources in `work_dir`
+    pub fn argv(&self, work_dir: &Path) -> Vec<OsString> {
+        let mut argv: Vec<OsString> = self.interpreter.iter().map(OsString::from).collect();
+        argv.push(self.script.clone().into());
+        argv.push(self.command.clone().into());
+        argv.push(self.platform.clone().into());
+        argv.push(work_dir.into());
+        argv.push(self.output_dir(work_dir).into());
+        argv.extend(self.args.iter().map(OsString::from));
+        argv
+    }
+
+    /// Environment variables the plugin SDK documents
+    pub fn env(&self, work_dir: &Path) -> Vec<(String, String)> {
+        vec![
+            ("BUILD_STUDIO_VERSION".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+            ("PLUGIN_DIR".to_string(), self.plugin_dir.to_string_lossy().to_string()),
+            ("PROJECT_DIR".to_string(), work_dir.to_string_lossy().to_string()),
+        ]
+    }
+
+    /// The command as it is checked against the security policy and recorded in the history.
+    /// Each word is quoted, so that an argument cannot read as a further command.
+    pub fn command_line(&self) -> String {
+        let mut words = match self.wasm {
+            Some(_) => vec!["wasm".to_string()],
+            None => self.interpreter.clone(),
+        };
+        words.push(self.script.to_string_lossy().to_string());
+        words.push(self.command.clone());
+        words.push(self.platform.clone());
+        words.extend(self.args.iter().cloned());
+        words.iter().map(|word| shell_word(word)).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// `word` as a single shell word: as it is when no character in it means anything to a shell
+pub(crate) fn shell_word(word: &str) -> String {
+    let plain = !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:,+@%".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        crate::container::shell_quote(word)
+    }
+}
+
+/// The plugins in `plugin_dir`, then the installed ones and those copied into the app's `plugins/`,
+/// with the status of their dependencies
+pub fn load_plugins(plugin_dir: &Path) -> Vec<Plugin> {
+    let mut plugins: Vec<Plugin> = plugins_in(plugin_dir)
+        .iter()
+        .map(|path| load_plugin(path).unwrap_or_else(|_| Plugin::unnamed(path)))
+        .collect();
+    if let Ok(registry) = PluginRegistry::open() {
+        for installed in registry.list().unwrap_or_default() {
+            let dir = registry.version_dir(&installed.id, &installed.version);
+            let mut plugin = load_plugin(&dir).unwrap_or_else(|_| Plugin::unnamed(&dir));
+            plugin.installed = true;
+            plugin.enabled = installed.enabled;
+            plugins.push(plugin);
+        }
+        for dir in registry.unregistered_dirs() {
+            plugins.push(load_plugin(&dir).unwrap_or_else(|_| Plugin::unnamed(&dir)));
+        }
+    }
+    for plugin in &mut plugins {
+        plugin.check_dependencies();
     }
-    
     plugins
 }
+
+/// `load_plugins` off the UI thread, since checking dependencies runs each tool
+#[tauri::command]
+pub async fn list_plugins(plugin_dir: String) -> Result<Vec<Plugin>, String> {
+    tokio::task::spawn_blocking(move || load_plugins(Path::new(&plugin_dir)))
+        .await
+        .map_err(|e| format!("Failed to list plugins: {}", e))
+}
+
+/// Run a plugin command on the project in `cwd`, emitting the same `build-*` events as
+/// `run_build`. The plugin is looked up in `plugin_dir`, or else in the project's and the app's
+/// plugin directories.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn run_plugin_command(
+    window: Window,
+    state: State<'_, BuildManager>,
+    plugin: String,
+    command: String,
+    platform: String,
+    args: Option<Vec<String>>,
+    cwd: String,
+    plugin_dir: Option<String>,
+    build_id: Option<String>,
+    approved: Option<bool>,
+) -> Result<(), String> {
+    let dirs = match plugin_dir {
+        Some(dir) => plugins_in(Path::new(&dir)),
+        None => plugin_dirs(Path::new(&cwd)),
+    };
+    let run = PluginRun::new(&find_plugin(&dirs, &plugin)?, &command, &platform, args.unwrap_or_default())?;
+    let project_config = build::read_project_config(&cwd, None)?;
+    let job = BuildJob::for_plugin(run, &cwd);
+    build::run_gui_job(window, state, project_config.as_ref(), job, build_id, approved.unwrap_or(false)).await
+}
+
+/// What a `protocol: jsonrpc` plugin contributes, asked of its server. The plugin is looked up
+/// like in `run_plugin_command`, and its server runs under the project's security policy.
+#[tauri::command]
+pub async fn plugin_provides(window: Window, plugin: String, cwd: String, plugin_dir: Option<String>) -> Result<Provides, String> {
+    let dirs = match plugin_dir {
+        Some(dir) => plugins_in(Path::new(&dir)),
+        None => plugin_dirs(Path::new(&cwd)),
+    };
+    let plugin = find_plugin(&dirs, &plugin)?;
+    let app_config_dir = window.app_handle().path().app_config_dir().ok();
+    let project_config = build::read_project_config(&cwd, None)?;
+    let security = build::resolve_security(app_config_dir.as_deref(), project_config.as_ref(), None)?;
+    tokio::task::spawn_blocking(move || rpc::provides(&plugin, &security.config))
+        .await
+        .map_err(|e| format!("Failed to ask plugin: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{self, CommandRule, PolicyDecision};
+
+    fn run(script: &str, args: &[&str]) -> PluginRun {
+        PluginRun {
+            plugin: "demo".to_string(),
+            command: "build".to_string(),
+            platform: "linux".to_string(),
+            plugin_dir: PathBuf::from("/plugins/demo"),
+            script: PathBuf::from(script),
+            interpreter: vec!["python3".to_string()],
+            wasm: None,
+            rpc: None,
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn command_line_quotes_each_word() {
+        let plain = run("/plugins/demo/build.py", &["--release"]);
+        assert_eq!(plain.command_line(), "python3 /plugins/demo/build.py build linux --release");
+
+        let spaced = run("/plugins/my demo/build.py", &["it's", ""]);
+        assert_eq!(spaced.command_line(), r#"python3 '/plugins/my demo/build.py' build linux 'it'\''s' ''"#);
+    }
+
+    fn write_plugin(dir: &Path, name: &str, version: &str) {
+        fs::create_dir_all(dir).unwrap();
+        let manifest = serde_json::json!({
+            "name": name, "version": version,
+            "commands": [{"name": "build", "script": "build.sh"}],
+        });
+        fs::write(dir.join("plugin.json"), manifest.to_string()).unwrap();
+        fs::write(dir.join("build.sh"), "#!/bin/sh\n").unwrap();
+    }
+
+    #[test]
+    fn installed_plugins_are_found_by_name_or_id_only() {
+        let root = std::env::temp_dir().join(format!("build-studio-plugins-{}", uuid::Uuid::new_v4()));
+        let config = root.join("config");
+        let registry = PluginRegistry::new(&config);
+        write_plugin(&registry.version_dir("demo-tool", "1.0.0"), "Demo Tool", "1.0.0");
+        let installed = serde_json::json!([{
+            "id": "demo-tool", "name": "Demo Tool", "version": "1.0.0", "enabled": true,
+            "source": "demo.zip", "installed_at": "2026-01-01T00:00:00Z",
+        }]);
+        fs::write(config.join(installer::REGISTRY_FILE), installed.to_string()).unwrap();
+        write_plugin(&config.join(PLUGINS_DIR).join("dropped"), "Dropped", "2.0.0");
+
+        let dirs: Vec<PathBuf> = registry.enabled_dirs().into_iter().chain(registry.unregistered_dirs()).collect();
+        let find = |name| find_plugin_in(&dirs, name, Some(&registry)).map(|p| p.name);
+        assert_eq!(find("Demo Tool"), Ok("Demo Tool".to_string()));
+        assert_eq!(find("demo-tool"), Ok("Demo Tool".to_string()));
+        assert!(find("1.0.0").is_err());
+        assert_eq!(find("dropped"), Ok("Dropped".to_string()));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn arguments_are_not_checked_as_commands() {
+        let rules = [CommandRule::blocked("rm")];
+        let run = run("/plugins/demo/build.py", &["; rm -rf /", "$(rm x)", "`rm x`"]);
+        assert!(matches!(policy::evaluate(&run.command_line(), &rules), PolicyDecision::Allow));
+    }
+}
@@ -0,0 +1,917 @@
+//! Command policy engine for Build Studio
+//! Splits a `sh -c` command line into the simple commands it runs and checks
+//! each executable against allow/deny/ask rules
+
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What to do with a command matched by a rule
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyAction {
+    Allow,
+    Deny,
+    Ask,
+}
+
+/// A single command policy rule. A rule matches a simple command when every
+/// pattern it sets matches; rules are evaluated in order and the first match wins.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(deny_unknown_fields, try_from = "RuleFields")]
+pub struct CommandRule {
+    /// Allow, deny or ask for confirmation
+    pub action: PolicyAction,
+    /// Optional label shown when the rule rejects a command
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Glob matched against the executable's basename (or its full path if the glob contains `/`)
+    #[serde(default)]
+    pub program: Option<String>,
+    /// Regular expression matched against the whole simple command, e.g. `^git push`
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+/// The fields of a `CommandRule` as written in a config, before checking it sets a pattern
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFields {
+    action: PolicyAction,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    program: Option<String>,
+    #[serde(default)]
+    pattern: Option<String>,
+}
+
+impl TryFrom<RuleFields> for CommandRule {
+    type Error = String;
+
+    fn try_from(fields: RuleFields) -> Result<Self, String> {
+        if fields.program.is_none() && fields.pattern.is_none() {
+            return Err("a command rule needs a `program` or a `pattern`".to_string());
+        }
+        Ok(Self {
+            action: fields.action,
+            name: fields.name,
+            program: fields.program,
+            pattern: fields.pattern,
+        })
+    }
+}
+
+impl CommandRule {
+    /// Deny rule for an entry of `SecurityConfig::blocked_commands`
+    pub fn blocked(program: &str) -> Self {
+        Self {
+            action: PolicyAction::Deny,
+            name: Some(format!("blocked_commands: {}", program)),
+            program: Some(program.to_string()),
+            pattern: None,
+        }
+    }
+}
+
+impl fmt::Display for CommandRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            return write!(f, "'{}'", name);
+        }
+        let action = match self.action {
+            PolicyAction::Allow => "allow",
+            PolicyAction::Deny => "deny",
+            PolicyAction::Ask => "ask",
+        };
+        write!(f, "'{}", action)?;
+        if let Some(program) = &self.program {
+            write!(f, " program={}", program)?;
+        }
+        if let Some(pattern) = &self.pattern {
+            write!(f, " pattern=/{}/", pattern)?;
+        }
+        write!(f, "'")
+    }
+}
+
+/// Why a command was denied or needs confirmation
+#[derive(Serialize, Clone, Debug)]
+pub struct PolicyViolation {
+    /// The rule that matched, or a description of the built-in check
+    pub rule: String,
+    /// The token that triggered the rule, as written in the command line
+    pub token: String,
+    /// Byte offset of the token in the command line
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Result of checking a command line against the policy
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "decision", rename_all = "lowercase")]
+pub enum PolicyDecision {
+    Allow,
+    Ask(PolicyViolation),
+    Deny(PolicyViolation),
+}
+
+/// A shell word after quote removal
+#[derive(Clone, Debug)]
+struct Word {
+    /// Value after quote and escape removal
+    text: String,
+    /// Text as written in the command line
+    raw: String,
+    /// Byte offset in the command line
+    offset: usize,
+    /// Contains a variable expansion or command substitution
+    dynamic: bool,
+}
+
+#[derive(Debug)]
+enum Token {
+    Word(Word),
+    /// `|`, `||`, `&&`, `;`, `&`, newline, `(` or `)`
+    Separator,
+    /// `<`, `>`, `>>`, `>&`, `<&`, `&>`, `<<`
+    Redirect,
+}
+
+/// A single executable invocation and its arguments
+#[derive(Debug)]
+struct SimpleCommand {
+    words: Vec<Word>,
+    /// Stands for a nested script whose text is only known at runtime, e.g. `sh -c "$CMD"`
+    script: bool,
+}
+
+/// Shell keywords that may precede a command without being one
+const PREFIX_KEYWORDS: &[&str] = &["if", "then", "else", "elif", "do", "while", "until", "!", "{"];
+/// Shell keywords that close a compound command
+const CLOSING_KEYWORDS: &[&str] = &["fi", "done", "esac", "}"];
+/// Commands that run another command given as their arguments
+const WRAPPERS: &[&str] = &[
+    "env", "sudo", "doas", "nice", "nohup", "time", "timeout", "exec", "command", "xargs", "builtin", "source", ".",
+];
+/// `find` actions that run a command, ended by `;` or `+`
+const FIND_ACTIONS: &[&str] = &["-exec", "-execdir", "-ok", "-okdir"];
+/// Shells whose `-c` argument is parsed as a nested command line
+const SHELLS: &[&str] = &["sh", "bash", "dash", "zsh", "ksh"];
+
+struct Lexer<'a> {
+    input: &'a str,
+    chars: Vec<(usize, char)>,
+    pos: usize,
+    base: usize,
+    /// Simple commands found inside `$(...)` and backticks
+    nested: Vec<SimpleCommand>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(input: &'a str, base: usize) -> Self {
+        Self {
+            input,
+            chars: input.char_indices().collect(),
+            pos: 0,
+            base,
+            nested: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|(_, c)| *c)
+    }
+
+    fn peek_at(&self, n: usize) -> Option<char> {
+        self.chars.get(self.pos + n).map(|(_, c)| *c)
+    }
+
+    fn offset(&self) -> usize {
+        self.chars.get(self.pos).map(|(i, _)| *i).unwrap_or(self.input.len())
+    }
+
+    fn error(&self, message: &str, offset: usize) -> PolicyViolation {
+        PolicyViolation {
+            rule: "shell syntax".to_string(),
+            token: self.input[offset.min(self.input.len())..].chars().take(16).collect(),
+            position: self.base + offset,
+            message: format!("Command could not be parsed: {} at position {}", message, self.base + offset),
+        }
+    }
+
+    fn tokenize(mut self) -> Result<(Vec<Token>, Vec<SimpleCommand>), PolicyViolation> {
+        let mut tokens = Vec::new();
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' => self.pos += 1,
+                '\n' | ';' | '(' | ')' => {
+                    self.pos += 1;
+                    if c == ';' && self.peek() == Some(';') {
+                        self.pos += 1;
+                    }
+                    tokens.push(Token::Separator);
+                }
+                '|' => {
+                    self.pos += if self.peek_at(1) == Some('|') { 2 } else { 1 };
+                    tokens.push(Token::Separator);
+                }
+                '&' => {
+                    match self.peek_at(1) {
+                        Some('&') => {
+                            self.pos += 2;
+                            tokens.push(Token::Separator);
+                        }
+                        Some('>') => {
+                            self.pos += if self.peek_at(2) == Some('>') { 3 } else { 2 };
+                            tokens.push(Token::Redirect);
+                        }
+                        _ => {
+                            self.pos += 1;
+                            tokens.push(Token::Separator);
+                        }
+                    }
+                }
+                '<' | '>' => {
+                    self.lex_redirect();
+                    tokens.push(Token::Redirect);
+                }
+                '#' => {
+                    while let Some(c) = self.peek() {
+                        if c == '\n' {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                _ => {
+                    let word = self.lex_word()?;
+                    // A file descriptor number directly before a redirection is not a word
+                    if matches!(self.peek(), Some('<') | Some('>'))
+                        && !word.raw.is_empty()
+                        && word.raw.chars().all(|c| c.is_ascii_digit())
+                    {
+                        continue;
+                    }
+                    tokens.push(Token::Word(word));
+                }
+            }
+        }
+        Ok((tokens, self.nested))
+    }
+
+    fn lex_redirect(&mut self) {
+        self.pos += 1;
+        if matches!(self.peek(), Some('<') | Some('>') | Some('&') | Some('|')) {
+            self.pos += 1;
+        }
+    }
+
+    fn lex_word(&mut self) -> Result<Word, PolicyViolation> {
+        let start = self.offset();
+        let mut text = String::new();
+        let mut dynamic = false;
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\n' | ';' | '&' | '|' | '(' | ')' | '<' | '>' => break,
+                '\\' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some('\n') => self.pos += 1,
+                        Some(escaped) => {
+                            text.push(escaped);
+                            self.pos += 1;
+                        }
+                        None => {}
+                    }
+                }
+                '\'' => {
+                    let quote = self.offset();
+                    self.pos += 1;
+                    loop {
+                        match self.peek() {
+                            Some('\'') => {
+                                self.pos += 1;
+                                break;
+                            }
+                            Some(c) => {
+                                text.push(c);
+                                self.pos += 1;
+                            }
+                            None => return Err(self.error("unterminated single quote", quote)),
+                        }
+                    }
+                }
+                '"' => {
+                    let quote = self.offset();
+                    self.pos += 1;
+                    loop {
+                        match self.peek() {
+                            Some('"') => {
+                                self.pos += 1;
+                                break;
+                            }
+                            Some('\\') => {
+                                self.pos += 1;
+                                match self.peek() {
+                                    Some(e @ ('"' | '\\' | '$' | '`')) => {
+                                        text.push(e);
+                                        self.pos += 1;
+                                    }
+                                    Some('\n') => self.pos += 1,
+                                    _ => text.push('\\'),
+                                }
+                            }
+                            Some('$') | Some('`') => {
+                                dynamic |= self.lex_expansion(&mut text)?;
+                            }
+                            Some(c) => {
+                                text.push(c);
+                                self.pos += 1;
+                            }
+                            None => return Err(self.error("unterminated double quote", quote)),
+                        }
+                    }
+                }
+                '$' | '`' => {
+                    dynamic |= self.lex_expansion(&mut text)?;
+                }
+                _ => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        let end = self.offset();
+        Ok(Word {
+            text,
+            raw: self.input[start..end].to_string(),
+            offset: self.base + start,
+            dynamic,
+        })
+    }
+
+    /// Lex `$VAR`, `${...}`, `$(...)`, `$((...))` or a backtick substitution.
+    /// Returns whether the expansion makes the word's value unknown until runtime.
+    fn lex_expansion(&mut self, text: &mut String) -> Result<bool, PolicyViolation> {
+        let start = self.offset();
+        if self.peek() == Some('`') {
+            self.pos += 1;
+            let inner_start = self.offset();
+            loop {
+                match self.peek() {
+                    Some('`') => break,
+                    Some('\\') => self.pos += 2,
+                    Some(_) => self.pos += 1,
+                    None => return Err(self.error("unterminated backtick", start)),
+                }
+            }
+            let inner_end = self.offset();
+            self.pos += 1;
+            self.parse_nested(inner_start, inner_end)?;
+            text.push_str(&self.input[start..self.offset()]);
+            return Ok(true);
+        }
+
+        // Leading `$`
+        self.pos += 1;
+        match self.peek() {
+            Some('(') if self.peek_at(1) == Some('(') => {
+                // Arithmetic expansion: contents are not commands
+                self.pos += 2;
+                self.skip_balanced(start, "unterminated arithmetic expansion")?;
+                if self.peek() == Some(')') {
+                    self.pos += 1;
+                }
+            }
+            Some('(') => {
+                self.pos += 1;
+                let inner_start = self.offset();
+                self.skip_balanced(start, "unterminated command substitution")?;
+                let inner_end = self.offset() - 1;
+                self.parse_nested(inner_start, inner_end)?;
+            }
+            Some('{') => {
+                self.pos += 1;
+                loop {
+                    match self.peek() {
+                        Some('}') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        Some(_) => self.pos += 1,
+                        None => return Err(self.error("unterminated parameter expansion", start)),
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_alphanumeric() || "_@*#?$!-".contains(c) => {
+                self.pos += 1;
+                if c.is_ascii_alphabetic() || c == '_' {
+                    while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_') {
+                        self.pos += 1;
+                    }
+                }
+            }
+            _ => {
+                // A lone `$` is literal
+                text.push('$');
+                return Ok(false);
+            }
+        }
+        text.push_str(&self.input[start..self.offset()]);
+        Ok(true)
+    }
+
+    /// Advance past the `)` matching an already consumed `(`, respecting quotes
+    fn skip_balanced(&mut self, start: usize, message: &str) -> Result<(), PolicyViolation> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.peek() {
+                Some('(') => depth += 1,
+                Some(')') => depth -= 1,
+                Some('\\') => self.pos += 1,
+                Some(q @ ('\'' | '"')) => {
+                    self.pos += 1;
+                    while let Some(c) = self.peek() {
+                        if c == q {
+                            break;
+                        }
+                        if c == '\\' && q == '"' {
+                            self.pos += 1;
+                        }
+                        self.pos += 1;
+                    }
+                }
+                Some(_) => {}
+                None => return Err(self.error(message, start)),
+            }
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    fn parse_nested(&mut self, start: usize, end: usize) -> Result<(), PolicyViolation> {
+        let commands = parse_commands(&self.input[start..end], self.base + start)?;
+        self.nested.extend(commands);
+        Ok(())
+    }
+}
+
+/// Split a command line into the simple commands it would execute, including
+/// those inside pipelines, lists, subshells and command substitutions
+fn parse_commands(input: &str, base: usize) -> Result<Vec<SimpleCommand>, PolicyViolation> {
+    let (tokens, nested) = Lexer::new(input, base).tokenize()?;
+    let mut commands = Vec::new();
+    let mut words = Vec::new();
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => words.push(word),
+            Token::Separator => {
+                if !words.is_empty() {
+                    commands.push(SimpleCommand { words: std::mem::take(&mut words), script: false });
+                }
+            }
+            Token::Redirect => {
+                // The redirection target is a file name, not an argument
+                tokens.next();
+            }
+        }
+    }
+    if !words.is_empty() {
+        commands.push(SimpleCommand { words, script: false });
+    }
+    commands.extend(nested);
+
+    // Follow `sh -c '...'` and `eval ...` into the nested command line
+    let mut expanded = Vec::new();
+    for command in commands {
+        for script in nested_scripts(&command) {
+            // Offsets inside quoted scripts are approximate; anchor them at the script word
+            let inner = parse_commands(&script.text, script.offset);
+            // Expansions are substituted before the nested shell parses the script,
+            // so they can add commands of their own
+            if script.dynamic {
+                expanded.push(SimpleCommand { words: vec![script], script: true });
+                expanded.extend(inner.unwrap_or_default());
+            } else {
+                expanded.extend(inner?);
+            }
+        }
+        expanded.push(command);
+    }
+    Ok(expanded)
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+/// The scripts a simple command hands to a nested shell: the `-c` argument of
+/// `sh -c` / `bash -c`, and the arguments of `eval` joined by spaces
+fn nested_scripts(command: &SimpleCommand) -> Vec<Word> {
+    let mut scripts = Vec::new();
+    for executable in executables(command) {
+        let Some(index) = command.words.iter().position(|w| std::ptr::eq(w, executable)) else {
+            continue;
+        };
+        let args = &command.words[index + 1..];
+        let name = basename(&executable.text);
+        if SHELLS.contains(&name) {
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                if arg.text.starts_with('-') && !arg.text.starts_with("--") && arg.text.contains('c') {
+                    scripts.extend(args.next().cloned());
+                    break;
+                }
+            }
+        } else if name == "eval" && !args.is_empty() {
+            scripts.push(Word {
+                text: args.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" "),
+                raw: args.iter().map(|w| w.raw.as_str()).collect::<Vec<_>>().join(" "),
+                offset: args[0].offset,
+                dynamic: args.iter().any(|w| w.dynamic),
+            });
+        }
+    }
+    scripts
+}
+
+/// The executables a simple command runs: the command word, plus whatever
+/// wrappers such as `env`, `sudo` or `find -exec` hand off to
+fn executables(command: &SimpleCommand) -> Vec<&Word> {
+    let mut words = command.words.as_slice();
+
+    // Skip leading keywords and variable assignments
+    while let Some((word, rest)) = words.split_first() {
+        if PREFIX_KEYWORDS.contains(&word.raw.as_str()) || is_assignment(word) {
+            words = rest;
+        } else {
+            break;
+        }
+    }
+
+    match words.first() {
+        Some(first) if CLOSING_KEYWORDS.contains(&first.raw.as_str()) || first.raw == "for" || first.raw == "case" => {
+            Vec::new()
+        }
+        _ => wrapped_executables(words),
+    }
+}
+
+/// The command word of `words` and the executables it runs with its arguments
+fn wrapped_executables(words: &[Word]) -> Vec<&Word> {
+    let Some((first, args)) = words.split_first() else {
+        return Vec::new();
+    };
+    let mut found = vec![first];
+    let name = basename(&first.text);
+    if name == "find" {
+        for (index, arg) in args.iter().enumerate() {
+            if FIND_ACTIONS.contains(&arg.text.as_str()) {
+                let action = &args[index + 1..];
+                let end = action.iter().position(|w| w.text == ";" || w.text == "+").unwrap_or(action.len());
+                found.extend(wrapped_executables(&action[..end]));
+            }
+        }
+    } else if WRAPPERS.contains(&name) {
+        let start = if name == "timeout" {
+            timeout_command(args)
+        } else {
+            args.iter().position(|w| !w.text.starts_with('-') && !is_assignment(w) && !w.text.chars().all(|c| c.is_ascii_digit()))
+        };
+        if let Some(start) = start {
+            found.extend(wrapped_executables(&args[start..]));
+        }
+    }
+    found
+}
+
+/// Index of the command in the arguments of `timeout [options] duration command`
+fn timeout_command(args: &[Word]) -> Option<usize> {
+    let mut index = 0;
+    while let Some(arg) = args.get(index) {
+        match arg.text.as_str() {
+            "-s" | "-k" | "--signal" | "--kill-after" => index += 2,
+            "--" => {
+                index += 1;
+                break;
+            }
+            option if option.starts_with('-') && option.len() > 1 => index += 1,
+            _ => break,
+        }
+    }
+    // Skip the duration
+    (index + 1 < args.len()).then_some(index + 1)
+}
+
+fn is_assignment(word: &Word) -> bool {
+    match word.raw.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+fn rule_matches(rule: &CommandRule, executable: &Word, command_text: &str) -> Result<bool, PolicyViolation> {
+    if rule.program.is_none() && rule.pattern.is_none() {
+        return Err(invalid_rule(rule, executable, "it needs a `program` or a `pattern`"));
+    }
+    if let Some(program) = &rule.program {
+        let glob = glob::Pattern::new(program).map_err(|e| invalid_rule(rule, executable, &e.to_string()))?;
+        let name = if executable.dynamic { executable.raw.as_str() } else { executable.text.as_str() };
+        let target = if program.contains('/') { name } else { basename(name) };
+        if !glob.matches(target) {
+            return Ok(false);
+        }
+    }
+    if let Some(pattern) = &rule.pattern {
+        let regex = Regex::new(pattern).map_err(|e| invalid_rule(rule, executable, &e.to_string()))?;
+        if !regex.is_match(command_text) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn invalid_rule(rule: &CommandRule, word: &Word, error: &str) -> PolicyViolation {
+    PolicyViolation {
+        rule: rule.to_string(),
+        token: word.raw.clone(),
+        position: word.offset,
+        message: format!("Invalid command rule {}: {}", rule, error),
+    }
+}
+
+fn violation(action: PolicyAction, rule: String, word: &Word) -> PolicyViolation {
+    let verb = if action == PolicyAction::Deny { "blocked" } else { "requires confirmation" };
+    PolicyViolation {
+        message: format!(
+            "Command {} by rule {} at token `{}` (position {})",
+            verb, rule, word.raw, word.offset
+        ),
+        rule,
+        token: word.raw.clone(),
+        position: word.offset,
+    }
+}
+
+/// Evaluate a command line against `rules`. Executables whose name is only known at runtime
+/// (e.g. `$(echo rm)` or `$CC`), and nested scripts with expansions (e.g. `sh -c "$CMD"`),
+/// require confirmation unless a rule matches them explicitly.
+pub fn evaluate(command: &str, rules: &[CommandRule]) -> PolicyDecision {
+    let commands = match parse_commands(command, 0) {
+        Ok(commands) => commands,
+        Err(violation) => return PolicyDecision::Deny(violation),
+    };
+
+    let mut ask = None;
+    for simple in &commands {
+        let command_text = simple.words.iter().map(|w| w.text.as_str()).collect::<Vec<_>>().join(" ");
+        let executables = if simple.script { simple.words.iter().collect() } else { executables(simple) };
+        for executable in executables {
+            let mut matched = None;
+            for rule in rules {
+                match rule_matches(rule, executable, &command_text) {
+                    Ok(true) => {
+                        matched = Some(rule);
+                        break;
+                    }
+                    Ok(false) => {}
+                    Err(violation) => return PolicyDecision::Deny(violation),
+                }
+            }
+            match matched.map(|rule| (rule.action, rule.to_string())) {
+                Some((PolicyAction::Deny, rule)) => {
+                    return PolicyDecision::Deny(violation(PolicyAction::Deny, rule, executable));
+                }
+                Some((PolicyAction::Ask, rule)) => {
+                    ask.get_or_insert_with(|| violation(PolicyAction::Ask, rule, executable));
+                }
+                Some((PolicyAction::Allow, _)) => {}
+                None if executable.dynamic => {
+                    let check = if simple.script { "'dynamic script'" } else { "'dynamic executable'" };
+                    ask.get_or_insert_with(|| violation(PolicyAction::Ask, check.to_string(), executable));
+                }
+                None => {}
+            }
+        }
+    }
+
+    match ask {
+        Some(violation) => PolicyDecision::Ask(violation),
+        None => PolicyDecision::Allow,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(action: PolicyAction, program: &str) -> CommandRule {
+        CommandRule {
+            action,
+            name: None,
+            program: Some(program.to_string()),
+            pattern: None,
+        }
+    }
+
+    fn deny_rm() -> Vec<CommandRule> {
+        vec![CommandRule::blocked("rm")]
+    }
+
+    fn decision(command: &str, rules: &[CommandRule]) -> &'static str {
+        match evaluate(command, rules) {
+            PolicyDecision::Allow => "allow",
+            PolicyDecision::Ask(_) => "ask",
+            PolicyDecision::Deny(_) => "deny",
+        }
+    }
+
+    #[test]
+    fn quoted_arguments_are_not_commands() {
+        assert_eq!(decision("echo 'rm -rf /'", &deny_rm()), "allow");
+        assert_eq!(decision("echo \"rm\" rm", &deny_rm()), "allow");
+        assert_eq!(decision("echo hi > rm", &deny_rm()), "allow");
+        assert_eq!(decision("make # rm -rf /", &deny_rm()), "allow");
+    }
+
+    #[test]
+    fn quote_removal_reveals_the_executable() {
+        assert_eq!(decision("\"r\"m -rf build", &deny_rm()), "deny");
+        assert_eq!(decision("'rm' -rf build", &deny_rm()), "deny");
+        assert_eq!(decision("r\\m -rf build", &deny_rm()), "deny");
+        assert_eq!(decision("/bin/rm -rf build", &deny_rm()), "deny");
+    }
+
+    #[test]
+    fn lists_and_pipelines_are_split() {
+        for command in ["make && rm x", "make; rm x", "make || rm x", "cat f | rm x", "make & rm x", "make\nrm x"] {
+            assert_eq!(decision(command, &deny_rm()), "deny", "{}", command);
+        }
+    }
+
+    #[test]
+    fn subshells_and_substitutions_are_checked() {
+        for command in ["(cd out && rm x)", "echo $(rm x)", "echo \"$(rm x)\"", "echo `rm x`", "echo $(echo $(rm x))"] {
+            assert_eq!(decision(command, &deny_rm()), "deny", "{}", command);
+        }
+        assert_eq!(decision("echo $(date) $((1 + 2))", &deny_rm()), "allow");
+    }
+
+    #[test]
+    fn wrappers_and_nested_shells_are_followed() {
+        for command in ["sudo rm x", "env FOO=1 rm x", "nice -n 10 rm x", "sh -c 'make && rm x'", "bash -ec \"rm x\""] {
+            assert_eq!(decision(command, &deny_rm()), "deny", "{}", command);
+        }
+        assert_eq!(decision("FOO=rm make", &deny_rm()), "allow");
+    }
+
+    #[test]
+    fn eval_is_parsed_as_a_script() {
+        for command in ["eval rm x", "eval \"make && rm x\"", "eval 'echo $(rm x)'", "command eval rm x"] {
+            assert_eq!(decision(command, &deny_rm()), "deny", "{}", command);
+        }
+        assert_eq!(decision("eval echo rm", &deny_rm()), "allow");
+    }
+
+    #[test]
+    fn timeout_skips_its_duration() {
+        for command in ["timeout 10 rm x", "timeout 1.5m rm x", "timeout -s KILL 10s rm x", "timeout --kill-after=5 -v 2h rm x"] {
+            assert_eq!(decision(command, &deny_rm()), "deny", "{}", command);
+        }
+        let deny_duration = [rule(PolicyAction::Deny, "10s")];
+        assert_eq!(decision("timeout 10s make", &deny_duration), "allow");
+    }
+
+    #[test]
+    fn sourced_scripts_are_checked() {
+        for command in [". ./rm", "source scripts/rm", "make && . /tmp/rm"] {
+            assert_eq!(decision(command, &deny_rm()), "deny", "{}", command);
+        }
+        let ask_setup = [rule(PolicyAction::Ask, "setup.sh")];
+        assert_eq!(decision(". ./setup.sh && make", &ask_setup), "ask");
+    }
+
+    #[test]
+    fn find_actions_are_checked() {
+        for command in [
+            "find . -name '*.o' -exec rm {} \\;",
+            "find . -execdir rm {} +",
+            "find . -ok sudo rm {} ';'",
+            "find . -exec echo {} \\; -exec rm {} \\;",
+            "find . -exec sh -c 'rm \"$1\"' _ {} \\;",
+        ] {
+            assert_eq!(decision(command, &deny_rm()), "deny", "{}", command);
+        }
+        assert_eq!(decision("find . -name rm -print", &deny_rm()), "allow");
+        assert_eq!(decision("find . -exec echo rm \\;", &deny_rm()), "allow");
+    }
+
+    #[test]
+    fn dynamic_nested_scripts_ask() {
+        for command in ["sh -c \"$CMD\"", "bash -c \"make $TARGET\"", "eval \"$CMD\"", "sh -c \"$(cat build.sh)\""] {
+            match evaluate(command, &[]) {
+                PolicyDecision::Ask(violation) => assert_eq!(violation.rule, "'dynamic script'", "{}", command),
+                other => panic!("{}: {:?}", command, other),
+            }
+        }
+        // Commands written out in the script are still checked
+        assert_eq!(decision("sh -c \"rm -rf $DIR\"", &deny_rm()), "deny");
+        // Single quotes leave the expansion to the nested shell, where it is only an argument
+        assert_eq!(decision("sh -c 'echo $HOME'", &[]), "allow");
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let allow_first = vec![rule(PolicyAction::Allow, "rm"), rule(PolicyAction::Deny, "rm")];
+        assert_eq!(decision("rm x", &allow_first), "allow");
+        let deny_first = vec![rule(PolicyAction::Deny, "rm"), rule(PolicyAction::Allow, "rm")];
+        assert_eq!(decision("rm x", &deny_first), "deny");
+    }
+
+    #[test]
+    fn deny_outweighs_ask_and_ask_outweighs_allow() {
+        let rules = vec![rule(PolicyAction::Ask, "git"), rule(PolicyAction::Deny, "rm"), rule(PolicyAction::Allow, "make")];
+        assert_eq!(decision("git push; rm x", &rules), "deny");
+        assert_eq!(decision("make && git push", &rules), "ask");
+        assert_eq!(decision("make", &rules), "allow");
+    }
+
+    #[test]
+    fn patterns_match_the_whole_command() {
+        let rules = vec![CommandRule {
+            action: PolicyAction::Ask,
+            name: Some("pushes".to_string()),
+            program: None,
+            pattern: Some("^git push".to_string()),
+        }];
+        assert_eq!(decision("git push origin", &rules), "ask");
+        assert_eq!(decision("git status", &rules), "allow");
+    }
+
+    #[test]
+    fn dynamic_executables_default_to_ask() {
+        for command in ["$CC main.c", "${CC} main.c", "$(echo rm) x", "`which make`"] {
+            match evaluate(command, &[]) {
+                PolicyDecision::Ask(violation) => assert_eq!(violation.rule, "'dynamic executable'"),
+                other => panic!("{}: {:?}", command, other),
+            }
+        }
+        // A rule naming the expansion as written decides instead
+        assert_eq!(decision("$CC main.c", &[rule(PolicyAction::Allow, "$CC")]), "allow");
+        // Dynamic arguments are fine
+        assert_eq!(decision("gcc $CFLAGS main.c", &[]), "allow");
+    }
+
+    #[test]
+    fn violations_point_at_the_token() {
+        let PolicyDecision::Deny(violation) = evaluate("make && \"rm\" -rf x", &deny_rm()) else {
+            panic!("expected deny");
+        };
+        assert_eq!(violation.token, "\"rm\"");
+        assert_eq!(violation.position, 8);
+        assert_eq!(violation.rule, "'blocked_commands: rm'");
+    }
+
+    #[test]
+    fn unparsable_commands_are_denied() {
+        for command in ["echo 'open", "echo \"open", "echo $(open", "echo `open", "echo ${open"] {
+            match evaluate(command, &[]) {
+                PolicyDecision::Deny(violation) => assert_eq!(violation.rule, "shell syntax", "{}", command),
+                other => panic!("{}: {:?}", command, other),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_rules_deny() {
+        let rules = vec![CommandRule {
+            action: PolicyAction::Allow,
+            name: None,
+            program: None,
+            pattern: Some("(".to_string()),
+        }];
+        assert_eq!(decision("make", &rules), "deny");
+    }
+
+    #[test]
+    fn rules_without_a_pattern_or_unknown_fields_are_rejected() {
+        assert!(serde_json::from_str::<CommandRule>(r#"{"action": "deny", "program": "rm"}"#).is_ok());
+        let empty = serde_json::from_str::<CommandRule>(r#"{"action": "deny", "name": "everything"}"#).unwrap_err();
+        assert!(empty.to_string().contains("needs a `program` or a `pattern`"), "{}", empty);
+        let misspelled = serde_json::from_str::<CommandRule>(r#"{"action": "deny", "programm": "rm"}"#).unwrap_err();
+        assert!(misspelled.to_string().contains("unknown field"), "{}", misspelled);
+    }
+}
@@ -0,0 +1,259 @@
+//! Toolchain discovery for Build Studio
+//! Finds the compilers, SDKs and packaging tools installed on this host, and tells whether a
+//! project's builds can run here
+
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use crate::config::{BuildConfig, BuildStudioConfig};
+use crate::fsutils;
+use crate::language;
+use crate::local_nodes;
+use crate::toolchain;
+
+/// Tools to look for: name, kind and the arguments that print its version (none to skip)
+const TOOLS: &[(&str, &str, &[&str])] = &[
+    ("gcc", "compiler", &["--version"]),
+    ("clang", "compiler", &["--version"]),
+    ("rustc", "compiler", &["--version"]),
+    ("cargo", "compiler", &["--version"]),
+    ("go", "compiler", &["version"]),
+    ("node", "compiler", &["--version"]),
+    ("javac", "compiler", &["-version"]),
+    ("x86_64-w64-mingw32-gcc", "compiler", &["--version"]),
+    ("i686-w64-mingw32-gcc", "compiler", &["--version"]),
+    ("emcc", "compiler", &["--version"]),
+    ("wasm-pack", "compiler", &["--version"]),
+    ("zig", "compiler", &["version"]),
+    ("cross", "compiler", &["--version"]),
+    // Used by packaging.rs
+    ("dpkg-deb", "packaging", &["--version"]),
+    ("rpmbuild", "packaging", &["--version"]),
+    ("candle", "packaging", &[]),
+    ("hdiutil", "packaging", &[]),
+    ("pkgbuild", "packaging", &[]),
+    ("aapt", "packaging", &["version"]),
+];
+
+/// Commands that are part of the shell rather than programs on the `PATH`
+const SHELL_BUILTINS: &[&str] = &[
+    ".", ":", "[", "cd", "echo", "env", "exec", "export", "false", "for", "if", "set", "test", "true", "while",
+];
+
+/// A compiler, SDK or packaging tool and what was found of it
+#[derive(Serialize, Clone, Debug)]
+pub struct ToolInfo {
+    pub name: String,
+    /// "compiler", "sdk" or "packaging"
+    pub kind: String,
+    /// Where it is installed; `None` if it was not found
+    pub path: Option<String>,
+    pub version: Option<String>,
+    /// Installed Rust targets for rustup, API levels for the Android NDK
+    pub details: Vec<String>,
+}
+
+impl ToolInfo {
+    fn missing(name: &str, kind: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            path: None,
+            version: None,
+            details: Vec::new(),
+        }
+    }
+}
+
+/// Whether one of a project's builds can run on this host
+#[derive(Serialize, Clone, Debug)]
+pub struct BuildReadiness {
+    pub name: String,
+    pub runnable: bool,
+    /// Why it cannot run
+    pub problems: Vec<String>,
+}
+
+/// Arguments that print a tool's version: those from `TOOLS`, `--version` for anything else
+pub(crate) fn version_args(name: &str) -> &'static [&'static str] {
+    match TOOLS.iter().find(|(tool, _, _)| *tool == name) {
+        Some((_, _, args)) => args,
+        None if name == "java" => &["-version"],
+        None => &["--version"],
+    }
+}
+
+/// Whether `name` is one of the tools `probe` looks for
+pub(crate) fn is_known_tool(name: &str) -> bool {
+    TOOLS.iter().any(|(tool, _, _)| *tool == name)
+}
+
+/// First non-empty line a tool prints for `args`, on stdout or (as `javac -version` does) stderr
+pub(crate) fn version_line(path: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new(path)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    [&output.stdout, &output.stderr].iter().find_map(|text| {
+        String::from_utf8_lossy(text)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(str::to_string)
+    })
+}
+
+fn probe_tool(name: &str, kind: &str, version_args: &[&str]) -> ToolInfo {
+    let Some(path) = fsutils::find_executable(name) else {
+        return ToolInfo::missing(name, kind);
+    };
+    ToolInfo {
+        name: name.to_string(),
+        kind: kind.to_string(),
+        version: if version_args.is_empty() { None } else { version_line(&path, version_args) },
+        path: Some(path.to_string_lossy().to_string()),
+        details: Vec::new(),
+    }
+}
+
+/// rustup and the Rust targets it has installed
+fn probe_rustup() -> ToolInfo {
+    let mut info = probe_tool("rustup", "sdk", &["--version"]);
+    if let Some(path) = &info.path {
+        if let Ok(output) = Command::new(path).args(["target", "list", "--installed"]).stdin(Stdio::null()).output() {
+            info.details = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+    }
+    info
+}
+
+/// The NDK at `$ANDROID_NDK_HOME`, its revision and the API levels its compilers target
+fn probe_android_ndk() -> ToolInfo {
+    let mut info = ToolInfo::missing("android-ndk", "sdk");
+    let Some(ndk) = std::env::var_os("ANDROID_NDK_HOME").map(PathBuf::from).filter(|p| p.is_dir()) else {
+        return info;
+    };
+    info.version = fs::read_to_string(ndk.join("source.properties")).ok().and_then(|props| {
+        props
+            .lines()
+            .find_map(|line| line.strip_prefix("Pkg.Revision"))
+            .map(|rest| rest.trim_start_matches([' ', '=']).trim().to_string())
+    });
+
+    // Compilers are named `<triple><api>-clang`, e.g. `aarch64-linux-android24-clang`
+    let bin = ndk.join("toolchains/llvm/prebuilt").join(toolchain::ndk_host_tag()).join("bin");
+    let mut levels: Vec<u32> = fs::read_dir(&bin)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| {
+                    let name = e.file_name().to_string_lossy().to_string();
+                    let api = name.strip_prefix("aarch64-linux-android")?.strip_suffix("-clang")?;
+                    api.parse().ok()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    levels.sort_unstable();
+    info.details = levels.iter().map(|api| format!("android-{}", api)).collect();
+    info.path = Some(ndk.to_string_lossy().to_string());
+    info
+}
+
+/// Look for every known compiler, SDK and packaging tool. Tools are probed in parallel since
+/// some, such as `emcc`, take a while to report their version.
+pub fn probe() -> Vec<ToolInfo> {
+    thread::scope(|scope| {
+        let mut probes: Vec<_> = TOOLS
+            .iter()
+            .map(|&(name, kind, args)| scope.spawn(move || probe_tool(name, kind, args)))
+            .collect();
+        probes.push(scope.spawn(probe_rustup));
+        probes.push(scope.spawn(probe_android_ndk));
+        probes.into_iter().filter_map(|probe| probe.join().ok()).collect()
+    })
+}
+
+/// Program a shell command starts with, if it is looked up on the `PATH`
+pub(crate) fn command_program(command: &str) -> Option<&str> {
+    let program = command.split_whitespace().next()?;
+    let looked_up = !program.contains(['/', '\\', '=', '$']) && !SHELL_BUILTINS.contains(&program);
+    looked_up.then_some(program)
+}
+
+/// Check that the tools `build` needs are installed, given what `probe` found
+pub fn check_build(config: &BuildStudioConfig, build: &BuildConfig, tools: &[ToolInfo]) -> BuildReadiness {
+    let mut problems = Vec::new();
+    if let Some(image) = &build.container {
+        // The image brings its own tools
+        if local_nodes::container_engine().is_none() {
+            problems.push(format!("Container image {} needs Docker or Podman", image));
+        }
+    } else {
+        let adapter = build.language.as_deref().and_then(language::adapter);
+        if let Err(e) = adapter.map(|a| a.check_tools()).unwrap_or(Ok(())) {
+            problems.push(e);
+        }
+        let profile = toolchain::resolve_profile(
+            Some(&build.platform),
+            build.target.as_deref(),
+            build.toolchain.as_deref(),
+            &config.toolchains,
+        );
+        match profile {
+            Ok(profile) => {
+                let mut dirs: Vec<PathBuf> = profile.path.iter().map(PathBuf::from).collect();
+                dirs.extend(std::env::var_os("PATH").map(|p| std::env::split_paths(&p).collect::<Vec<_>>()).unwrap_or_default());
+                if profile.name == "android-ndk" && std::env::var_os("ANDROID_NDK_HOME").is_none() {
+                    problems.push("ANDROID_NDK_HOME is not set".to_string());
+                }
+                if profile.name == "cross" && local_nodes::container_engine().is_none() {
+                    problems.push("The cross toolchain needs Docker or Podman".to_string());
+                }
+                let needed = [&profile.cc, &profile.ld].into_iter().flatten().chain(&profile.tools);
+                for tool in needed {
+                    let program = command_program(tool).unwrap_or(tool);
+                    if fsutils::find_executable_in(program, &dirs).is_none() {
+                        problems.push(format!("The {} toolchain needs {}, which is not installed", profile.name, program));
+                    }
+                }
+                // Cargo builds for the target need its standard library; cross brings its own
+                let rustup = tools.iter().find(|t| t.name == "rustup" && t.path.is_some() && profile.name != "cross");
+                if let (Some(rustup), Some(target), Some("rust")) = (rustup, &profile.target, adapter.map(|a| a.name)) {
+                    if !rustup.details.contains(target) {
+                        problems.push(format!("Rust target {} is not installed (rustup target add {})", target, target));
+                    }
+                }
+            }
+            Err(e) => problems.push(e),
+        }
+        // Tools of the language adapter were checked above
+        if let Some(program) = command_program(&build.command) {
+            let checked = adapter.is_some_and(|a| a.tools.contains(&program));
+            if !checked && fsutils::find_executable(program).is_none() {
+                problems.push(format!("{} is not installed or not on the PATH", program));
+            }
+        }
+    }
+    BuildReadiness {
+        name: build.name.clone(),
+        runnable: problems.is_empty(),
+        problems,
+    }
+}
+
+/// Compilers, SDKs and packaging tools found on this host
+#[tauri::command]
+pub async fn get_toolchains() -> Result<Vec<ToolInfo>, String> {
+    tokio::task::spawn_blocking(probe)
+        .await
+        .map_err(|e| format!("Failed to probe toolchains: {}", e))
+}
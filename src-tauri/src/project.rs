@@ -1,8 +1,9 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 use tauri::State;
 use std::sync::Mutex;
+use crate::config;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Project {
@@ -40,7 +41,7 @@ impl ProjectManager {
 
     pub fn add_project(&mut self, name: String, path: PathBuf) -> bool {
         // Previously required a buildstudio.config.yaml file. Now we allow any folder.
-        let config_path = path.join("buildstudio.config.yaml");
+        let config_path = config::find_config(&path).unwrap_or_else(|| path.join("buildstudio.config.yaml"));
         let project = Project {
             name,
             path: path.clone(),
@@ -53,6 +54,28 @@ impl ProjectManager {
         true
     }
 
+    /// The project whose directory is `path`
+    pub fn find_by_path(&self, path: &Path) -> Option<&Project> {
+        let canonical = path.canonicalize().ok();
+        self.projects.iter().find(|p| {
+            p.path == path || (canonical.is_some() && p.path.canonicalize().ok() == canonical)
+        })
+    }
+
+    /// Update `last_build` and `last_status` of the project at `path` after a build
+    pub fn record_build(&mut self, path: &Path, finished_at: &str, status: &str) -> bool {
+        let name = match self.find_by_path(path) {
+            Some(project) => project.name.clone(),
+            None => return false,
+        };
+        if let Some(project) = self.projects.iter_mut().find(|p| p.name == name) {
+            project.last_build = Some(finished_at.to_string());
+            project.last_status = Some(status.to_string());
+        }
+        self.save_projects();
+        true
+    }
+
     pub fn remove_project(&mut self, name: &str) -> bool {
         let len = self.projects.len();
         self.projects.retain(|p| p.name != name);
@@ -0,0 +1,222 @@
+//! Tool requirements for Build Studio plugins
+//! A requirement such as `go>=1.18`, `node ^18`, `cmake >=3.20, <4` or `python3 3.8 - 3.12`
+//! names a tool and the versions it may have. Tools are found on the `PATH` and asked for their
+//! version the way `probe` asks; versions are compared as semver, with missing parts taken as 0.
+
+use regex::Regex;
+use semver::{Version, VersionReq};
+use serde::Serialize;
+use std::fmt;
+use std::sync::OnceLock;
+use std::thread;
+use crate::fsutils;
+use crate::probe;
+
+/// A tool and the versions of it that will do
+#[derive(Clone, Debug)]
+pub struct Requirement {
+    pub tool: String,
+    /// `None` when any version will do
+    pub versions: Option<VersionReq>,
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.versions {
+            Some(versions) => write!(f, "{} {}", self.tool, versions),
+            None => write!(f, "{}", self.tool),
+        }
+    }
+}
+
+/// Characters that start a version constraint
+const OPERATORS: [char; 5] = ['<', '>', '=', '~', '^'];
+
+/// Parse a requirement: a tool name, then optionally a constraint. Constraints are semver
+/// comparators (`>=1.18`, `<2`, `~1.2`, `^18`, `=1.2.3`) separated by commas, or a range
+/// `1.2 - 1.4` that includes both ends.
+pub fn parse(requirement: &str) -> Result<Requirement, String> {
+    let requirement = requirement.trim();
+    let end = requirement
+        .find(|c: char| c.is_whitespace() || OPERATORS.contains(&c))
+        .unwrap_or(requirement.len());
+    let (tool, constraint) = requirement.split_at(end);
+    if tool.is_empty() {
+        return Err(format!("Requirement '{}' does not name a tool", requirement));
+    }
+    let constraint = constraint.trim();
+    if constraint.is_empty() {
+        return Ok(Requirement { tool: tool.to_string(), versions: None });
+    }
+    let constraint = match constraint.split_once(" - ") {
+        Some((low, high)) => format!(">={}, <={}", low.trim(), high.trim()),
+        None => constraint.to_string(),
+    };
+    let versions = VersionReq::parse(&constraint)
+        .map_err(|e| format!("Invalid version constraint in requirement '{}': {}", requirement, e))?;
+    Ok(Requirement { tool: tool.to_string(), versions: Some(versions) })
+}
+
+/// The version in a tool's `--version` line, such as `1.21.3` in `go version go1.21.3 linux/amd64`.
+/// Dotted numbers are preferred over lone ones; pre-release and build suffixes are dropped.
+pub fn extract_version(line: &str) -> Option<Version> {
+    static DOTTED: OnceLock<Regex> = OnceLock::new();
+    static NUMBER: OnceLock<Regex> = OnceLock::new();
+    let dotted = DOTTED.get_or_init(|| Regex::new(r"\d+(?:\.\d+){1,2}").unwrap());
+    let number = NUMBER.get_or_init(|| Regex::new(r"\d+").unwrap());
+    let found = dotted.find(line).or_else(|| number.find(line))?.as_str();
+    let mut parts = found.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some(Version::new(major, minor, patch))
+}
+
+/// Whether a requirement is met on this host
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DependencyStatus {
+    Ready,
+    MissingTool,
+    VersionMismatch,
+    /// The requirement itself cannot be parsed; the plugin's manifest needs fixing
+    InvalidRequirement,
+}
+
+/// What was found for one requirement
+#[derive(Serialize, Clone, Debug)]
+pub struct DependencyCheck {
+    /// The requirement as written
+    pub requirement: String,
+    pub tool: String,
+    pub status: DependencyStatus,
+    /// Version the tool reported, if any
+    pub found: Option<String>,
+    /// Why the requirement is not met
+    pub message: Option<String>,
+}
+
+/// Check one requirement against the tools on the `PATH`
+pub fn check(requirement: &str) -> DependencyCheck {
+    let parsed = match parse(requirement) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return DependencyCheck {
+                requirement: requirement.to_string(),
+                tool: String::new(),
+                status: DependencyStatus::InvalidRequirement,
+                found: None,
+                message: Some(e),
+            }
+        }
+    };
+    let mut result = DependencyCheck {
+        requirement: requirement.to_string(),
+        tool: parsed.tool.clone(),
+        status: DependencyStatus::Ready,
+        found: None,
+        message: None,
+    };
+    let Some(path) = fsutils::find_executable(&parsed.tool) else {
+        result.status = DependencyStatus::MissingTool;
+        result.message = Some(format!("needs {}, which is not installed or not on the PATH", parsed));
+        return result;
+    };
+    let Some(versions) = &parsed.versions else {
+        return result;
+    };
+    let line = probe::version_line(&path, probe::version_args(&parsed.tool));
+    let version = line.as_deref().and_then(extract_version);
+    result.found = version.as_ref().map(Version::to_string);
+    match version {
+        Some(version) if versions.matches(&version) => {}
+        Some(version) => {
+            result.status = DependencyStatus::VersionMismatch;
+            result.message = Some(format!("needs {}, but {} {} is installed", parsed, parsed.tool, version));
+        }
+        None => {
+            result.status = DependencyStatus::VersionMismatch;
+            result.message = Some(format!(
+                "needs {}, but the version of {} could not be determined from: {}",
+                parsed,
+                parsed.tool,
+                line.as_deref().unwrap_or("(no output)")
+            ));
+        }
+    }
+    result
+}
+
+/// Check every requirement; tools are asked for their versions in parallel
+pub fn check_all(requirements: &[String]) -> Vec<DependencyCheck> {
+    thread::scope(|scope| {
+        let checks: Vec<_> = requirements.iter().map(|r| scope.spawn(move || check(r))).collect();
+        checks.into_iter().filter_map(|check| check.join().ok()).collect()
+    })
+}
+
+/// The overall status of a set of checks: an invalid requirement outweighs a missing tool, which
+/// outweighs a version mismatch
+pub fn overall_status(checks: &[DependencyCheck]) -> DependencyStatus {
+    let has = |status| checks.iter().any(|c| c.status == status);
+    if has(DependencyStatus::InvalidRequirement) {
+        DependencyStatus::InvalidRequirement
+    } else if has(DependencyStatus::MissingTool) {
+        DependencyStatus::MissingTool
+    } else if has(DependencyStatus::VersionMismatch) {
+        DependencyStatus::VersionMismatch
+    } else {
+        DependencyStatus::Ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(requirement: &str) -> Option<String> {
+        parse(requirement).unwrap().versions.map(|v| v.to_string())
+    }
+
+    #[test]
+    fn parses_tools_and_constraints() {
+        let go = parse("go>=1.18").unwrap();
+        assert_eq!(go.tool, "go");
+        assert_eq!(go.versions.unwrap().to_string(), ">=1.18");
+        assert_eq!(versions("node ^18"), Some("^18".to_string()));
+        assert_eq!(versions("node 18"), Some("^18".to_string()));
+        assert_eq!(versions("cmake >=3.20, <4"), Some(">=3.20, <4".to_string()));
+        assert_eq!(versions("  make  "), None);
+        assert_eq!(parse("make").unwrap().tool, "make");
+    }
+
+    #[test]
+    fn ranges_include_both_ends() {
+        let python = parse("python3 3.8 - 3.12").unwrap().versions.unwrap();
+        assert!(python.matches(&Version::new(3, 8, 0)));
+        assert!(python.matches(&Version::new(3, 12, 0)));
+        assert!(!python.matches(&Version::new(3, 13, 0)));
+        assert!(!python.matches(&Version::new(3, 7, 9)));
+    }
+
+    #[test]
+    fn rejects_invalid_requirements() {
+        assert!(parse(">=1.2").unwrap_err().contains("does not name a tool"));
+        assert!(parse("go >=one").unwrap_err().starts_with("Invalid version constraint in requirement 'go >=one'"));
+        let check = check("go >=one");
+        assert_eq!(check.status, DependencyStatus::InvalidRequirement);
+        assert_eq!(overall_status(&[check]), DependencyStatus::InvalidRequirement);
+    }
+
+    #[test]
+    fn extracts_versions_from_version_lines() {
+        let version = |line| extract_version(line).map(|v| v.to_string());
+        assert_eq!(version("go version go1.21.3 linux/amd64"), Some("1.21.3".to_string()));
+        assert_eq!(version("v18.19.0"), Some("18.19.0".to_string()));
+        assert_eq!(version("Python 3.12"), Some("3.12.0".to_string()));
+        assert_eq!(version("cmake version 3.28.1-rc2"), Some("3.28.1".to_string()));
+        assert_eq!(version("javac 21"), Some("21.0.0".to_string()));
+        assert_eq!(version("mvn 2 then 3.9.6"), Some("3.9.6".to_string()));
+        assert_eq!(version("no version here"), None);
+    }
+}
@@ -0,0 +1,509 @@
+//! JSON-RPC plugin host for Build Studio
+//! A plugin with `protocol: jsonrpc` has a `server` script that Build Studio starts once and keeps
+//! running. The two exchange JSON-RPC 2.0 messages over the server's stdin and stdout, one message
+//! per line. Build Studio sends the requests `initialize`, `provides`, `build`, `package` and
+//! `cancel`; while it works on a request the server sends `log`, `progress` and `diagnostic`
+//! notifications, which are written to the build output. Whatever it writes to stderr is passed
+//! through to the output of the requests in flight.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::build::{self, BuildOutcome, OutputSink, OutputStream};
+use crate::plugin::{self, Plugin, PluginRun};
+use crate::security::{self, SecurityConfig};
+
+/// Version of the protocol sent with `initialize`
+pub const PROTOCOL_VERSION: u32 = 1;
+/// How long `initialize` and `provides` may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a cancelled request may take to finish before the server is killed
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+
+/// JSON-RPC error code for requests the host does not handle
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Request a command of a `protocol: jsonrpc` plugin is sent as
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RpcMethod {
+    #[default]
+    Build,
+    Package,
+}
+
+impl RpcMethod {
+    fn name(self) -> &'static str {
+        match self {
+            RpcMethod::Build => "build",
+            RpcMethod::Package => "package",
+        }
+    }
+}
+
+/// What a plugin contributes, as it answers `provides`
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Provides {
+    #[serde(default)]
+    pub toolchains: Vec<String>,
+    #[serde(default)]
+    pub packagers: Vec<String>,
+    #[serde(default)]
+    pub hooks: Vec<String>,
+}
+
+/// Result of a `build` or `package` request
+#[derive(Deserialize, Default)]
+struct TaskResult {
+    /// Files to keep as artifacts, relative to the project directory
+    #[serde(default)]
+    artifacts: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// Any message the server sends: a response, a notification or a request of its own
+#[derive(Deserialize)]
+struct Message {
+    #[serde(default)]
+    id: Option<Value>,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+type Reply = Result<Value, String>;
+
+/// A running plugin server
+struct Server {
+    plugin: String,
+    /// Launcher and argv it was started with, which key it among the running servers
+    key: Vec<OsString>,
+    /// Taken once the server is stopped and reaped
+    child: Mutex<Option<Child>>,
+    stdin: Mutex<ChildStdin>,
+    next_id: AtomicU64,
+    /// Requests waiting for their response, by id
+    pending: Mutex<HashMap<u64, mpsc::Sender<Reply>>>,
+    /// Output of the requests in flight, by id
+    sinks: Mutex<HashMap<u64, OutputSink>>,
+    exited: AtomicBool,
+}
+
+/// Servers started so far, by launcher and argv
+fn servers() -> &'static Mutex<HashMap<Vec<OsString>, Arc<Server>>> {
+    static SERVERS: OnceLock<Mutex<HashMap<Vec<OsString>, Arc<Server>>>> = OnceLock::new();
+    SERVERS.get_or_init(Default::default)
+}
+
+impl Server {
+    /// The running server for `argv` behind `launcher`, started and initialized if there is none
+    fn get(plugin: &str, plugin_dir: &Path, argv: Vec<OsString>, launcher: &[String], sink: Option<&OutputSink>) -> Result<Arc<Self>, String> {
+        let key: Vec<OsString> = launcher.iter().map(OsString::from).chain(argv.iter().cloned()).collect();
+        if let Some(server) = Self::running(&key) {
+            return Ok(server);
+        }
+        // Starting a server can take up to REQUEST_TIMEOUT, so other plugins' servers stay
+        // usable meanwhile
+        let server = Self::start(plugin, plugin_dir, argv, launcher, key.clone())?;
+        let init = json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "buildStudioVersion": env!("CARGO_PKG_VERSION"),
+            "pluginDir": plugin_dir,
+        });
+        if let Err(e) = server.call("initialize", init, sink) {
+            server.stop();
+            return Err(format!("Plugin '{}' failed to initialize: {}", plugin, e));
+        }
+        let mut running = servers().lock().unwrap();
+        // Another request may have started the same server in the meantime; keep only one
+        if let Some(existing) = running.get(&key).filter(|s| !s.exited.load(Ordering::SeqCst)).cloned() {
+            drop(running);
+            server.stop();
+            return Ok(existing);
+        }
+        running.insert(key, server.clone());
+        Ok(server)
+    }
+
+    /// The server for `key` if it is still running; one that has exited is forgotten
+    fn running(key: &[OsString]) -> Option<Arc<Self>> {
+        let mut running = servers().lock().unwrap();
+        let server = running.get(key)?.clone();
+        if server.exited.load(Ordering::SeqCst) {
+            running.remove(key);
+            return None;
+        }
+        Some(server)
+    }
+
+    fn start(plugin: &str, plugin_dir: &Path, argv: Vec<OsString>, launcher: &[String], key: Vec<OsString>) -> Result<Arc<Self>, String> {
+        let mut cmd = build::launch_command(argv, launcher);
+        cmd.current_dir(plugin_dir)
+            .env("BUILD_STUDIO_VERSION", env!("CARGO_PKG_VERSION"))
+            .env("PLUGIN_DIR", plugin_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut child = cmd.spawn().map_err(|e| format!("Failed to start plugin '{}': {}", plugin, e))?;
+        let stdin = child.stdin.take().ok_or("Failed to open plugin stdin")?;
+        let stdout = child.stdout.take().ok_or("Failed to capture plugin stdout")?;
+        let stderr = child.stderr.take().ok_or("Failed to capture plugin stderr")?;
+        let server = Arc::new(Self {
+            plugin: plugin.to_string(),
+            key,
+            child: Mutex::new(Some(child)),
+            stdin: Mutex::new(stdin),
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            sinks: Mutex::new(HashMap::new()),
+            exited: AtomicBool::new(false),
+        });
+        let reader = server.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                reader.handle(&line);
+            }
+            reader.closed();
+        });
+        let reader = server.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                reader.output(None, OutputStream::Stderr, line);
+            }
+        });
+        Ok(server)
+    }
+
+    fn send(&self, message: Value) -> Result<(), String> {
+        let mut stdin = self.stdin.lock().unwrap();
+        writeln!(stdin, "{}", message)
+            .and_then(|_| stdin.flush())
+            .map_err(|e| format!("Failed to write to plugin '{}': {}", self.plugin, e))
+    }
+
+    /// Send a request; its notifications go to `sink` and its response to the returned receiver
+    fn request(&self, method: &str, params: Value, sink: Option<&OutputSink>) -> Result<(u64, mpsc::Receiver<Reply>), String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::channel();
+        self.pending.lock().unwrap().insert(id, sender);
+        if let Some(sink) = sink {
+            self.sinks.lock().unwrap().insert(id, sink.clone());
+        }
+        let sent = self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        if self.exited.load(Ordering::SeqCst) || sent.is_err() {
+            self.finish(id);
+            sent?;
+            return Err(format!("Plugin '{}' has exited", self.plugin));
+        }
+        Ok((id, receiver))
+    }
+
+    /// Send a request and wait for its result
+    fn call(&self, method: &str, params: Value, sink: Option<&OutputSink>) -> Reply {
+        let (id, receiver) = self.request(method, params, sink)?;
+        let reply = receiver
+            .recv_timeout(REQUEST_TIMEOUT)
+            .unwrap_or_else(|_| Err(format!("Plugin '{}' did not answer {} in time", self.plugin, method)));
+        self.finish(id);
+        reply
+    }
+
+    /// Forget a request
+    fn finish(&self, id: u64) {
+        self.pending.lock().unwrap().remove(&id);
+        self.sinks.lock().unwrap().remove(&id);
+    }
+
+    /// Write a line to the output of request `id`, or of every request in flight if it names none
+    fn output(&self, id: Option<u64>, stream: OutputStream, line: String) {
+        let sinks = self.sinks.lock().unwrap();
+        match id.and_then(|id| sinks.get(&id)) {
+            Some(sink) => sink(stream, line),
+            None => sinks.values().for_each(|sink| sink(stream, line.clone())),
+        }
+    }
+
+    /// Act on one line the server wrote to stdout
+    fn handle(&self, line: &str) {
+        if line.trim().is_empty() {
+            return;
+        }
+        let message: Message = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(_) => {
+                // Not JSON-RPC; stray prints still reach the build output
+                self.output(None, OutputStream::Stdout, line.to_string());
+                return;
+            }
+        };
+        match (message.method, message.id) {
+            (Some(method), Some(id)) => {
+                let error = json!({ "code": METHOD_NOT_FOUND, "message": format!("Build Studio does not handle {}", method) });
+                let _ = self.send(json!({ "jsonrpc": "2.0", "id": id, "error": error }));
+            }
+            (Some(method), None) => self.notify(&method, &message.params),
+            (None, Some(id)) => {
+                let reply = match message.error {
+                    Some(error) => Err(format!("{} (code {})", error.message, error.code)),
+                    None => Ok(message.result.unwrap_or(Value::Null)),
+                };
+                if let Some(sender) = id.as_u64().and_then(|id| self.pending.lock().unwrap().remove(&id)) {
+                    let _ = sender.send(reply);
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// Write a `log`, `progress` or `diagnostic` notification to the build output
+    fn notify(&self, method: &str, params: &Value) {
+        let id = params.get("id").and_then(Value::as_u64);
+        let text = |key: &str| params.get(key).and_then(Value::as_str).unwrap_or_default().to_string();
+        match method {
+            "log" => {
+                let stream = match text("level").as_str() {
+                    "warning" | "error" => OutputStream::Stderr,
+                    _ => OutputStream::Stdout,
+                };
+                for line in text("message").lines() {
+                    self.output(id, stream, line.to_string());
+                }
+            }
+            "progress" => {
+                let line = match params.get("percent").and_then(Value::as_f64) {
+                    Some(percent) => format!("[{:>3.0}%] {}", percent, text("message")),
+                    None => text("message"),
+                };
+                self.output(id, OutputStream::Stdout, line.trim_end().to_string());
+            }
+            "diagnostic" => {
+                // file:line:column: severity: message, as compilers print them
+                let mut location: Vec<String> = vec![text("file")];
+                location.extend(["line", "column"].iter().filter_map(|key| params.get(*key).and_then(Value::as_u64)).map(|n| n.to_string()));
+                let severity = match text("severity") {
+                    severity if severity.is_empty() => "error".to_string(),
+                    severity => severity,
+                };
+                let prefix = if location[0].is_empty() { String::new() } else { format!("{}: ", location.join(":")) };
+                self.output(id, OutputStream::Stderr, format!("{}{}: {}", prefix, severity, text("message")));
+            }
+            _ => {}
+        }
+    }
+
+    /// The server closed its stdout: fail everything still waiting on it, and reap the process
+    /// so that it does not linger as a zombie
+    fn closed(&self) {
+        self.exited.store(true, Ordering::SeqCst);
+        for (_, sender) in self.pending.lock().unwrap().drain() {
+            let _ = sender.send(Err("the plugin exited".to_string()));
+        }
+        // A server without its stdout cannot answer anything, so one still running is killed
+        self.stop();
+    }
+
+    /// Kill the server and its children and wait for it; only the first call does anything,
+    /// since the pid may belong to another process once the server has been reaped
+    fn stop(&self) {
+        if let Some(mut child) = self.child.lock().unwrap().take() {
+            build::kill_process_tree(&mut child);
+        }
+    }
+
+    /// Stop the server and forget it, so the next request starts a new one
+    fn kill(&self) {
+        self.stop();
+        let mut running = servers().lock().unwrap();
+        if running.get(&self.key).is_some_and(|s| std::ptr::eq(Arc::as_ptr(s), self)) {
+            running.remove(&self.key);
+        }
+    }
+}
+
+/// Stop every running server, when the app or the CLI exits
+pub fn stop_all() {
+    let running: Vec<Arc<Server>> = servers().lock().unwrap().drain().map(|(_, server)| server).collect();
+    for server in running {
+        server.stop();
+    }
+}
+
+/// Program and arguments that start the server `script`
+fn server_argv(script: &Path) -> Vec<OsString> {
+    let mut argv: Vec<OsString> = plugin::interpreter(script).into_iter().map(OsString::from).collect();
+    argv.push(script.into());
+    argv
+}
+
+/// A path the server reported as an artifact, relative to the project in `work_dir`
+fn artifact_path(path: &str, work_dir: &Path) -> Option<String> {
+    let path = PathBuf::from(path);
+    let relative = if path.is_absolute() { path.strip_prefix(work_dir).ok()?.to_path_buf() } else { path };
+    relative
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir))
+        .then(|| relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// Ask a `protocol: jsonrpc` plugin what it contributes. Its server is checked against the
+/// security policy and started behind the same network isolation as the plugin's builds.
+pub fn provides(plugin: &Plugin, security_config: &SecurityConfig) -> Result<Provides, String> {
+    let (plugin_dir, script) = plugin.server()?;
+    let argv = server_argv(&script);
+    let command_line = argv.iter().map(|word| plugin::shell_word(&word.to_string_lossy())).collect::<Vec<_>>().join(" ");
+    build::check_policy(&command_line, security_config, false)?;
+    let launcher = security::network_isolation_prefix(security_config)?;
+    let server = Server::get(&plugin.name, &plugin_dir, argv, &launcher, None)?;
+    let result = server
+        .call("provides", json!({}), None)
+        .map_err(|e| format!("Plugin '{}' failed to answer provides: {}", plugin.name, e))?;
+    serde_json::from_value(result).map_err(|e| format!("Plugin '{}' answered provides with an invalid result: {}", plugin.name, e))
+}
+
+/// Send a command of a `protocol: jsonrpc` plugin to its server, started behind `launcher` if it
+/// is not running yet, and stream its notifications to `sink` until it answers, times out or
+/// `cancel` is set. Returns how it ended and the artifacts it reported.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    run: &PluginRun,
+    method: RpcMethod,
+    work_dir: &Path,
+    env: Vec<(String, String)>,
+    launcher: &[String],
+    sink: OutputSink,
+    cancel: &AtomicBool,
+    max_build_time: Option<u32>,
+) -> Result<(BuildOutcome, Vec<String>), String> {
+    let server = Server::get(&run.plugin, &run.plugin_dir, server_argv(&run.script), launcher, Some(&sink))?;
+    let params = json!({
+        "command": run.command,
+        "platform": run.platform,
+        "projectDir": work_dir,
+        "outputDir": run.output_dir(work_dir),
+        "args": run.args,
+        "env": env.into_iter().collect::<HashMap<_, _>>(),
+    });
+    let (id, receiver) = server.request(method.name(), params, Some(&sink))?;
+
+    let deadline = max_build_time.map(|secs| Instant::now() + Duration::from_secs(secs.into()));
+    let mut stopped: Option<(BuildOutcome, Instant)> = None;
+    let reply = loop {
+        match receiver.recv_timeout(build::POLL_INTERVAL) {
+            Ok(reply) => break Some(reply),
+            Err(mpsc::RecvTimeoutError::Disconnected) => break None,
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+        match &stopped {
+            Some((_, since)) if since.elapsed() >= CANCEL_GRACE => {
+                sink(OutputStream::Stderr, format!("Plugin '{}' did not stop in time; killing it", run.plugin));
+                server.kill();
+                break None;
+            }
+            Some(_) => {}
+            None => {
+                let outcome = if cancel.load(Ordering::SeqCst) {
+                    BuildOutcome::Cancelled
+                } else if deadline.is_some_and(|d| Instant::now() >= d) {
+                    BuildOutcome::Timeout
+                } else {
+                    continue;
+                };
+                // The answer to `cancel` does not matter; the request it names still gets one
+                let _ = server.request("cancel", json!({ "id": id }), None).map(|(cancel_id, _)| server.finish(cancel_id));
+                stopped = Some((outcome, Instant::now()));
+            }
+        }
+    };
+    server.finish(id);
+
+    if let Some((outcome, _)) = stopped {
+        return Ok((outcome, Vec::new()));
+    }
+    let result = match reply {
+        Some(reply) => reply.map_err(|e| format!("Plugin '{}' failed to {}: {}", run.plugin, method.name(), e))?,
+        None => return Err(format!("Plugin '{}' exited before answering {}", run.plugin, method.name())),
+    };
+    let result: TaskResult = serde_json::from_value(result)
+        .map_err(|e| format!("Plugin '{}' answered {} with an invalid result: {}", run.plugin, method.name(), e))?;
+    let mut artifacts = Vec::new();
+    for path in result.artifacts {
+        match artifact_path(&path, work_dir) {
+            Some(path) => artifacts.push(path),
+            None => sink(OutputStream::Stderr, format!("Ignoring artifact outside the project: {}", path)),
+        }
+    }
+    Ok((BuildOutcome::Success, artifacts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A server answering each request with an empty result after `delay` seconds, until it
+    /// kills itself after `lifetime` seconds
+    fn responder(delay: u32, lifetime: u32) -> Vec<OsString> {
+        let script = format!(
+            r#"(sleep {lifetime}; kill 0) & while read -r line; do sleep {delay}; id=$(echo "$line" | sed 's/.*"id":\([0-9]*\).*/\1/'); echo "{{\"jsonrpc\":\"2.0\",\"id\":$id,\"result\":{{}}}}"; done"#,
+        );
+        ["sh", "-c", &script].iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn starting_a_server_does_not_block_other_servers() {
+        let dir = std::env::temp_dir();
+        let slow_dir = dir.clone();
+        let slow = thread::spawn(move || Server::get("slow", &slow_dir, responder(10, 2), &[], None).map(|_| ()));
+        thread::sleep(Duration::from_millis(200));
+        let started = Instant::now();
+        let fast = Server::get("fast", &dir, responder(0, 30), &[], None).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        fast.kill();
+        assert!(slow.join().unwrap().unwrap_err().starts_with("Plugin 'slow' failed to initialize"));
+    }
+
+    #[test]
+    fn exited_servers_are_reaped() {
+        let server = Server::get("short", &std::env::temp_dir(), responder(0, 1), &[], None).unwrap();
+        let started = Instant::now();
+        while server.child.lock().unwrap().is_some() {
+            assert!(started.elapsed() < Duration::from_secs(10), "the server was not reaped");
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert!(server.exited.load(Ordering::SeqCst));
+        assert!(Server::running(&server.key).is_none());
+    }
+
+    #[test]
+    fn provides_checks_the_server_against_the_policy() {
+        let dir = std::env::temp_dir().join(format!("build-studio-rpc-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = json!({"name": "Demo Server", "version": "1.0.0", "protocol": "jsonrpc", "server": "server.sh"});
+        std::fs::write(dir.join("plugin.json"), manifest.to_string()).unwrap();
+        std::fs::write(dir.join("server.sh"), "#!/bin/sh\nexit 1\n").unwrap();
+        let plugin = plugin::load_plugin(&dir).unwrap();
+
+        let config = SecurityConfig { blocked_commands: vec!["sh".to_string()], ..SecurityConfig::default() };
+        let error = provides(&plugin, &config).unwrap_err();
+        assert!(error.contains("blocked_commands: sh"), "{}", error);
+        assert!(Server::running(&[OsString::from("/bin/sh"), dir.join("server.sh").into()]).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,9 +1,17 @@
 //! Security module for Build Studio
 //! Provides sandboxing and security features for builds
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
 use std::fs;
+use std::process::{Command, Stdio};
+use crate::fsutils::{LinkStrategy, TreeSync};
+use crate::policy::{self, CommandRule, PolicyDecision};
+
+/// File inside the sandbox recording which files were synced from the project
+const SANDBOX_MANIFEST: &str = ".buildstudio-sandbox.json";
 
 /// Security configuration for builds
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -14,10 +22,23 @@ pub struct SecurityConfig {
     pub allowed_paths: Vec<String>,
     /// Blocked system commands
     pub blocked_commands: Vec<String>,
+    /// Allow/deny/ask rules checked before `blocked_commands`
+    #[serde(default)]
+    pub command_rules: Vec<CommandRule>,
     /// Enable network isolation
     pub network_isolation: bool,
     /// Maximum build time in seconds
     pub max_build_time: Option<u32>,
+    /// Directories the build writes to; copied from the sandbox back into the project
+    #[serde(default = "default_output_paths")]
+    pub output_paths: Vec<String>,
+    /// How project files are placed into the sandbox
+    #[serde(default)]
+    pub sandbox_strategy: LinkStrategy,
+}
+
+fn default_output_paths() -> Vec<String> {
+    vec!["./builds".to_string(), "./packages".to_string()]
 }
 
 impl Default for SecurityConfig {
@@ -33,65 +54,393 @@ impl Default for SecurityConfig {
                 "chmod".to_string(),
                 "chown".to_string(),
             ],
+            command_rules: vec![],
             network_isolation: true,
             max_build_time: Some(3600), // 1 hour default
+            output_paths: default_output_paths(),
+            sandbox_strategy: LinkStrategy::Auto,
         }
     }
 }
 
-/// Validate that a command is not blocked
-pub fn validate_command(command: &str, config: &SecurityConfig) -> bool {
-    if !config.enable_sandbox {
-        return true;
+/// Name of the user-level security policy file in the app config dir
+pub const SECURITY_POLICY_FILE: &str = "security.json";
+
+/// Settings that can be overridden and locked, by their `SecurityConfig` field name
+pub const SECURITY_SETTINGS: &[&str] = &[
+    "enable_sandbox",
+    "allowed_paths",
+    "blocked_commands",
+    "command_rules",
+    "network_isolation",
+    "max_build_time",
+    "output_paths",
+    "sandbox_strategy",
+];
+
+/// Partial security settings, as written in the `security:` section of
+/// buildstudio.config.yaml, on a single build, or in the user defaults.
+/// Unset fields keep the value from the level below.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SecurityOverrides {
+    pub enable_sandbox: Option<bool>,
+    pub allowed_paths: Option<Vec<String>>,
+    pub blocked_commands: Option<Vec<String>>,
+    pub command_rules: Option<Vec<CommandRule>>,
+    pub network_isolation: Option<bool>,
+    /// Maximum build time in seconds; `0` removes the limit
+    pub max_build_time: Option<u32>,
+    pub output_paths: Option<Vec<String>>,
+    pub sandbox_strategy: Option<LinkStrategy>,
+}
+
+impl SecurityOverrides {
+    /// Apply these overrides to `config`, leaving `locked` settings untouched.
+    /// Returns the names of the settings that were skipped because they are locked.
+    fn apply(&self, config: &mut SecurityConfig, locked: &[String]) -> Vec<String> {
+        let mut skipped = Vec::new();
+        macro_rules! apply {
+            ($field:ident, $value:expr) => {
+                if let Some(value) = &self.$field {
+                    if locked.iter().any(|l| l == stringify!($field)) {
+                        skipped.push(stringify!($field).to_string());
+                    } else {
+                        config.$field = $value(value.clone());
+                    }
+                }
+            };
+            ($field:ident) => {
+                apply!($field, |value| value)
+            };
+        }
+        apply!(enable_sandbox);
+        apply!(allowed_paths);
+        apply!(blocked_commands);
+        apply!(command_rules);
+        apply!(network_isolation);
+        apply!(max_build_time, |secs| if secs == 0 { None } else { Some(secs) });
+        apply!(output_paths);
+        apply!(sandbox_strategy);
+        skipped
     }
-    
-    // Check if command is in blocked list
-    for blocked in &config.blocked_commands {
-        if command.contains(blocked) {
-            return false;
+
+    /// Set the fields `other` sets, except `locked` ones. Returns the names of the skipped settings.
+    fn overlay(&mut self, other: &Self, locked: &[String]) -> Vec<String> {
+        let mut skipped = Vec::new();
+        macro_rules! overlay {
+            ($($field:ident),*) => {$(
+                if other.$field.is_some() {
+                    if locked.iter().any(|l| l == stringify!($field)) {
+                        skipped.push(stringify!($field).to_string());
+                    } else {
+                        self.$field = other.$field.clone();
+                    }
+                }
+            )*};
         }
+        overlay!(
+            enable_sandbox,
+            allowed_paths,
+            blocked_commands,
+            command_rules,
+            network_isolation,
+            max_build_time,
+            output_paths,
+            sandbox_strategy
+        );
+        skipped
     }
-    
-    true
 }
 
-/// Create a sandboxed environment for builds
-pub fn create_sandbox(project_dir: &str, config: &SecurityConfig) -> Result<String, String> {
-    if !config.enable_sandbox {
-        return Ok(project_dir.to_string());
+/// Security policy stored as `security.json`, either in the user's app config dir or in the
+/// machine-wide config dir. Only the machine-wide file can lock settings against the user.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SecurityPolicy {
+    /// Defaults applied on top of the built-in security config
+    #[serde(default)]
+    pub defaults: SecurityOverrides,
+    /// Settings that projects and builds may not override, e.g. `network_isolation`
+    #[serde(default)]
+    pub locked: Vec<String>,
+    /// User defaults that were ignored because the machine-wide policy locks them
+    #[serde(skip)]
+    pub ignored: Vec<String>,
+}
+
+impl SecurityPolicy {
+    /// Load the user policy from `app_config_dir` and combine it with the machine-wide policy.
+    /// Missing files mean no defaults.
+    pub fn load(app_config_dir: &Path) -> Result<Self, String> {
+        let user = Self::load_file(&app_config_dir.join(SECURITY_POLICY_FILE))?;
+        match crate::fsutils::system_config_dir() {
+            Some(dir) => Ok(Self::load_file(&dir.join(SECURITY_POLICY_FILE))?.enforce_over(&user)),
+            None => Ok(user),
+        }
     }
-    
-    // Create a temporary directory for the sandbox
-    let sandbox_dir = format!("{}/.sandbox", project_dir);
-    
-    // Create the sandbox directory
-    fs::create_dir_all(&sandbox_dir)
-        .map_err(|e| format!("Failed to create sandbox directory: {}", e))?;
-    
-    // Copy allowed files to sandbox
-    for allowed_path in &config.allowed_paths {
-        let src_path = format!("{}/{}", project_dir, allowed_path);
-        let dest_path = format!("{}/{}", sandbox_dir, allowed_path);
-        
-        if Path::new(&src_path).exists() {
-            // Create parent directory if needed
-            if let Some(parent) = Path::new(&dest_path).parent() {
-                fs::create_dir_all(parent)
-                    .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    /// Apply the `user` policy on top of this machine-wide one. The user can neither change
+    /// nor unlock settings this policy locks.
+    fn enforce_over(mut self, user: &Self) -> Self {
+        self.ignored = self
+            .defaults
+            .overlay(&user.defaults, &self.locked)
+            .into_iter()
+            .map(|setting| format!("user default '{}' is locked by the system policy", setting))
+            .collect();
+        for setting in &user.locked {
+            if !self.locked.contains(setting) {
+                self.locked.push(setting.clone());
             }
-            
-            // Copy file or directory
-            if Path::new(&src_path).is_dir() {
-                fs_extra::dir::copy(&src_path, &dest_path, &fs_extra::dir::CopyOptions::new())
-                    .map_err(|e| format!("Failed to copy directory: {}", e))?;
+        }
+        self
+    }
+
+    fn load_file(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let policy: Self = serde_json::from_str(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+        if let Some(unknown) = policy.locked.iter().find(|l| !SECURITY_SETTINGS.contains(&l.as_str())) {
+            return Err(format!("Invalid {}: unknown locked setting '{}'", path.display(), unknown));
+        }
+        Ok(policy)
+    }
+}
+
+/// The security config a build runs with, after merging all levels
+#[derive(Serialize, Clone, Debug)]
+pub struct EffectiveSecurity {
+    pub config: SecurityConfig,
+    /// Project or build overrides that were ignored because the setting is locked
+    pub ignored: Vec<String>,
+}
+
+impl EffectiveSecurity {
+    /// Merge built-in defaults, policy defaults, project settings and build settings, in that order
+    pub fn resolve(policy: &SecurityPolicy, project: Option<&SecurityOverrides>, build: Option<&SecurityOverrides>) -> Self {
+        Self::resolve_from(SecurityConfig::default(), policy, project, build)
+    }
+
+    /// Like `resolve`, starting from `base` instead of the built-in defaults
+    pub fn resolve_from(
+        mut config: SecurityConfig,
+        policy: &SecurityPolicy,
+        project: Option<&SecurityOverrides>,
+        build: Option<&SecurityOverrides>,
+    ) -> Self {
+        policy.defaults.apply(&mut config, &[]);
+        let mut ignored = policy.ignored.clone();
+        for (level, overrides) in [("project", project), ("build", build)] {
+            if let Some(overrides) = overrides {
+                for setting in overrides.apply(&mut config, &policy.locked) {
+                    ignored.push(format!("{} setting '{}' is locked by the security policy", level, setting));
+                }
+            }
+        }
+        Self { config, ignored }
+    }
+
+    /// Human-readable summary shown before a build starts
+    pub fn summary(&self) -> Vec<String> {
+        let config = &self.config;
+        let on_off = |flag: bool| if flag { "on" } else { "off" };
+        let mut lines = vec![
+            format!("sandbox: {} ({:?})", on_off(config.enable_sandbox), config.sandbox_strategy).to_lowercase(),
+            format!("network isolation: {}", on_off(config.network_isolation)),
+            match config.max_build_time {
+                Some(secs) => format!("max build time: {}s", secs),
+                None => "max build time: unlimited".to_string(),
+            },
+            format!("allowed paths: {}", config.allowed_paths.join(", ")),
+            format!("output paths: {}", config.output_paths.join(", ")),
+            format!("blocked commands: {}", config.blocked_commands.join(", ")),
+        ];
+        lines.extend(config.command_rules.iter().map(|rule| format!("command rule: {}", rule)));
+        lines.extend(self.ignored.iter().map(|note| format!("ignored: {}", note)));
+        lines
+    }
+}
+
+/// Check every executable a command line would run against the command rules,
+/// then against the blocked commands list. This applies to sandboxed and unsandboxed builds alike.
+pub fn validate_command(command: &str, config: &SecurityConfig) -> PolicyDecision {
+    let rules: Vec<CommandRule> = config
+        .command_rules
+        .iter()
+        .cloned()
+        .chain(config.blocked_commands.iter().map(|blocked| CommandRule::blocked(blocked)))
+        .collect();
+    policy::evaluate(command, &rules)
+}
+
+/// Launchers that move a command into a fresh network namespace, in order of preference.
+/// bubblewrap brings up loopback itself; with plain `unshare` it is raised by hand.
+const NETWORK_LAUNCHERS: &[&[&str]] = &[
+    &["bwrap", "--dev-bind", "/", "/", "--unshare-net", "--die-with-parent", "--"],
+    &["unshare", "--user", "--map-root-user", "--net", "--", "sh", "-c", "ip link set lo up 2>/dev/null; exec \"$@\"", "sh"],
+];
+
+/// Check that a launcher is installed and allowed to create namespaces on this host
+fn launcher_works(launcher: &[&str]) -> bool {
+    Command::new(launcher[0])
+        .args(&launcher[1..])
+        .arg("true")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Command prefix that runs a build without network access (only loopback is reachable).
+///
+/// Returns an empty prefix when isolation is disabled. On Linux the build is placed in a new
+/// network namespace; if no launcher can create one the build is refused rather than run with
+/// network access. Other platforms do not support isolation yet and get an empty prefix.
+pub fn network_isolation_prefix(config: &SecurityConfig) -> Result<Vec<String>, String> {
+    if !config.enable_sandbox || !config.network_isolation || !cfg!(target_os = "linux") {
+        return Ok(vec![]);
+    }
+
+    NETWORK_LAUNCHERS
+        .iter()
+        .find(|launcher| launcher_works(launcher))
+        .map(|launcher| launcher.iter().map(|arg| arg.to_string()).collect())
+        .ok_or_else(|| {
+            "Network isolation is enabled but no network namespace could be created. \
+             Install bubblewrap (bwrap), enable unprivileged user namespaces, \
+             or set network_isolation to false."
+                .to_string()
+        })
+}
+
+/// Turn a configured path such as `./src` into a path relative to the project root
+fn project_relative(path: &str) -> Result<PathBuf, String> {
+    let mut relative = PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => return Err(format!("Sandbox paths must stay inside the project: {}", path)),
+        }
+    }
+    Ok(relative)
+}
+
+/// Bring `dest` up to date with the given project-relative `paths`, skipping the project
+/// directories in `skipped` and transferring only changed files. Files synced by an earlier
+/// call that disappeared from the project are removed again.
+pub fn sync_project(
+    project: &Path,
+    dest: &Path,
+    paths: &[String],
+    skipped: &[&str],
+    strategy: LinkStrategy,
+) -> Result<(), String> {
+    fs::create_dir_all(dest)
+        .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+
+    let manifest_path = dest.join(SANDBOX_MANIFEST);
+    let previous: BTreeSet<PathBuf> = fs::read_to_string(&manifest_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+
+    // Sync allowed files, never descending into the project's own sandbox
+    let mut sync = TreeSync::new(strategy)
+        .excluding(project.join(".sandbox"))
+        .excluding(dest.to_path_buf());
+    for dir in skipped {
+        sync = sync.excluding(project.join(dir));
+    }
+    // Links into the allowed paths are recreated inside the sandbox, others are copied through
+    let relatives = paths.iter().map(|path| project_relative(path)).collect::<Result<Vec<_>, _>>()?;
+    let roots: Vec<PathBuf> = relatives.iter().map(|relative| project.join(relative)).collect();
+    sync = sync.linking_within(project, dest, &roots);
+    let mut current = BTreeSet::new();
+    for (path, relative) in paths.iter().zip(relatives) {
+        let src_path = project.join(&relative);
+        if !src_path.exists() {
+            continue;
+        }
+        let synced = sync
+            .sync(&src_path, &dest.join(&relative))
+            .map_err(|e| format!("Failed to sync {} into {}: {}", path, dest.display(), e))?;
+        current.extend(synced.into_iter().map(|file| {
+            if file.as_os_str().is_empty() {
+                relative.clone()
             } else {
-                fs::copy(&src_path, &dest_path)
-                    .map_err(|e| format!("Failed to copy file: {}", e))?;
+                relative.join(file)
             }
+        }));
+    }
+
+    // Drop files synced earlier that no longer exist in the project, but never through a
+    // directory that has since become a link
+    for stale in previous.difference(&current) {
+        let through_link = stale.ancestors().skip(1).any(|dir| !dir.as_os_str().is_empty() && dest.join(dir).is_symlink());
+        if !through_link && stale.components().all(|c| matches!(c, Component::Normal(_))) {
+            let _ = fs::remove_file(dest.join(stale));
         }
     }
-    
-    Ok(sandbox_dir)
+
+    let manifest = serde_json::to_string(&current).map_err(|e| e.to_string())?;
+    fs::write(&manifest_path, manifest)
+        .map_err(|e| format!("Failed to write sync manifest: {}", e))
+}
+
+/// Create or refresh the sandboxed environment for a build.
+///
+/// Each build has its own sandbox in `<project>/.sandbox/<build name>`, so builds that run in
+/// parallel never share one, and builds without a name use `.sandbox/default`. The sandbox is
+/// kept between builds: only files whose size or modification time changed are transferred,
+/// using reflinks where the filesystem supports them, and files that disappeared from the
+/// project are removed again. The project directories in `skipped`, such as a language's build
+/// caches, are never synced; the build keeps its own copies of them in the sandbox.
+pub fn create_sandbox(
+    project_dir: &str,
+    build_name: Option<&str>,
+    skipped: &[&str],
+    config: &SecurityConfig,
+) -> Result<String, String> {
+    if !config.enable_sandbox {
+        return Ok(project_dir.to_string());
+    }
+
+    let project = Path::new(project_dir);
+    let sandbox = project.join(".sandbox").join(crate::fsutils::safe_file_name(build_name.unwrap_or("default")));
+    sync_project(project, &sandbox, &config.allowed_paths, skipped, config.sandbox_strategy)
+        .map_err(|e| format!("Failed to prepare sandbox: {}", e))?;
+    Ok(sandbox.to_string_lossy().to_string())
+}
+
+/// Copy the declared output directories from the sandbox back into the project.
+/// The sandbox itself is kept so that the next build only transfers changed files.
+pub fn collect_sandbox_outputs(project_dir: &str, sandbox_dir: &str, config: &SecurityConfig) -> Result<(), String> {
+    let project = Path::new(project_dir);
+    let sandbox = Path::new(sandbox_dir);
+    if project == sandbox {
+        return Ok(());
+    }
+
+    // Outputs must not share inodes with the sandbox, which the next build may rewrite
+    let strategy = match config.sandbox_strategy {
+        LinkStrategy::Hardlink => LinkStrategy::Auto,
+        strategy => strategy,
+    };
+    let mut sync = TreeSync::new(strategy);
+    for output_path in &config.output_paths {
+        let relative = project_relative(output_path)?;
+        let built = sandbox.join(&relative);
+        if built.exists() {
+            sync.sync(&built, &project.join(&relative))
+                .map_err(|e| format!("Failed to copy {} out of sandbox: {}", output_path, e))?;
+        }
+    }
+    Ok(())
 }
 
 /// Clean up sandbox environment
@@ -102,3 +451,163 @@ pub fn cleanup_sandbox(sandbox_dir: &str) -> Result<(), String> {
     }
     Ok(())
 }
+
+/// Delete a project's sandboxes so that the next build starts from a full sync
+#[tauri::command]
+pub fn clear_sandbox(project_dir: String) -> Result<(), String> {
+    cleanup_sandbox(&Path::new(&project_dir).join(".sandbox").to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn isolated() -> SecurityConfig {
+        SecurityConfig {
+            enable_sandbox: true,
+            network_isolation: true,
+            ..SecurityConfig::default()
+        }
+    }
+
+    #[test]
+    fn no_prefix_without_isolation() {
+        let config = SecurityConfig { network_isolation: false, ..isolated() };
+        assert_eq!(network_isolation_prefix(&config), Ok(vec![]));
+        let config = SecurityConfig { enable_sandbox: false, ..isolated() };
+        assert_eq!(network_isolation_prefix(&config), Ok(vec![]));
+    }
+
+    /// Skipped where bwrap and unshare cannot create a namespace, or curl is missing
+    #[test]
+    fn curl_fails_inside_network_namespace() {
+        let prefix = match network_isolation_prefix(&isolated()) {
+            Ok(prefix) if !prefix.is_empty() => prefix,
+            _ => {
+                eprintln!("skipped: no network namespace can be created on this host");
+                return;
+            }
+        };
+        if crate::fsutils::find_executable("curl").is_none() {
+            eprintln!("skipped: curl is not installed");
+            return;
+        }
+        let output = Command::new(&prefix[0])
+            .args(&prefix[1..])
+            .args(["curl", "-sS", "--max-time", "5", "-o", "/dev/null", "https://example.com"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "curl reached the network inside the sandbox");
+    }
+
+    #[test]
+    fn each_build_has_its_own_sandbox() {
+        let project = std::env::temp_dir().join(format!("build-studio-sandbox-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join("src/main.c"), "int main() {}").unwrap();
+        let config = SecurityConfig { sandbox_strategy: LinkStrategy::Copy, ..isolated() };
+        let project_dir = project.to_string_lossy();
+
+        let linux = create_sandbox(&project_dir, Some("Linux Build"), &[], &config).unwrap();
+        let windows = create_sandbox(&project_dir, Some("Windows Build"), &[], &config).unwrap();
+        assert_eq!(Path::new(&linux), project.join(".sandbox/Linux_Build"));
+        assert_eq!(Path::new(&windows), project.join(".sandbox/Windows_Build"));
+        assert!(Path::new(&linux).join("src/main.c").is_file());
+        assert!(Path::new(&windows).join("src/main.c").is_file());
+        assert_eq!(create_sandbox(&project_dir, None, &[], &config).unwrap(), project.join(".sandbox/default").to_string_lossy());
+        fs::remove_dir_all(&project).unwrap();
+    }
+
+    fn policy(json: &str) -> SecurityPolicy {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn system_policy_locks_user_defaults() {
+        let system = policy(r#"{"defaults": {"network_isolation": true}, "locked": ["network_isolation"]}"#);
+        let user = policy(r#"{"defaults": {"network_isolation": false, "max_build_time": 60}, "locked": ["allowed_paths"]}"#);
+        let merged = system.enforce_over(&user);
+        assert_eq!(merged.defaults.network_isolation, Some(true));
+        assert_eq!(merged.defaults.max_build_time, Some(60));
+        assert_eq!(merged.locked, ["network_isolation", "allowed_paths"]);
+        assert_eq!(merged.ignored, ["user default 'network_isolation' is locked by the system policy"]);
+
+        let project = SecurityOverrides { network_isolation: Some(false), ..Default::default() };
+        let effective = EffectiveSecurity::resolve(&merged, Some(&project), None);
+        assert!(effective.config.network_isolation);
+        assert_eq!(effective.ignored.len(), 2);
+    }
+
+    #[test]
+    fn resolve_from_keeps_base_until_overridden() {
+        let base = SecurityConfig { enable_sandbox: false, ..SecurityConfig::default() };
+        let effective = EffectiveSecurity::resolve_from(base.clone(), &SecurityPolicy::default(), None, None);
+        assert!(!effective.config.enable_sandbox);
+
+        let project = SecurityOverrides { enable_sandbox: Some(true), ..Default::default() };
+        let effective = EffectiveSecurity::resolve_from(base, &SecurityPolicy::default(), Some(&project), None);
+        assert!(effective.config.enable_sandbox);
+    }
+    fn temp_project() -> PathBuf {
+        let project = std::env::temp_dir().join(format!("build-studio-sync-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(project.join("src/include")).unwrap();
+        project
+    }
+
+    fn manifest(sandbox: &Path) -> BTreeSet<PathBuf> {
+        serde_json::from_str(&fs::read_to_string(sandbox.join(SANDBOX_MANIFEST)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn files_removed_from_the_project_are_removed_from_the_sandbox() {
+        let project = temp_project();
+        let sandbox = project.join(".sandbox/default");
+        fs::write(project.join("src/main.c"), "int main;").unwrap();
+        fs::write(project.join("src/old.c"), "int old;").unwrap();
+        let paths = ["./src".to_string()];
+        sync_project(&project, &sandbox, &paths, &[], LinkStrategy::Copy).unwrap();
+        assert_eq!(manifest(&sandbox), BTreeSet::from([PathBuf::from("src/main.c"), PathBuf::from("src/old.c")]));
+
+        fs::remove_file(project.join("src/old.c")).unwrap();
+        fs::write(sandbox.join("src/main.o"), "").unwrap();
+        sync_project(&project, &sandbox, &paths, &[], LinkStrategy::Copy).unwrap();
+        assert!(!sandbox.join("src/old.c").exists());
+        assert!(sandbox.join("src/main.c").exists());
+        // Files the build wrote were never synced, so they stay
+        assert!(sandbox.join("src/main.o").exists());
+        assert_eq!(manifest(&sandbox), BTreeSet::from([PathBuf::from("src/main.c")]));
+        fs::remove_dir_all(project).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn links_into_allowed_paths_are_recreated_and_others_copied() {
+        use std::os::unix::fs::symlink;
+        let project = temp_project();
+        let sandbox = project.join(".sandbox/default");
+        fs::write(project.join("src/main.c"), "int main;").unwrap();
+        fs::write(project.join("src/include/config.h"), "#define DEBUG").unwrap();
+        fs::write(project.join("secret.txt"), "hunter2").unwrap();
+        symlink("include/config.h", project.join("src/config.h")).unwrap();
+        symlink(project.join("src/main.c"), project.join("src/app.c")).unwrap();
+        symlink(project.join("secret.txt"), project.join("src/secret.txt")).unwrap();
+        symlink(project.join("src"), project.join("src/include/src")).unwrap();
+        let paths = ["./src".to_string()];
+        sync_project(&project, &sandbox, &paths, &[], LinkStrategy::Copy).unwrap();
+
+        assert_eq!(fs::read_link(sandbox.join("src/config.h")).unwrap(), Path::new("include/config.h"));
+        // An absolute link is made relative so that it stays inside the sandbox
+        assert_eq!(fs::read_link(sandbox.join("src/app.c")).unwrap(), Path::new("main.c"));
+        assert_eq!(fs::read_link(sandbox.join("src/include/src")).unwrap(), Path::new(".."));
+        assert!(!sandbox.join("src/secret.txt").is_symlink());
+        assert_eq!(fs::read_to_string(sandbox.join("src/secret.txt")).unwrap(), "hunter2");
+        for link in ["src/config.h", "src/app.c", "src/secret.txt", "src/include/src"] {
+            assert!(manifest(&sandbox).contains(Path::new(link)), "{}", link);
+        }
+
+        fs::remove_file(project.join("src/config.h")).unwrap();
+        sync_project(&project, &sandbox, &paths, &[], LinkStrategy::Copy).unwrap();
+        assert!(sandbox.join("src/config.h").symlink_metadata().is_err());
+        fs::remove_dir_all(project).unwrap();
+    }
+}
@@ -1,4 +1,10 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use crate::fsutils;
+use crate::local_nodes;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum Toolchain {
@@ -7,18 +13,528 @@ pub enum Toolchain {
     AndroidNDK,
     Emscripten,
     WasmPack,
+    /// GNU cross compiler for a Linux target triple (`aarch64-linux-gnu-gcc`, ...)
+    Gcc(String),
+    /// `zig cc -target <triple>` as C/C++ compiler and linker
+    Zig(String),
+    /// cross-rs, which builds Rust for the target triple in a Docker or Podman container
+    Cross(String),
+    /// A profile defined in the project's `toolchains:` section
     Custom(String),
 }
 
+/// Build environment for a toolchain. Profiles only set environment variables;
+/// the build command itself is never rewritten.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ToolchainProfile {
+    /// Name a build's `toolchain:` or `platform:` selects this profile by
+    pub name: String,
+    /// Target triple, e.g. `aarch64-unknown-linux-gnu`
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub cc: Option<String>,
+    #[serde(default)]
+    pub cxx: Option<String>,
+    #[serde(default)]
+    pub ar: Option<String>,
+    /// Linker; also used as Cargo's linker for `target`. Defaults to `cc` for Cargo.
+    #[serde(default)]
+    pub ld: Option<String>,
+    #[serde(default)]
+    pub sysroot: Option<String>,
+    /// Directories put in front of `PATH`
+    #[serde(default)]
+    pub path: Vec<String>,
+    #[serde(default)]
+    pub goos: Option<String>,
+    #[serde(default)]
+    pub goarch: Option<String>,
+    /// Any further variables
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Executables the toolchain needs besides `cc` and `ld`, checked by `doctor`
+    #[serde(default)]
+    pub tools: Vec<String>,
+}
+
+/// Names of the built-in toolchains, as used in a build's `toolchain:` field
+pub const TOOLCHAIN_NAMES: &[&str] = &["native", "mingw", "android-ndk", "emscripten", "wasm-pack", "gcc", "zig", "cross"];
+
+/// Platform names with a known toolchain, offered for a build's `platform:`. Any other name
+/// builds natively, and any Linux target triple selects its GNU cross compiler.
+pub const PLATFORMS: &[&str] = &[
+    "linux",
+    "macos",
+    "windows",
+    "wasm",
+    "emscripten",
+    "android",
+    "linux-aarch64",
+    "linux-armv7",
+    "linux-riscv64",
+    "linux-musl",
+    "linux-aarch64-musl",
+];
+
+/// Compiler flag variables a profile's `sysroot` is added to
+pub const SYSROOT_FLAGS: [&str; 2] = ["CFLAGS", "CXXFLAGS"];
+
+/// Vendor field of Rust's target triples, as in `x86_64-unknown-linux-gnu` or `aarch64-apple-darwin`
+const RUST_VENDORS: &[&str] = &[
+    "unknown", "pc", "apple", "sun", "nvidia", "fortanix", "uwp", "wrs", "esp", "espressif", "kmc", "nintendo",
+    "sony", "ibm", "unikraft", "openwrt", "win7", "risc0", "lynx", "nuttx", "mti",
+];
+
+/// Whether `target` is a Rust target triple, which Cargo's per-target variables are named after,
+/// rather than a GNU one such as `arm-linux-gnueabihf`
+fn is_rust_target(target: &str) -> bool {
+    let parts: Vec<&str> = target.split('-').collect();
+    match parts.as_slice() {
+        [_, os] => os.starts_with("wasi"),
+        [_, "linux", env] => env.starts_with("android"),
+        [_, "none", _] => true,
+        [_, vendor, _, ..] => RUST_VENDORS.contains(vendor),
+        _ => false,
+    }
+}
+
+/// Android API level used for NDK compilers
+const ANDROID_API_LEVEL: u32 = 24;
+
+/// Go's name for the architecture of a target triple
+fn go_arch(arch: &str) -> Option<&'static str> {
+    match arch {
+        "x86_64" => Some("amd64"),
+        "i686" | "i586" => Some("386"),
+        "aarch64" => Some("arm64"),
+        "riscv64gc" | "riscv64" => Some("riscv64"),
+        a if a.starts_with("arm") => Some("arm"),
+        _ => None,
+    }
+}
+
+/// Go's name for the operating system of a target triple
+fn go_os(target: &str) -> Option<&'static str> {
+    if target.contains("android") {
+        Some("android")
+    } else if target.contains("linux") {
+        Some("linux")
+    } else if target.contains("windows") {
+        Some("windows")
+    } else if target.contains("darwin") || target.contains("apple") {
+        Some("darwin")
+    } else {
+        None
+    }
+}
+
+/// GNU tool prefix for a Rust-style Linux triple: `aarch64-unknown-linux-gnu` -> `aarch64-linux-gnu`
+fn gnu_prefix(target: &str) -> String {
+    let parts: Vec<&str> = target.split('-').collect();
+    let arch = match parts[0] {
+        "riscv64gc" => "riscv64",
+        a if a.starts_with("armv7") => "arm",
+        a => a,
+    };
+    let rest: Vec<&str> = parts[1..].iter().copied().filter(|p| *p != "unknown").collect();
+    format!("{}-{}", arch, rest.join("-"))
+}
+
+/// Zig's name for a Rust-style triple: `x86_64-unknown-linux-musl` -> `x86_64-linux-musl`
+fn zig_target(target: &str) -> String {
+    gnu_prefix(target).replace("-pc-", "-").replace("apple-darwin", "macos")
+}
+
+/// Scripts running `zig cc`, `zig c++` and `zig ar` for `target`. Variables such as Cargo's
+/// linker must name a single executable, not a command with arguments.
+fn zig_wrappers(target: &str) -> Result<[String; 3], String> {
+    let dir = fsutils::app_cache_dir()
+        .ok_or("Could not determine the cache directory")?
+        .join("zig")
+        .join(target);
+    write_zig_wrappers(&dir, target)
+}
+
+fn write_zig_wrappers(dir: &Path, target: &str) -> Result<[String; 3], String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let zig_target = zig_target(target);
+    let tools = [
+        ("cc", format!("cc -target {}", zig_target)),
+        ("c++", format!("c++ -target {}", zig_target)),
+        ("ar", "ar".to_string()),
+    ];
+    let mut paths = tools.iter().map(|(name, args)| write_wrapper(dir, name, args));
+    Ok([paths.next().unwrap()?, paths.next().unwrap()?, paths.next().unwrap()?])
+}
+
+#[cfg(unix)]
+fn write_wrapper(dir: &Path, name: &str, args: &str) -> Result<String, String> {
+    use std::os::unix::fs::PermissionsExt;
+    let path = dir.join(name);
+    fs::write(&path, format!("#!/bin/sh\nexec zig {} \"$@\"\n", args))
+        .and_then(|_| fs::set_permissions(&path, fs::Permissions::from_mode(0o755)))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[cfg(not(unix))]
+fn write_wrapper(dir: &Path, name: &str, args: &str) -> Result<String, String> {
+    let path = dir.join(format!("{}.cmd", name));
+    fs::write(&path, format!("@zig {} %*\r\n", args)).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Directory name of the NDK's prebuilt host tools
+pub fn ndk_host_tag() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "darwin-x86_64"
+    } else if cfg!(target_os = "windows") {
+        "windows-x86_64"
+    } else {
+        "linux-x86_64"
+    }
+}
+
 impl Toolchain {
-    pub fn command_prefix(&self) -> Vec<String> {
-        match self {
-            Toolchain::Native => vec![],
-            Toolchain::MinGW => vec!["x86_64-w64-mingw32-".to_string()],
-            Toolchain::AndroidNDK => vec!["$ANDROID_NDK_HOME/toolchains/llvm/prebuilt/linux-x86_64/bin/".to_string()],
-            Toolchain::Emscripten => vec!["emcc".to_string()],
-            Toolchain::WasmPack => vec!["wasm-pack".to_string()],
-            Toolchain::Custom(prefix) => vec![prefix.clone()],
+    /// Look up a toolchain by the name used in a build's `toolchain:` field. `gcc` cross-compiles
+    /// for a Linux `target` (or platform) and is the host compiler otherwise. `zig` and `cross`
+    /// need a `target` triple or a platform that stands for one, such as `linux-aarch64`.
+    pub fn from_name(name: &str, target: Option<&str>, platform: Option<&str>) -> Result<Option<Self>, String> {
+        let needs_target = || {
+            target
+                .map(str::to_string)
+                .or_else(|| platform.and_then(platform_target))
+                .ok_or_else(|| format!("The {} toolchain needs a target triple", name))
+        };
+        Ok(match name.to_lowercase().as_str() {
+            "native" => Some(Toolchain::Native),
+            "mingw" => Some(Toolchain::MinGW),
+            "android-ndk" | "ndk" => Some(Toolchain::AndroidNDK),
+            "emscripten" | "emcc" => Some(Toolchain::Emscripten),
+            "wasm-pack" => Some(Toolchain::WasmPack),
+            "gcc" => Some(match target.or(platform).map(get_toolchain_for_platform) {
+                Some(gcc @ Toolchain::Gcc(_)) => gcc,
+                _ => Toolchain::Native,
+            }),
+            "zig" => Some(Toolchain::Zig(needs_target()?)),
+            "cross" => Some(Toolchain::Cross(needs_target()?)),
+            _ => None,
+        })
+    }
+
+    /// The build environment for this toolchain
+    pub fn profile(&self) -> Result<ToolchainProfile, String> {
+        Ok(match self {
+            Toolchain::Native => ToolchainProfile { name: "native".to_string(), ..Default::default() },
+            Toolchain::MinGW => ToolchainProfile {
+                name: "mingw".to_string(),
+                target: Some("x86_64-pc-windows-gnu".to_string()),
+                cc: Some("x86_64-w64-mingw32-gcc".to_string()),
+                cxx: Some("x86_64-w64-mingw32-g++".to_string()),
+                ar: Some("x86_64-w64-mingw32-ar".to_string()),
+                goos: Some("windows".to_string()),
+                goarch: Some("amd64".to_string()),
+                ..Default::default()
+            },
+            Toolchain::AndroidNDK => {
+                let target = "aarch64-linux-android";
+                let bin = std::env::var("ANDROID_NDK_HOME")
+                    .map(|ndk| format!("{}/toolchains/llvm/prebuilt/{}/bin", ndk, ndk_host_tag()))
+                    .ok();
+                ToolchainProfile {
+                    name: "android-ndk".to_string(),
+                    target: Some(target.to_string()),
+                    cc: Some(format!("{}{}-clang", target, ANDROID_API_LEVEL)),
+                    cxx: Some(format!("{}{}-clang++", target, ANDROID_API_LEVEL)),
+                    ar: Some("llvm-ar".to_string()),
+                    path: bin.into_iter().collect(),
+                    goos: Some("android".to_string()),
+                    goarch: Some("arm64".to_string()),
+                    ..Default::default()
+                }
+            }
+            Toolchain::Emscripten => ToolchainProfile {
+                name: "emscripten".to_string(),
+                target: Some("wasm32-unknown-emscripten".to_string()),
+                cc: Some("emcc".to_string()),
+                cxx: Some("em++".to_string()),
+                ar: Some("emar".to_string()),
+                ..Default::default()
+            },
+            Toolchain::WasmPack => ToolchainProfile {
+                name: "wasm-pack".to_string(),
+                target: Some("wasm32-unknown-unknown".to_string()),
+                goos: Some("js".to_string()),
+                goarch: Some("wasm".to_string()),
+                ..Default::default()
+            },
+            Toolchain::Gcc(target) => {
+                // musl-tools installs a plain `musl-gcc` wrapper for the host architecture,
+                // which works with the host's binutils and has no C++ counterpart
+                let prefix = gnu_prefix(target);
+                let (cc, cxx, ar) = if target == "x86_64-unknown-linux-musl"
+                    && fsutils::find_executable(&format!("{}-gcc", prefix)).is_none()
+                {
+                    ("musl-gcc".to_string(), None, None)
+                } else {
+                    (format!("{}-gcc", prefix), Some(format!("{}-g++", prefix)), Some(format!("{}-ar", prefix)))
+                };
+                let mut env = BTreeMap::new();
+                if target.starts_with("armv7") {
+                    env.insert("GOARM".to_string(), "7".to_string());
+                }
+                ToolchainProfile {
+                    name: format!("gcc ({})", target),
+                    target: Some(target.clone()),
+                    cc: Some(cc),
+                    cxx,
+                    ar,
+                    goos: go_os(target).map(str::to_string),
+                    goarch: go_arch(target.split('-').next().unwrap_or_default()).map(str::to_string),
+                    env,
+                    ..Default::default()
+                }
+            }
+            Toolchain::Zig(target) => {
+                let [cc, cxx, ar] = zig_wrappers(target)?;
+                ToolchainProfile {
+                    name: "zig".to_string(),
+                    target: Some(target.clone()),
+                    cc: Some(cc),
+                    cxx: Some(cxx),
+                    ar: Some(ar),
+                    goos: go_os(target).map(str::to_string),
+                    goarch: go_arch(target.split('-').next().unwrap_or_default()).map(str::to_string),
+                    tools: vec!["zig".to_string()],
+                    ..Default::default()
+                }
+            }
+            Toolchain::Cross(target) => {
+                // cross starts its build container with the same engine Build Studio uses
+                let env = local_nodes::container_engine()
+                    .map(|engine| ("CROSS_CONTAINER_ENGINE".to_string(), engine.to_string()))
+                    .into_iter()
+                    .collect();
+                ToolchainProfile {
+                    name: "cross".to_string(),
+                    target: Some(target.clone()),
+                    env,
+                    tools: vec!["cross".to_string()],
+                    ..Default::default()
+                }
+            }
+            Toolchain::Custom(name) => ToolchainProfile { name: name.clone(), ..Default::default() },
+        })
+    }
+}
+
+impl ToolchainProfile {
+    /// Environment variables for the build, in a stable order. `PATH` is left to the caller,
+    /// which knows the `PATH` the build inherits.
+    pub fn env(&self) -> Vec<(String, String)> {
+        let mut vars = Vec::new();
+        let mut set = |key: &str, value: &Option<String>| {
+            if let Some(value) = value {
+                vars.push((key.to_string(), value.clone()));
+            }
+        };
+        set("CC", &self.cc);
+        set("CXX", &self.cxx);
+        set("AR", &self.ar);
+        set("LD", &self.ld);
+        set("GOOS", &self.goos);
+        set("GOARCH", &self.goarch);
+        set("BUILD_STUDIO_TARGET", &self.target);
+        if let Some(sysroot) = &self.sysroot {
+            vars.push(("SYSROOT".to_string(), sysroot.clone()));
+            vars.push(("PKG_CONFIG_SYSROOT_DIR".to_string(), sysroot.clone()));
+        }
+        let target = self.target.as_deref().filter(|target| is_rust_target(target));
+        if let (Some(target), Some(linker)) = (target, self.ld.as_ref().or(self.cc.as_ref())) {
+            let key = format!("CARGO_TARGET_{}_LINKER", target.to_uppercase().replace(['-', '.'], "_"));
+            vars.push((key, linker.clone()));
         }
+        vars.extend(self.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        // The sysroot is added to the flags from `env`, not put in their place
+        if let Some(sysroot) = &self.sysroot {
+            for key in SYSROOT_FLAGS {
+                let flag = format!("--sysroot={}", sysroot);
+                match vars.iter_mut().find(|(k, _)| k == key) {
+                    Some((_, flags)) => *flags = format!("{} {}", flags, flag),
+                    None => vars.push((key.to_string(), flag)),
+                }
+            }
+        }
+        vars
+    }
+
+    /// Whether the profile changes anything about the build environment
+    pub fn is_native(&self) -> bool {
+        self.env().is_empty() && self.path.is_empty()
+    }
+}
+
+/// The toolchain for a platform name. Besides the named platforms, any Linux target triple
+/// (`aarch64-unknown-linux-gnu`, `x86_64-unknown-linux-musl`, ...) selects its GNU cross compiler.
+pub fn get_toolchain_for_platform(platform: &str) -> Toolchain {
+    let gcc = |target: &str| Toolchain::Gcc(target.to_string());
+    match platform {
+        "windows" => Toolchain::MinGW,
+        "wasm" | "webassembly" => Toolchain::WasmPack,
+        "emscripten" => Toolchain::Emscripten,
+        "android" => Toolchain::AndroidNDK,
+        "linux-aarch64" | "linux-arm64" | "aarch64" | "arm64" => gcc("aarch64-unknown-linux-gnu"),
+        "linux-armv7" | "armv7" => gcc("armv7-unknown-linux-gnueabihf"),
+        "linux-riscv64" | "riscv64" => gcc("riscv64gc-unknown-linux-gnu"),
+        "linux-musl" | "musl" => gcc("x86_64-unknown-linux-musl"),
+        "linux-aarch64-musl" | "aarch64-musl" => gcc("aarch64-unknown-linux-musl"),
+        triple if triple.contains("-linux-") && !triple.contains("android") => gcc(triple),
+        _ => Toolchain::Native,
+    }
+}
+
+/// The Linux target triple a platform name stands for, such as `aarch64-unknown-linux-gnu`
+/// for `linux-aarch64`
+pub fn platform_target(platform: &str) -> Option<String> {
+    match get_toolchain_for_platform(platform) {
+        Toolchain::Gcc(triple) => Some(triple),
+        _ => None,
+    }
+}
+
+/// The profile a build runs with: its `toolchain:` by name (custom profiles first), otherwise
+/// a custom profile named like its platform, otherwise the built-in toolchain for its `target:`
+/// triple or its platform
+pub fn resolve_profile(
+    platform: Option<&str>,
+    target: Option<&str>,
+    toolchain: Option<&str>,
+    custom: &[ToolchainProfile],
+) -> Result<ToolchainProfile, String> {
+    let find_custom = |name: &str| custom.iter().find(|p| p.name == name).cloned();
+    if let Some(name) = toolchain {
+        if let Some(profile) = find_custom(name) {
+            return Ok(profile);
+        }
+        return Toolchain::from_name(name, target, platform)?
+            .ok_or_else(|| format!("Unknown toolchain: {}", name))?
+            .profile();
+    }
+    match platform.and_then(find_custom) {
+        Some(profile) => Ok(profile),
+        None => target.or(platform).map(get_toolchain_for_platform).unwrap_or(Toolchain::Native).profile(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var<'a>(vars: &'a [(String, String)], key: &str) -> Option<&'a str> {
+        vars.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn sysroot_is_appended_to_flags() {
+        let profile = ToolchainProfile {
+            sysroot: Some("/opt/pi".to_string()),
+            env: BTreeMap::from([("CFLAGS".to_string(), "-O2 -g".to_string())]),
+            ..Default::default()
+        };
+        let vars = profile.env();
+        assert_eq!(var(&vars, "CFLAGS"), Some("-O2 -g --sysroot=/opt/pi"));
+        assert_eq!(var(&vars, "CXXFLAGS"), Some("--sysroot=/opt/pi"));
+        assert_eq!(vars.iter().filter(|(k, _)| k == "CFLAGS").count(), 1);
+    }
+
+    #[test]
+    fn cargo_linker_only_for_rust_targets() {
+        let profile = |target: &str| ToolchainProfile {
+            target: Some(target.to_string()),
+            cc: Some("cc".to_string()),
+            ..Default::default()
+        };
+        let vars = profile("aarch64-unknown-linux-gnu").env();
+        assert_eq!(var(&vars, "CARGO_TARGET_AARCH64_UNKNOWN_LINUX_GNU_LINKER"), Some("cc"));
+        assert!(!profile("arm-linux-gnueabihf").env().iter().any(|(k, _)| k.starts_with("CARGO_TARGET_")));
+
+        for target in ["x86_64-pc-windows-gnu", "aarch64-apple-darwin", "aarch64-linux-android", "wasm32-wasip1", "thumbv7em-none-eabihf"] {
+            assert!(is_rust_target(target), "{}", target);
+        }
+        for target in ["aarch64-linux-gnu", "x86_64-linux-musl", "arm-linux-gnueabihf", "x86_64"] {
+            assert!(!is_rust_target(target), "{}", target);
+        }
+    }
+
+    #[test]
+    fn zig_targets_drop_the_vendor() {
+        assert_eq!(zig_target("aarch64-unknown-linux-musl"), "aarch64-linux-musl");
+        assert_eq!(zig_target("x86_64-pc-windows-gnu"), "x86_64-windows-gnu");
+        assert_eq!(zig_target("aarch64-apple-darwin"), "aarch64-macos");
+        assert_eq!(zig_target("armv7-unknown-linux-gnueabihf"), "arm-linux-gnueabihf");
+    }
+
+    /// Runs the wrappers with a fake `zig` that prints its arguments one per line
+    #[cfg(unix)]
+    #[test]
+    fn zig_wrappers_pass_arguments_through() {
+        let dir = std::env::temp_dir().join(format!("build-studio-zig-{}", std::process::id()));
+        let [cc, cxx, ar] = write_zig_wrappers(&dir.join("wrappers"), "aarch64-unknown-linux-musl").unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        fs::write(dir.join("zig"), "#!/bin/sh\nprintf '%s\\n' \"$@\"\n").unwrap();
+        fs::set_permissions(dir.join("zig"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        let run = |wrapper: &str, args: &[&str]| {
+            let output = std::process::Command::new(wrapper)
+                .args(args)
+                .env("PATH", format!("{}:/usr/bin:/bin", dir.display()))
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+        assert_eq!(run(&cc, &["-c", "my file.c"]), "cc\n-target\naarch64-linux-musl\n-c\nmy file.c\n");
+        assert_eq!(run(&cxx, &["-O2"]), "c++\n-target\naarch64-linux-musl\n-O2\n");
+        assert_eq!(run(&ar, &["rcs", "lib.a"]), "ar\nrcs\nlib.a\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn custom_profiles_come_first() {
+        let custom = [ToolchainProfile {
+            name: "linux-aarch64".to_string(),
+            cc: Some("my-cc".to_string()),
+            ..Default::default()
+        }];
+        let by_platform = resolve_profile(Some("linux-aarch64"), None, None, &custom).unwrap();
+        assert_eq!(by_platform.cc.as_deref(), Some("my-cc"));
+        let by_name = resolve_profile(Some("linux"), None, Some("linux-aarch64"), &custom).unwrap();
+        assert_eq!(by_name, by_platform);
+        let built_in = resolve_profile(Some("linux-aarch64"), None, None, &[]).unwrap();
+        assert_eq!(built_in.cc.as_deref(), Some("aarch64-linux-gnu-gcc"));
+    }
+
+    #[test]
+    fn target_selects_the_built_in_toolchain() {
+        let profile = resolve_profile(Some("linux"), Some("riscv64gc-unknown-linux-gnu"), None, &[]).unwrap();
+        assert_eq!(profile.target.as_deref(), Some("riscv64gc-unknown-linux-gnu"));
+        assert_eq!(profile.cc.as_deref(), Some("riscv64-linux-gnu-gcc"));
+        assert_eq!(profile.goarch.as_deref(), Some("riscv64"));
+        assert!(resolve_profile(Some("linux"), None, None, &[]).unwrap().is_native());
+        assert!(resolve_profile(None, None, None, &[]).unwrap().is_native());
+    }
+
+    #[test]
+    fn named_toolchains() {
+        let cross = resolve_profile(Some("linux-aarch64"), None, Some("cross"), &[]).unwrap();
+        assert_eq!(cross.name, "cross");
+        assert_eq!(cross.target.as_deref(), Some("aarch64-unknown-linux-gnu"));
+        let gcc = resolve_profile(Some("linux"), Some("aarch64-unknown-linux-gnu"), Some("gcc"), &[]).unwrap();
+        assert_eq!(gcc.cxx.as_deref(), Some("aarch64-linux-gnu-g++"));
+        assert_eq!(resolve_profile(Some("windows"), None, Some("mingw"), &[]).unwrap().goos.as_deref(), Some("windows"));
+
+        assert_eq!(resolve_profile(Some("linux"), None, Some("zig"), &[]), Err("The zig toolchain needs a target triple".to_string()));
+        assert_eq!(resolve_profile(Some("linux"), None, Some("tcc"), &[]), Err("Unknown toolchain: tcc".to_string()));
     }
 }
@@ -0,0 +1,242 @@
+//! Config variables for Build Studio
+//! `${NAME}` in a build's `command` and `container` and in the `package` fields is replaced by a
+//! variable from, in order of precedence, `--var` on the command line, the environment, the
+//! project's `.env` file and the config's `vars:` map, or by a built-in such as `${project.dir}`.
+//! A name defined nowhere is an error. Shell syntax such as `${1}` or `${f%.c}` is left to the
+//! shell, and `$${` is kept as a literal `${`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use crate::config::BuildConfig;
+
+/// Optional file of `NAME=value` lines next to the config
+pub const ENV_FILE: &str = ".env";
+
+/// Read `NAME=value` lines, skipping blank lines and `#` comments. Values may be quoted and lines
+/// may start with `export`.
+pub fn read_env_file(path: &Path) -> Result<BTreeMap<String, String>, String> {
+    let mut values = BTreeMap::new();
+    if !path.is_file() {
+        return Ok(values);
+    }
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("{}:{}: expected NAME=value", path.display(), number + 1))?;
+        let value = value.trim();
+        let unquoted = ['"', '\''].iter().find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q));
+        values.insert(name.trim().to_string(), unquoted.unwrap_or(value).to_string());
+    }
+    Ok(values)
+}
+
+/// The variables of one project, resolved lazily as a config refers to them
+pub struct Variables {
+    /// `--var` values, which win over everything else
+    overrides: BTreeMap<String, String>,
+    /// `.env` values over the config's `vars:`; these may refer to other variables
+    defaults: BTreeMap<String, String>,
+    project_dir: PathBuf,
+    timestamp: String,
+    package_version: Option<String>,
+    git_sha: OnceLock<Option<String>>,
+}
+
+impl Variables {
+    /// Variables for the project in `project_dir` whose config declares `vars`
+    pub fn new(
+        project_dir: &Path,
+        vars: &BTreeMap<String, String>,
+        overrides: &BTreeMap<String, String>,
+    ) -> Result<Self, String> {
+        let mut defaults = vars.clone();
+        defaults.extend(read_env_file(&project_dir.join(ENV_FILE))?);
+        Ok(Self {
+            overrides: overrides.clone(),
+            defaults,
+            project_dir: fs::canonicalize(project_dir).unwrap_or_else(|_| project_dir.to_path_buf()),
+            // One value for the whole config, so that every build sees the same one
+            timestamp: chrono::Utc::now().format("%Y%m%d%H%M%S").to_string(),
+            package_version: None,
+            git_sha: OnceLock::new(),
+        })
+    }
+
+    /// Make `${package.version}` available once the package section has been expanded
+    pub fn set_package_version(&mut self, version: Option<String>) {
+        self.package_version = version;
+    }
+
+    fn git_sha(&self) -> Option<String> {
+        self.git_sha
+            .get_or_init(|| {
+                let output = Command::new("git")
+                    .args(["rev-parse", "HEAD"])
+                    .current_dir(&self.project_dir)
+                    .stdin(Stdio::null())
+                    .stderr(Stdio::null())
+                    .output()
+                    .ok()?;
+                let sha = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                (output.status.success() && !sha.is_empty()).then_some(sha)
+            })
+            .clone()
+    }
+
+    fn builtin(&self, name: &str, build: Option<&BuildConfig>) -> Result<Option<String>, String> {
+        let value = match name {
+            "project.dir" => Some(self.project_dir.to_string_lossy().to_string()),
+            "timestamp" => Some(self.timestamp.clone()),
+            "build.name" | "build.platform" => {
+                let build = build.ok_or_else(|| format!("${{{}}} can only be used in a build", name))?;
+                Some(if name == "build.name" { build.name.clone() } else { build.platform.clone() })
+            }
+            "git.sha" => Some(self.git_sha().ok_or("${git.sha} needs a git repository with at least one commit")?),
+            "package.version" => {
+                Some(self.package_version.clone().ok_or("${package.version} needs a version in the package section")?)
+            }
+            _ => None,
+        };
+        Ok(value)
+    }
+
+    fn lookup(&self, name: &str, build: Option<&BuildConfig>, stack: &mut Vec<String>) -> Result<String, String> {
+        if let Some(value) = self.builtin(name, build)? {
+            return Ok(value);
+        }
+        if let Some(value) = self.overrides.get(name) {
+            return Ok(value.clone());
+        }
+        if let Ok(value) = std::env::var(name) {
+            return Ok(value);
+        }
+        let value = self.defaults.get(name).ok_or_else(|| {
+            format!(
+                "Undefined variable ${{{}}}; define it in vars:, {} or the environment, or write $${{{}}} for a literal ${{{}}}",
+                name, ENV_FILE, name, name
+            )
+        })?;
+        if stack.iter().any(|n| n == name) {
+            stack.push(name.to_string());
+            return Err(format!("Variables refer to each other in a cycle: {}", stack.join(" -> ")));
+        }
+        stack.push(name.to_string());
+        let expanded = self.expand_with(value, build, stack);
+        stack.pop();
+        expanded
+    }
+
+    fn expand_with(&self, text: &str, build: Option<&BuildConfig>, stack: &mut Vec<String>) -> Result<String, String> {
+        let mut expanded = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            // `$${` escapes the variable
+            if rest[..start].ends_with('$') {
+                expanded.push_str(&rest[..start - 1]);
+                expanded.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            expanded.push_str(&rest[..start]);
+            let name = rest[start + 2..].find('}').map(|end| &rest[start + 2..start + 2 + end]);
+            match name.filter(|name| is_variable_name(name)) {
+                Some(name) => {
+                    expanded.push_str(&self.lookup(name, build, stack)?);
+                    rest = &rest[start + name.len() + 3..];
+                }
+                None => {
+                    expanded.push_str("${");
+                    rest = &rest[start + 2..];
+                }
+            }
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    /// Replace the variables in `text`; `build` provides `${build.name}` and `${build.platform}`
+    pub fn expand(&self, text: &str, build: Option<&BuildConfig>) -> Result<String, String> {
+        self.expand_with(text, build, &mut Vec::new())
+    }
+}
+
+/// Whether `${name}` names a variable: letters, digits, `_` and `.`, not starting with a digit.
+/// Anything else, such as `${1}`, `${f%.c}` or `${var:-x}`, is shell syntax.
+fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Parse `NAME=value` arguments given with `--var`
+pub fn parse_override(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.to_string())),
+        _ => Err(format!("Expected NAME=value, got: {}", arg)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(vars: &[(&str, &str)], overrides: &[(&str, &str)]) -> Variables {
+        let owned = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let dir = std::env::temp_dir().join(format!("build-studio-vars-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let variables = Variables::new(&dir, &owned(vars), &owned(overrides)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        variables
+    }
+
+    #[test]
+    fn expands_variables_in_order_of_precedence() {
+        let vars = variables(&[("out", "${root}/dist"), ("root", "/src"), ("mode", "debug")], &[("mode", "release")]);
+        assert_eq!(vars.expand("make OUT=${out} MODE=${mode}", None).unwrap(), "make OUT=/src/dist MODE=release");
+    }
+
+    #[test]
+    fn leaves_shell_syntax_alone() {
+        let vars = variables(&[("out", "dist")], &[]);
+        let command = r#"for f in *.c; do cc -o "${f%.c}" "$f"; done; echo ${1} ${var:-x} ${#args} $out"#;
+        assert_eq!(vars.expand(command, None).unwrap(), command);
+        assert_eq!(vars.expand("${out}/${name:-app}", None).unwrap(), "dist/${name:-app}");
+        assert_eq!(vars.expand("echo ${unclosed", None).unwrap(), "echo ${unclosed");
+    }
+
+    #[test]
+    fn double_dollar_keeps_a_literal() {
+        let vars = variables(&[("out", "dist")], &[]);
+        assert_eq!(vars.expand("echo $${out} ${out} $${project.dir}", None).unwrap(), "echo ${out} dist ${project.dir}");
+    }
+
+    #[test]
+    fn undefined_variables_are_errors() {
+        let vars = variables(&[], &[]);
+        assert_eq!(
+            vars.expand("make OUT=${build_studio_undefined}", None).unwrap_err(),
+            "Undefined variable ${build_studio_undefined}; define it in vars:, .env or the environment, \
+             or write $${build_studio_undefined} for a literal ${build_studio_undefined}"
+        );
+        assert_eq!(vars.expand("echo $${build_studio_undefined}", None).unwrap(), "echo ${build_studio_undefined}");
+        assert!(vars.expand("${project.dri}", None).unwrap_err().starts_with("Undefined variable ${project.dri}"));
+        assert_eq!(vars.expand("${build.name}", None).unwrap_err(), "${build.name} can only be used in a build");
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let vars = variables(&[("a", "x${b}"), ("b", "${c}"), ("c", "${a}")], &[]);
+        assert_eq!(
+            vars.expand("${a}", None).unwrap_err(),
+            "Variables refer to each other in a cycle: a -> b -> c -> a"
+        );
+    }
+}
@@ -0,0 +1,395 @@
+//! WebAssembly plugin runtime for Build Studio
+//! Commands of `kind: wasm` plugins are WASI (preview 1) command modules run in an embedded
+//! runtime rather than as host processes. A module sees the project at `/project` and its plugin
+//! directory, read-only, at `/plugin`, and nothing else of the host: no other files, none of the
+//! host's environment and no network, which preview 1 has no way to open. Memory and fuel are
+//! limited per run. Compiled modules are cached in the app cache dir.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{self, File};
+use std::path::{Component, Path};
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmtime::{Caller, Config, Engine, Linker, Module, ResourceLimiter, Store, Trap, UpdateDeadline};
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+use wasmtime_wasi::{DirPerms, FilePerms, I32Exit, OutputFile, WasiCtxBuilder};
+use crate::build::{self, BuildOutcome, OutputSink, OutputStream};
+use crate::fsutils;
+use crate::plugin::PluginRun;
+
+/// Where the module sees the project
+pub const GUEST_PROJECT_DIR: &str = "/project";
+/// Where the module sees its plugin directory
+pub const GUEST_PLUGIN_DIR: &str = "/plugin";
+/// Import module of the host functions
+const HOST_MODULE: &str = "build_studio";
+
+/// Limits of a WebAssembly plugin, from the manifest's `wasm:` section
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WasmSettings {
+    /// Largest the module's memory may grow to, in MiB
+    #[serde(default = "default_memory_mb")]
+    pub memory_mb: u64,
+    /// Fuel a run may burn; each WebAssembly instruction costs about one unit
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+}
+
+fn default_memory_mb() -> u64 {
+    256
+}
+
+fn default_fuel() -> u64 {
+    10_000_000_000
+}
+
+impl Default for WasmSettings {
+    fn default() -> Self {
+        Self {
+            memory_mb: default_memory_mb(),
+            fuel: default_fuel(),
+        }
+    }
+}
+
+/// Why a run was interrupted
+const RUNNING: u8 = 0;
+const CANCELLED: u8 = 1;
+const TIMED_OUT: u8 = 2;
+
+/// Error a run stops with when the module grows its memory past `memory_mb`
+#[derive(Debug)]
+struct MemoryLimitReached;
+
+impl fmt::Display for MemoryLimitReached {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "memory limit reached")
+    }
+}
+
+impl std::error::Error for MemoryLimitReached {}
+
+/// Caps the memory of a run; growing past it stops the run rather than failing the grow, so
+/// the user learns which limit to raise
+struct MemoryLimit {
+    bytes: usize,
+}
+
+impl ResourceLimiter for MemoryLimit {
+    fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> wasmtime::Result<bool> {
+        if desired > self.bytes {
+            return Err(MemoryLimitReached.into());
+        }
+        Ok(true)
+    }
+
+    fn table_growing(&mut self, _current: usize, _desired: usize, _maximum: Option<usize>) -> wasmtime::Result<bool> {
+        Ok(true)
+    }
+}
+
+/// Per-run state the host functions and the runtime work with
+struct RunState {
+    wasi: WasiP1Ctx,
+    limits: MemoryLimit,
+    sink: OutputSink,
+    /// Paths the module reported with `report_artifact`, relative to the project
+    artifacts: Vec<String>,
+    interrupt: Arc<AtomicU8>,
+}
+
+/// The engine every module runs on; fuel and epochs are enabled to bound and interrupt runs
+fn engine() -> Result<Engine, String> {
+    static ENGINE: OnceLock<Result<Engine, String>> = OnceLock::new();
+    ENGINE
+        .get_or_init(|| {
+            let mut config = Config::new();
+            config.consume_fuel(true).epoch_interruption(true);
+            Engine::new(&config).map_err(|e| format!("Failed to start the WebAssembly runtime: {}", e))
+        })
+        .clone()
+}
+
+/// Compile `path`, or load it from the cache of compiled modules
+fn load_module(engine: &Engine, path: &Path) -> Result<Module, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let key = format!("{:x}", Sha256::new().chain_update(&bytes).chain_update(env!("CARGO_PKG_VERSION")).finalize());
+    let cached = fsutils::app_cache_dir().map(|dir| dir.join("wasm").join(format!("{}.cwasm", key)));
+    if let Some(cached) = cached.as_ref().filter(|p| p.is_file()) {
+        // SAFETY: the file was written by `Module::serialize` below, into the user's own cache
+        // dir; a file from another runtime version or config is rejected by `deserialize_file`
+        if let Ok(module) = unsafe { Module::deserialize_file(engine, cached) } {
+            return Ok(module);
+        }
+    }
+    let module = Module::new(engine, &bytes).map_err(|e| format!("Failed to compile {}: {:#}", path.display(), e))?;
+    if let (Some(cached), Ok(compiled)) = (cached, module.serialize()) {
+        // A missing cache only costs a compile next time
+        let _ = cached.parent().map(fs::create_dir_all).transpose().and_then(|_| fs::write(&cached, compiled));
+    }
+    Ok(module)
+}
+
+/// The `len` bytes at `ptr` in the calling module's memory, as text
+fn guest_str(caller: &mut Caller<'_, RunState>, ptr: i32, len: i32) -> Option<String> {
+    let memory = caller.get_export("memory")?.into_memory()?;
+    let start = usize::try_from(ptr).ok()?;
+    let bytes = memory.data(&caller).get(start..start.checked_add(usize::try_from(len).ok()?)?)?;
+    Some(String::from_utf8_lossy(bytes).to_string())
+}
+
+/// A reported artifact as a path relative to the project, if it stays inside it
+fn artifact_path(path: &str) -> Option<String> {
+    let relative = path.strip_prefix(GUEST_PROJECT_DIR).unwrap_or(path);
+    let mut parts = Vec::new();
+    for component in Path::new(relative).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::RootDir | Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// The WASI imports and the host functions modules may import from `build_studio`:
+/// - `log(stream, ptr, len)` writes a line to the build output, on stderr if `stream` is 1
+/// - `report_artifact(ptr, len) -> i32` keeps a file of the project as an artifact of the build;
+///   returns 0, or -1 for a path outside the project
+fn linker(engine: &Engine) -> Result<Linker<RunState>, String> {
+    let mut linker = Linker::new(engine);
+    preview1::add_to_linker_sync(&mut linker, |state: &mut RunState| &mut state.wasi)
+        .map_err(|e| format!("Failed to set up WASI: {}", e))?;
+    linker
+        .func_wrap(HOST_MODULE, "log", |mut caller: Caller<'_, RunState>, stream: i32, ptr: i32, len: i32| {
+            let text = guest_str(&mut caller, ptr, len).ok_or_else(|| wasmtime::Error::msg("log: text out of bounds"))?;
+            let stream = if stream == 1 { OutputStream::Stderr } else { OutputStream::Stdout };
+            let sink = caller.data().sink.clone();
+            for line in text.lines() {
+                sink(stream, line.to_string());
+            }
+            Ok(())
+        })
+        .and_then(|linker| {
+            linker.func_wrap(HOST_MODULE, "report_artifact", |mut caller: Caller<'_, RunState>, ptr: i32, len: i32| {
+                let path = guest_str(&mut caller, ptr, len).ok_or_else(|| wasmtime::Error::msg("report_artifact: path out of bounds"))?;
+                Ok(match artifact_path(&path) {
+                    Some(path) => {
+                        caller.data_mut().artifacts.push(path);
+                        0
+                    }
+                    None => -1,
+                })
+            })
+        })
+        .map_err(|e| format!("Failed to set up host functions: {}", e))?;
+    Ok(linker)
+}
+
+/// Exit status of a module that called `proc_exit(code)`
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+    ExitStatus::from_raw(code as u32)
+}
+
+/// A pipe whose write end the module's stdout or stderr writes to
+fn output_pipe() -> Result<(std::io::PipeReader, File), String> {
+    let (reader, writer) = std::io::pipe().map_err(|e| format!("Failed to create output pipe: {}", e))?;
+    #[cfg(unix)]
+    let writer = File::from(std::os::fd::OwnedFd::from(writer));
+    #[cfg(windows)]
+    let writer = File::from(std::os::windows::io::OwnedHandle::from(writer));
+    Ok((reader, writer))
+}
+
+/// Run a WebAssembly plugin command on the project in `work_dir`, streaming its output to `sink`
+/// until it exits, runs out of fuel or memory, times out or `cancel` is set. Returns how it ended
+/// and the artifacts it reported.
+pub fn run(
+    run: &PluginRun,
+    settings: &WasmSettings,
+    work_dir: &Path,
+    sink: OutputSink,
+    cancel: &AtomicBool,
+    max_build_time: Option<u32>,
+) -> Result<(BuildOutcome, Vec<String>), String> {
+    let engine = engine()?;
+    let module = load_module(&engine, &run.script)?;
+    let linker = linker(&engine)?;
+
+    let guest_output = format!("{}/builds/{}", GUEST_PROJECT_DIR, run.platform);
+    let script = run.script.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let mut argv = vec![script, run.command.clone(), run.platform.clone(), GUEST_PROJECT_DIR.to_string(), guest_output];
+    argv.extend(run.args.iter().cloned());
+    let (stdout, stdout_writer) = output_pipe()?;
+    let (stderr, stderr_writer) = output_pipe()?;
+
+    let mut wasi = WasiCtxBuilder::new();
+    wasi.args(&argv)
+        .env("BUILD_STUDIO_VERSION", env!("CARGO_PKG_VERSION"))
+        .env("PLUGIN_DIR", GUEST_PLUGIN_DIR)
+        .env("PROJECT_DIR", GUEST_PROJECT_DIR)
+        .stdout(OutputFile::new(stdout_writer))
+        .stderr(OutputFile::new(stderr_writer));
+    wasi.preopened_dir(work_dir, GUEST_PROJECT_DIR, DirPerms::all(), FilePerms::all())
+        .and_then(|wasi| wasi.preopened_dir(&run.plugin_dir, GUEST_PLUGIN_DIR, DirPerms::READ, FilePerms::READ))
+        .map_err(|e| format!("Failed to give the plugin its directories: {}", e))?;
+
+    let interrupt = Arc::new(AtomicU8::new(RUNNING));
+    let state = RunState {
+        wasi: wasi.build_p1(),
+        limits: MemoryLimit {
+            bytes: usize::try_from(settings.memory_mb.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX),
+        },
+        sink: sink.clone(),
+        artifacts: Vec::new(),
+        interrupt: interrupt.clone(),
+    };
+    let mut store = Store::new(&engine, state);
+    store.limiter(|state| &mut state.limits);
+    store.set_fuel(settings.fuel).map_err(|e| format!("Failed to set fuel: {}", e))?;
+    // Checked on every epoch tick: stop once the run is cancelled or out of time
+    store.set_epoch_deadline(1);
+    store.epoch_deadline_callback(|context| match context.data().interrupt.load(Ordering::SeqCst) {
+        RUNNING => Ok(UpdateDeadline::Continue(1)),
+        _ => Err(Trap::Interrupt.into()),
+    });
+
+    let readers = [
+        build::stream_lines(stdout, OutputStream::Stdout, sink.clone()),
+        build::stream_lines(stderr, OutputStream::Stderr, sink.clone()),
+    ];
+    let done = AtomicBool::new(false);
+    let deadline = max_build_time.map(|secs| Instant::now() + Duration::from_secs(secs.into()));
+    let result = thread::scope(|scope| {
+        scope.spawn(|| {
+            while !done.load(Ordering::SeqCst) {
+                if cancel.load(Ordering::SeqCst) {
+                    interrupt.store(CANCELLED, Ordering::SeqCst);
+                } else if deadline.is_some_and(|d| Instant::now() >= d) {
+                    interrupt.store(TIMED_OUT, Ordering::SeqCst);
+                }
+                engine.increment_epoch();
+                thread::sleep(build::POLL_INTERVAL);
+            }
+        });
+        let result = linker.instantiate(&mut store, &module).and_then(|instance| {
+            let start = instance.get_typed_func::<(), ()>(&mut store, "_start").map_err(|_| {
+                wasmtime::Error::msg("the module exports no _start function; build it as a WASI command")
+            })?;
+            start.call(&mut store, ())
+        });
+        done.store(true, Ordering::SeqCst);
+        result
+    });
+    let artifacts = std::mem::take(&mut store.data_mut().artifacts);
+    // Closes the module's end of the output pipes so the readers finish
+    drop(store);
+    for reader in readers {
+        let _ = reader.join();
+    }
+
+    let outcome = match result {
+        Ok(()) => BuildOutcome::Success,
+        Err(e) => match (e.downcast_ref::<I32Exit>(), e.downcast_ref::<Trap>()) {
+            (Some(I32Exit(0)), _) => BuildOutcome::Success,
+            (Some(I32Exit(code)), _) => BuildOutcome::Failure(exit_status(*code)),
+            (_, Some(Trap::Interrupt)) if interrupt.load(Ordering::SeqCst) == CANCELLED => BuildOutcome::Cancelled,
+            (_, Some(Trap::Interrupt)) => BuildOutcome::Timeout,
+            (_, Some(Trap::OutOfFuel)) => {
+                return Err(format!(
+                    "Plugin '{}' ran out of fuel after {} units; raise wasm.fuel in its manifest",
+                    run.plugin, settings.fuel
+                ))
+            }
+            _ if e.downcast_ref::<MemoryLimitReached>().is_some() => {
+                return Err(format!(
+                    "Plugin '{}' needs more than {} MiB of memory; raise wasm.memory_mb in its manifest",
+                    run.plugin, settings.memory_mb
+                ))
+            }
+            _ => return Err(format!("Plugin '{}' failed: {:#}", run.plugin, e)),
+        },
+    };
+    Ok((outcome, artifacts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the module `wat` as a plugin command with `settings`
+    fn run_wat(wat: &str, settings: WasmSettings, cancel: bool) -> Result<BuildOutcome, String> {
+        let dir = std::env::temp_dir().join(format!("build-studio-wasm-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("command.wasm");
+        fs::write(&script, wat::parse_str(wat).unwrap()).unwrap();
+        let plugin_run = PluginRun {
+            plugin: "test".to_string(),
+            command: "build".to_string(),
+            platform: "wasm".to_string(),
+            plugin_dir: dir.clone(),
+            script,
+            interpreter: Vec::new(),
+            wasm: Some(settings.clone()),
+            rpc: None,
+            args: Vec::new(),
+        };
+        let sink: OutputSink = Arc::new(|_, _| {});
+        let result = run(&plugin_run, &settings, &dir, sink, &AtomicBool::new(cancel), None);
+        fs::remove_dir_all(&dir).unwrap();
+        result.map(|(outcome, _)| outcome)
+    }
+
+    const SPIN: &str = r#"(module (func (export "_start") (loop (br 0))))"#;
+
+    #[test]
+    fn returning_from_start_succeeds() {
+        let outcome = run_wat(r#"(module (func (export "_start")))"#, WasmSettings::default(), false);
+        assert!(matches!(outcome, Ok(BuildOutcome::Success)));
+    }
+
+    #[test]
+    fn running_out_of_fuel_stops_the_run() {
+        let settings = WasmSettings { fuel: 10_000, ..WasmSettings::default() };
+        let error = run_wat(SPIN, settings, false).unwrap_err();
+        assert_eq!(error, "Plugin 'test' ran out of fuel after 10000 units; raise wasm.fuel in its manifest");
+    }
+
+    #[test]
+    fn growing_past_the_memory_limit_stops_the_run() {
+        // 32 pages of 64 KiB are 2 MiB
+        let grow = r#"(module (memory 1) (func (export "_start") (drop (memory.grow (i32.const 32)))))"#;
+        let settings = WasmSettings { memory_mb: 1, ..WasmSettings::default() };
+        let error = run_wat(grow, settings, false).unwrap_err();
+        assert_eq!(error, "Plugin 'test' needs more than 1 MiB of memory; raise wasm.memory_mb in its manifest");
+
+        let within = WasmSettings { memory_mb: 4, ..WasmSettings::default() };
+        assert!(matches!(run_wat(grow, within, false), Ok(BuildOutcome::Success)));
+    }
+
+    #[test]
+    fn cancelling_interrupts_the_run() {
+        let settings = WasmSettings { fuel: u64::MAX, ..WasmSettings::default() };
+        assert!(matches!(run_wat(SPIN, settings, true), Ok(BuildOutcome::Cancelled)));
+    }
+
+    #[test]
+    fn manifests_cannot_ask_for_the_network() {
+        let parsed = serde_json::from_str::<WasmSettings>(r#"{"network": true}"#);
+        assert!(parsed.unwrap_err().to_string().starts_with("unknown field `network`"));
+    }
+}
@@ -2,10 +2,36 @@ import { writable } from 'svelte/store';
 
 export interface BuildConfig {
   name: string;
+  extends?: string;
   platform: string;
   language?: string;
   command: string;
+  // Downloads dependencies with network access before `command` runs
+  fetch?: string;
+  // `<plugin>/<command>` run instead of `command`
+  plugin?: string;
   container?: string;
+  toolchain?: string;
+  target?: string;
+  artifacts?: string[];
+  depends_on?: string[];
+  matrix?: Record<string, unknown>;
+  // Set on builds generated from a matrix: the build declaring it and this build's axis values
+  matrix_cell?: { build: string; values: Record<string, string> };
+}
+
+export interface ToolchainProfile {
+  name: string;
+  target?: string;
+  cc?: string;
+  cxx?: string;
+  ar?: string;
+  ld?: string;
+  sysroot?: string;
+  path?: string[];
+  goos?: string;
+  goarch?: string;
+  env?: Record<string, string>;
 }
 
 export interface PackageConfig {
@@ -16,8 +42,14 @@ export interface PackageConfig {
 }
 
 export interface BuildStudioConfig {
+  vars?: Record<string, string>;
   builds: BuildConfig[];
   package?: PackageConfig;
+  toolchains?: ToolchainProfile[];
 }
 
 export const buildConfig = writable<BuildStudioConfig | null>(null);
+
+// Profiles the project's config defines, and the one builds run with
+export const profiles = writable<string[]>([]);
+export const selectedProfile = writable<string | null>(null);
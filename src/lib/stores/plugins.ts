@@ -1,12 +1,49 @@
 import { writable } from 'svelte/store';
 import { invoke } from '@tauri-apps/api/core';
 
+export interface PluginCommand {
+  name: string;
+  description: string;
+  script: string;
+  // Request a 'jsonrpc' plugin gets for this command
+  method: 'build' | 'package';
+}
+
+export type DependencyStatus = 'ready' | 'missing-tool' | 'version-mismatch' | 'invalid-requirement';
+
+export interface DependencyCheck {
+  requirement: string;
+  tool: string;
+  status: DependencyStatus;
+  found: string | null;
+  message: string | null;
+}
+
+export interface WasmSettings {
+  memory_mb: number;
+  fuel: number;
+}
+
 export interface PluginInfo {
   name: string;
   author: string;
   version: string;
   description: string;
+  // 'wasm' plugins run WebAssembly modules in the embedded runtime
+  kind: 'script' | 'wasm';
+  wasm: WasmSettings;
+  // 'jsonrpc' plugins keep their server script running and get commands as JSON-RPC requests
+  protocol: 'args' | 'jsonrpc';
+  server?: string;
   path: string;
+  commands: PluginCommand[];
+  platforms: string[];
+  dependencies: string[];
+  status: DependencyStatus;
+  dependency_checks: DependencyCheck[];
+  // Installed into the app config dir; only installed plugins can be disabled or removed
+  installed: boolean;
+  enabled: boolean;
 }
 
 export const plugins = writable<PluginInfo[]>([]);
@@ -22,25 +59,55 @@ export async function loadPlugins(pluginDir: string) {
   }
 }
 
-// Add a plugin by providing its file path
+// Run a plugin command on a project; output arrives as build-* events like run_build
+export async function runPluginCommand(
+  plugin: string,
+  command: string,
+  platform: string,
+  cwd: string,
+  args: string[] = [],
+  pluginDir?: string
+) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  await invoke('run_plugin_command', { plugin, command, platform, args, cwd, pluginDir });
+}
+
+export interface PluginProvides {
+  toolchains: string[];
+  packagers: string[];
+  hooks: string[];
+}
+
+// Ask a JSON-RPC plugin which toolchains, packagers and hooks it contributes
+export async function pluginProvides(plugin: string, cwd: string, pluginDir?: string) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  return invoke<PluginProvides>('plugin_provides', { plugin, cwd, pluginDir });
+}
+
+// Install a plugin from a .zip, .tar.gz or git checkout
 export async function addPlugin(pluginPath: string) {
-  try {
-    const { invoke } = await import('@tauri-apps/api/core');
-    await invoke('add_plugin', { path: pluginPath });
-    // Reload plugins after addition
-    await loadPlugins('../../plugins');
-  } catch (error) {
-    console.error('Failed to add plugin:', error);
-  }
+  const { invoke } = await import('@tauri-apps/api/core');
+  await invoke('install_plugin', { path: pluginPath });
+  // Reload plugins after addition
+  await loadPlugins('../../plugins');
 }
 
-// Remove a plugin by name
+// Uninstall a plugin by name, with every version kept of it
 export async function removePlugin(name: string) {
-  try {
-    const { invoke } = await import('@tauri-apps/api/core');
-    await invoke('remove_plugin', { name });
-    await loadPlugins('../../plugins');
-  } catch (error) {
-    console.error('Failed to remove plugin:', error);
-  }
+  const { invoke } = await import('@tauri-apps/api/core');
+  await invoke('uninstall_plugin', { name });
+  await loadPlugins('../../plugins');
+}
+
+export async function setPluginEnabled(name: string, enabled: boolean) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  await invoke(enabled ? 'enable_plugin' : 'disable_plugin', { name });
+  await loadPlugins('../../plugins');
+}
+
+// Return an installed plugin to the version its last upgrade replaced
+export async function rollbackPlugin(name: string) {
+  const { invoke } = await import('@tauri-apps/api/core');
+  await invoke('rollback_plugin', { name });
+  await loadPlugins('../../plugins');
 }